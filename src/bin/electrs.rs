@@ -61,7 +61,6 @@ fn run_server(config: Arc<Config>) -> Result<()> {
         &config,
         &metrics,
     );
-    let mut tip = indexer.update(&daemon)?;
 
     let chain = Arc::new(ChainQuery::new(
         Arc::clone(&store),
@@ -75,18 +74,6 @@ fn run_server(config: Arc<Config>) -> Result<()> {
         &metrics,
         Arc::clone(&config),
     )));
-    loop {
-        match Mempool::update(&mempool, &daemon) {
-            Ok(_) => break,
-            Err(e) => {
-                warn!(
-                    "Error performing initial mempool update, trying again in 5 seconds: {}",
-                    e.display_chain()
-                );
-                signal.wait(Duration::from_secs(5), false)?;
-            }
-        }
-    }
 
     #[cfg(feature = "liquid")]
     let asset_db = config.asset_db_path.as_ref().map(|db_dir| {
@@ -100,12 +87,32 @@ fn run_server(config: Arc<Config>) -> Result<()> {
         Arc::clone(&mempool),
         Arc::clone(&daemon),
         Arc::clone(&config),
+        &metrics,
         #[cfg(feature = "liquid")]
         asset_db,
     ));
 
     // TODO: configuration for which servers to start
+    // The REST server starts before the initial sync, to report its progress. /ready tells
+    // whether the index caught up, and data routes respond with 503 until the first update
+    // completed and the mempool was loaded.
     let rest_server = rest::start(Arc::clone(&config), Arc::clone(&query), &metrics);
+
+    let mut tip = indexer.update(&daemon)?;
+
+    loop {
+        match Mempool::update(&mempool, &daemon) {
+            Ok(_) => break,
+            Err(e) => {
+                warn!(
+                    "Error performing initial mempool update, trying again in 5 seconds: {}",
+                    e.display_chain()
+                );
+                signal.wait(Duration::from_secs(5), false)?;
+            }
+        }
+    }
+
     let electrum_server = ElectrumRPC::start(Arc::clone(&config), Arc::clone(&query), &metrics);
 
     if let Some(ref precache_file) = config.precache_scripts {
@@ -142,18 +149,25 @@ fn run_server(config: Arc<Config>) -> Result<()> {
 
         // Index new blocks
         let current_tip = daemon.getbestblockhash()?;
+        let mut updated = current_tip != tip;
         if current_tip != tip {
             indexer.update(&daemon)?;
             tip = current_tip;
         };
 
         // Update mempool
-        if let Err(e) = Mempool::update(&mempool, &daemon) {
+        match Mempool::update(&mempool, &daemon) {
+            Ok((added, removed)) => updated |= added + removed > 0,
             // Log the error if the result is an Err
-            warn!(
+            Err(e) => warn!(
                 "Error updating mempool, skipping mempool update: {}",
                 e.display_chain()
-            );
+            ),
+        }
+
+        // Wake up REST requests waiting for updates
+        if updated {
+            query.notify_updated();
         }
 
         // Update subscribed clients
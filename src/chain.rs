@@ -81,6 +81,23 @@ impl Network {
         }
     }
 
+    /// The block subsidy (in satoshis) for the block at the given height
+    #[cfg(not(feature = "liquid"))]
+    pub fn block_subsidy(self, height: usize) -> u64 {
+        let halving_interval = if self.is_regtest() { 150 } else { 210_000 };
+        let halvings = height / halving_interval;
+        if halvings >= 64 {
+            return 0;
+        }
+        (50 * 100_000_000u64) >> halvings
+    }
+
+    /// Elements-based networks have no block subsidy
+    #[cfg(feature = "liquid")]
+    pub fn block_subsidy(self, _height: usize) -> u64 {
+        0
+    }
+
     #[cfg(feature = "liquid")]
     pub fn address_params(self) -> &'static address::AddressParams {
         // Liquid regtest uses elements's address params
@@ -124,6 +141,29 @@ impl Network {
             "liquidregtest".to_string(),
         ];
     }
+
+    /// The name the network is selected with, as accepted by Network::from()
+    pub fn name(self) -> &'static str {
+        match self {
+            #[cfg(not(feature = "liquid"))]
+            Network::Bitcoin => "mainnet",
+            #[cfg(not(feature = "liquid"))]
+            Network::Testnet => "testnet",
+            #[cfg(not(feature = "liquid"))]
+            Network::Testnet4 => "testnet4",
+            #[cfg(not(feature = "liquid"))]
+            Network::Regtest => "regtest",
+            #[cfg(not(feature = "liquid"))]
+            Network::Signet => "signet",
+
+            #[cfg(feature = "liquid")]
+            Network::Liquid => "liquid",
+            #[cfg(feature = "liquid")]
+            Network::LiquidTestnet => "liquidtestnet",
+            #[cfg(feature = "liquid")]
+            Network::LiquidRegtest => "liquidregtest",
+        }
+    }
 }
 
 pub fn genesis_hash(network: Network) -> BlockHash {
@@ -1,9 +1,12 @@
 use clap::{App, Arg};
 use dirs::home_dir;
+use std::ffi::OsString;
 use std::fs;
+use std::net::IpAddr;
 use std::net::SocketAddr;
 use std::net::ToSocketAddrs;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::sync::Arc;
 use stderrlog;
 
@@ -40,28 +43,62 @@ pub struct Config {
     pub daemon_rpc_addr: SocketAddr,
     pub cookie: Option<String>,
     pub electrum_rpc_addr: SocketAddr,
-    pub http_addr: SocketAddr,
+    pub http_addrs: Vec<SocketAddr>,
     pub http_socket_file: Option<PathBuf>,
+    pub http_tls_cert: Option<PathBuf>,
+    pub http_tls_key: Option<PathBuf>,
     pub rpc_socket_file: Option<PathBuf>,
     pub monitoring_addr: SocketAddr,
     pub jsonrpc_import: bool,
     pub light_mode: bool,
     pub main_loop_delay: u64,
     pub address_search: bool,
+    pub txid_search: bool,
     pub index_unspendables: bool,
     pub cors: Option<String>,
     pub precache_scripts: Option<String>,
     pub precache_threads: usize,
     pub utxos_limit: usize,
+    pub max_utxos_per_query: usize,
     pub electrum_txs_limit: usize,
     pub electrum_banner: String,
     pub mempool_backlog_stats_ttl: u64,
     pub mempool_recent_txs_size: usize,
+    pub mempool_conflicts_history_size: usize,
     pub rest_default_block_limit: usize,
     pub rest_default_chain_txs_per_page: usize,
+    pub rest_max_chain_txs_per_page: usize,
+    pub rest_block_tx_fees: bool,
+    pub rest_block_audit: bool,
+    pub rest_inscriptions: bool,
+    pub rest_json_errors: bool,
+    pub http_access_log: bool,
+    pub http2_enabled: bool,
+    pub rest_compression: bool,
+    pub rest_compress_binary: bool,
+    pub rest_admin_token: Option<String>,
+    pub internal_api_access: InternalApiAccess,
+    pub rest_rate_limit: Option<f64>,
+    pub rest_rate_limit_burst: u32,
+    pub rest_rate_limit_max_clients: usize,
+    pub rest_trusted_proxy_header: Option<String>,
+    pub rest_disabled_endpoints: Vec<String>,
     pub rest_default_max_mempool_txs: usize,
     pub rest_default_max_address_summary_txs: usize,
     pub rest_max_mempool_page_size: usize,
+    pub rest_default_outspends_limit: usize,
+    pub rest_max_outspends_limit: usize,
+    pub rest_max_ancestry_depth: usize,
+    pub rest_ready_max_lag: usize,
+    pub rest_max_lag_blocks: Option<usize>,
+    pub rest_request_timeout: u64,
+    pub rest_heavy_concurrency: usize,
+    pub rest_heavy_queue_timeout: u64,
+    pub rest_sse_max_connections: usize,
+    pub rest_sse_max_addresses: usize,
+    pub rest_ws_max_connections: usize,
+    pub rest_max_body_size: usize,
+    pub rest_max_tx_body_size: usize,
     pub rest_max_mempool_txid_page_size: usize,
 
     #[cfg(feature = "liquid")]
@@ -77,6 +114,95 @@ pub struct Config {
     pub tor_proxy: Option<std::net::SocketAddr>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpCidr {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpCidr {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        // clients on a dual-stack listener show up as v4-mapped v6 addresses
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+            ip => ip,
+        };
+        match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpCidr {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (addr, prefix_len) = match s.split_once('/') {
+            Some((addr, len)) => (addr, Some(len)),
+            None => (s, None),
+        };
+        let addr: IpAddr = addr
+            .parse()
+            .map_err(|_| format!("invalid IP address in '{}'", s))?;
+        let max_len = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix_len {
+            Some(len) => len
+                .parse::<u8>()
+                .ok()
+                .filter(|len| *len <= max_len)
+                .ok_or_else(|| format!("invalid prefix length in '{}'", s))?,
+            None => max_len,
+        };
+        Ok(IpCidr { addr, prefix_len })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InternalApiAccess {
+    UnixOnly,
+    Any,
+    Cidrs(Vec<IpCidr>),
+}
+
+impl InternalApiAccess {
+    // whether a TCP listener on `addr` exposes the internal routes beyond the local host
+    pub fn is_world_reachable(&self, addr: &SocketAddr) -> bool {
+        match self {
+            InternalApiAccess::UnixOnly => false,
+            InternalApiAccess::Any => !addr.ip().is_loopback(),
+            InternalApiAccess::Cidrs(cidrs) => cidrs.iter().any(|cidr| cidr.prefix_len == 0),
+        }
+    }
+}
+
+impl FromStr for InternalApiAccess {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim() {
+            "unix-only" => Ok(InternalApiAccess::UnixOnly),
+            "any" => Ok(InternalApiAccess::Any),
+            list => list
+                .split(',')
+                .map(|cidr| cidr.trim().parse())
+                .collect::<std::result::Result<Vec<IpCidr>, _>>()
+                .map(InternalApiAccess::Cidrs),
+        }
+    }
+}
+
 fn str_to_socketaddr(address: &str, what: &str) -> SocketAddr {
     address
         .to_socket_addrs()
@@ -88,6 +214,18 @@ fn str_to_socketaddr(address: &str, what: &str) -> SocketAddr {
 
 impl Config {
     pub fn from_args() -> Config {
+        let mut config = Config::parse_from(std::env::args_os());
+        config.log.init().expect("logging initialization failed");
+        config
+    }
+
+    /// Parse the configuration from a list of command line arguments, starting with the binary
+    /// name. Unlike `from_args`, this doesn't set up logging.
+    pub fn parse_from<I, T>(argv: I) -> Config
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
         let network_help = format!("Select network type ({})", Network::names().join(", "));
 
         let args = App::new("Mempool Electrum Rust Server")
@@ -153,8 +291,30 @@ impl Config {
             .arg(
                 Arg::with_name("http_addr")
                     .long("http-addr")
-                    .help("HTTP server 'addr:port' to listen on (default: '127.0.0.1:3000' for mainnet, '127.0.0.1:3001' for testnet and '127.0.0.1:3002' for regtest)")
-                    .takes_value(true),
+                    .help("HTTP server 'addr:port' to listen on, can be given multiple times or as a comma-separated list (default: '127.0.0.1:3000' for mainnet, '127.0.0.1:3001' for testnet and '127.0.0.1:3002' for regtest, unless --http-socket-file is set)")
+                    .takes_value(true)
+                    .multiple(true)
+                    .number_of_values(1)
+                    .use_delimiter(true),
+            )
+            .arg(
+                Arg::with_name("http_tls_cert")
+                    .long("http-tls-cert")
+                    .help("PEM certificate chain to serve the REST API over HTTPS with (requires --http-tls-key)")
+                    .takes_value(true)
+                    .requires("http_tls_key"),
+            )
+            .arg(
+                Arg::with_name("http_tls_key")
+                    .long("http-tls-key")
+                    .help("PEM private key for --http-tls-cert")
+                    .takes_value(true)
+                    .requires("http_tls_cert"),
+            )
+            .arg(
+                Arg::with_name("http2")
+                    .long("http2")
+                    .help("Serve the REST API over HTTP/2 too, negotiated with ALPN over TLS or with prior knowledge (h2c) over plaintext")
             )
             .arg(
                 Arg::with_name("daemon_rpc_addr")
@@ -189,15 +349,103 @@ impl Config {
                     .long("address-search")
                     .help("Enable prefix address search")
             )
+            .arg(
+                Arg::with_name("txid_search")
+                    .long("txid-search")
+                    .help("Enable prefix txid search (only covers transactions indexed while enabled)")
+            )
             .arg(
                 Arg::with_name("index_unspendables")
                     .long("index-unspendables")
                     .help("Enable indexing of provably unspendable outputs")
             )
+            .arg(
+                Arg::with_name("rest_block_tx_fees")
+                    .long("rest-block-tx-fees")
+                    .help("Enable the block/:hash/tx-fees endpoint, which is expensive to compute for uncached blocks")
+            )
+            .arg(
+                Arg::with_name("rest_block_audit")
+                    .long("rest-block-audit")
+                    .help("Enable the block/:hash/audit-summary endpoint, which re-reads the whole block to check its stored metadata")
+            )
+            .arg(
+                Arg::with_name("rest_inscriptions")
+                    .long("rest-inscriptions")
+                    .help("Detect ord inscription envelopes in taproot script path spends and report their content type and length on inputs")
+            )
+            .arg(
+                Arg::with_name("http_access_log")
+                    .long("http-access-log")
+                    .help("Log a line for every HTTP request, under the electrs::http_access target")
+            )
+            .arg(
+                Arg::with_name("rest_json_errors")
+                    .long("rest-json-errors")
+                    .help("Always return REST errors as JSON, rather than only to clients that send Accept: application/json")
+            )
+            .arg(
+                Arg::with_name("rest_compression")
+                    .long("rest-compression")
+                    .help("Compress REST responses with gzip or brotli for clients that accept it")
+            )
+            .arg(
+                Arg::with_name("rest_compress_binary")
+                    .long("rest-compress-binary")
+                    .help("Also compress application/octet-stream responses (raw blocks and transactions), which are skipped by default")
+                    .requires("rest_compression")
+            )
+            .arg(
+                Arg::with_name("rest_admin_token")
+                    .long("rest-admin-token")
+                    .help("Secret required in the X-Admin-Token header to use admin endpoints over TCP (admin endpoints are always allowed over the unix socket)")
+                    .takes_value(true)
+            )
+            .arg(
+                Arg::with_name("internal_api_access")
+                    .long("internal-api-access")
+                    .help("Who may use the /internal REST routes: 'unix-only', 'any', or a comma-separated list of CIDRs (e.g. '127.0.0.1/32,10.0.0.0/8')")
+                    .takes_value(true)
+                    .default_value("any")
+            )
+            .arg(
+                Arg::with_name("rest_rate_limit")
+                    .long("rest-rate-limit")
+                    .help("Maximum sustained number of REST requests per second per client IP. Requests over the limit get 429 Too Many Requests [default: no limit]")
+                    .takes_value(true)
+            )
+            .arg(
+                Arg::with_name("rest_rate_limit_burst")
+                    .long("rest-rate-limit-burst")
+                    .help("Number of REST requests a client IP can make in a burst before being held to --rest-rate-limit")
+                    .takes_value(true)
+                    .default_value("20")
+            )
+            .arg(
+                Arg::with_name("rest_rate_limit_max_clients")
+                    .long("rest-rate-limit-max-clients")
+                    .help("Maximum number of client IPs tracked by the rate limiter, the least recently seen ones are forgotten first")
+                    .takes_value(true)
+                    .default_value("100000")
+            )
+            .arg(
+                Arg::with_name("rest_trusted_proxy_header")
+                    .long("rest-trusted-proxy-header")
+                    .help("Header set by a trusted reverse proxy to identify the client IP (e.g. X-Forwarded-For), the last address in it is used for rate limiting")
+                    .takes_value(true)
+            )
+            .arg(
+                Arg::with_name("rest_disabled_endpoints")
+                    .long("rest-disabled-endpoints")
+                    .help("Comma-separated REST route prefixes to disable, optionally restricted to a method (e.g. 'POST /tx,/internal'). Disabled routes respond with 403 Forbidden")
+                    .takes_value(true)
+                    .multiple(true)
+                    .use_delimiter(true)
+            )
             .arg(
                 Arg::with_name("cors")
                     .long("cors")
-                    .help("Origins allowed to make cross-site requests")
+                    .help("Comma-separated list of origins allowed to make cross-site requests, or * for any origin")
                     .takes_value(true)
             )
             .arg(
@@ -218,6 +466,12 @@ impl Config {
                     .help("Maximum number of utxos to process per address. Lookups for addresses with more utxos will fail. Applies to the Electrum and HTTP APIs.")
                     .default_value("500")
             )
+            .arg(
+                Arg::with_name("max_utxos_per_query")
+                    .long("max-utxos-per-query")
+                    .help("Maximum number of utxos (confirmed and unconfirmed) returned by a single utxo lookup. Scans exceeding it are aborted. Applies to the Electrum and HTTP APIs.")
+                    .default_value("500")
+            )
             .arg(
                 Arg::with_name("mempool_backlog_stats_ttl")
                     .long("mempool-backlog-stats-ttl")
@@ -230,6 +484,12 @@ impl Config {
                     .help("The number of transactions that mempool will keep in its recents queue. This is returned by mempool/recent endpoint.")
                     .default_value("10")
             )
+            .arg(
+                Arg::with_name("mempool_conflicts_history_size")
+                    .long("mempool-conflicts-history-size")
+                    .help("The number of replaced or evicted mempool transactions to remember. This is returned by tx/:txid/conflicts endpoint.")
+                    .default_value("10000")
+            )
             .arg(
                 Arg::with_name("rest_default_block_limit")
                     .long("rest-default-block-limit")
@@ -242,6 +502,12 @@ impl Config {
                     .help("The default number of on-chain transactions returned by the txs endpoints.")
                     .default_value("25")
             )
+            .arg(
+                Arg::with_name("rest_max_chain_txs_per_page")
+                    .long("rest-max-chain-txs-per-page")
+                    .help("The maximum number of on-chain transactions returned by the txs/chain endpoints, regardless of the requested max_txs.")
+                    .default_value("1000")
+            )
             .arg(
                 Arg::with_name("rest_default_max_mempool_txs")
                     .long("rest-default-max-mempool-txs")
@@ -254,6 +520,84 @@ impl Config {
                     .help("The default number of transactions returned by the address summary endpoints.")
                     .default_value("5000")
             )
+            .arg(
+                Arg::with_name("rest_default_outspends_limit")
+                    .long("rest-default-outspends-limit")
+                    .help("The default number of outputs returned by the tx/:txid/outspends endpoint. Transactions with more outputs are paginated.")
+                    .default_value("1000")
+            )
+            .arg(
+                Arg::with_name("rest_max_outspends_limit")
+                    .long("rest-max-outspends-limit")
+                    .help("The maximum number of outputs returned by the tx/:txid/outspends endpoint, regardless of the requested limit.")
+                    .default_value("5000")
+            )
+            .arg(
+                Arg::with_name("rest_max_ancestry_depth")
+                    .long("rest-max-ancestry-depth")
+                    .help("The maximum number of hops followed by the tx/:txid/ancestry endpoint, regardless of the requested depth.")
+                    .default_value("100")
+            )
+            .arg(
+                Arg::with_name("rest_ready_max_lag")
+                    .long("rest-ready-max-lag")
+                    .help("Number of blocks the index can be behind the daemon's tip while /ready still reports it as ready")
+                    .default_value("0")
+            )
+            .arg(
+                Arg::with_name("rest_max_lag_blocks")
+                    .long("rest-max-lag-blocks")
+                    .help("Number of blocks the index can be behind the daemon's tip before REST data endpoints respond 503, unless requested with ?allow_stale=true [default: serve stale data]")
+                    .takes_value(true)
+            )
+            .arg(
+                Arg::with_name("rest_sse_max_connections")
+                    .long("rest-sse-max-connections")
+                    .help("Maximum number of concurrent /stream event streams")
+                    .default_value("1000")
+            )
+            .arg(
+                Arg::with_name("rest_sse_max_addresses")
+                    .long("rest-sse-max-addresses")
+                    .help("Maximum number of addresses a single /stream/address event stream can follow")
+                    .default_value("100")
+            )
+            .arg(
+                Arg::with_name("rest_ws_max_connections")
+                    .long("rest-ws-max-connections")
+                    .help("Maximum number of concurrent /ws websocket connections")
+                    .default_value("1000")
+            )
+            .arg(
+                Arg::with_name("rest_request_timeout")
+                    .long("rest-request-timeout")
+                    .help("Timeout in seconds for handling a REST request, after which a 504 is returned (0 to disable)")
+                    .default_value("0")
+            )
+            .arg(
+                Arg::with_name("rest_heavy_concurrency")
+                    .long("rest-heavy-concurrency")
+                    .help("Maximum number of expensive REST requests (address history and utxos, block txs, mempool dumps) handled at once, others wait in a queue (0 for no limit)")
+                    .default_value("0")
+            )
+            .arg(
+                Arg::with_name("rest_heavy_queue_timeout")
+                    .long("rest-heavy-queue-timeout")
+                    .help("Timeout in seconds for expensive REST requests waiting in the --rest-heavy-concurrency queue, after which a 503 is returned")
+                    .default_value("10")
+            )
+            .arg(
+                Arg::with_name("rest_max_body_size")
+                    .long("rest-max-body-size")
+                    .help("The maximum size in bytes of REST request bodies. Larger requests are rejected with a 413.")
+                    .default_value("1000000")
+            )
+            .arg(
+                Arg::with_name("rest_max_tx_body_size")
+                    .long("rest-max-tx-body-size")
+                    .help("The maximum size in bytes of REST request bodies for the transaction broadcast, decode and test endpoints.")
+                    .default_value("8000000")
+            )
             .arg(
                 Arg::with_name("rest_max_mempool_page_size")
                     .long("rest-max-mempool-page-size")
@@ -282,7 +626,7 @@ impl Config {
         let args = args.arg(
                 Arg::with_name("http_socket_file")
                     .long("http-socket-file")
-                    .help("HTTP server 'unix socket file' to listen on (default disabled, enabling this disables the http server)")
+                    .help("HTTP server 'unix socket file' to listen on (default disabled, enabling this disables the default TCP listener, unless --http-addr is also given)")
                     .takes_value(true),
             );
 
@@ -326,7 +670,7 @@ impl Config {
                 .takes_value(true),
         );
 
-        let m = args.get_matches();
+        let m = args.get_matches_from(argv);
 
         if m.is_present("version") {
             eprintln!("{}", *VERSION_STRING);
@@ -440,13 +784,18 @@ impl Config {
                 .unwrap_or(&format!("127.0.0.1:{}", default_electrum_port)),
             "Electrum RPC",
         );
-        let http_addr: SocketAddr = str_to_socketaddr(
-            m.value_of("http_addr")
-                .unwrap_or(&format!("127.0.0.1:{}", default_http_port)),
-            "HTTP Server",
-        );
-
         let http_socket_file: Option<PathBuf> = m.value_of("http_socket_file").map(PathBuf::from);
+        // the unix socket replaces the default TCP listener, but can be combined with explicit ones
+        let http_addrs: Vec<SocketAddr> = match m.values_of("http_addr") {
+            Some(addrs) => addrs
+                .map(|addr| str_to_socketaddr(addr, "HTTP Server"))
+                .collect(),
+            None if http_socket_file.is_some() => vec![],
+            None => vec![str_to_socketaddr(
+                &format!("127.0.0.1:{}", default_http_port),
+                "HTTP Server",
+            )],
+        };
         let rpc_socket_file: Option<PathBuf> = m.value_of("rpc_socket_file").map(PathBuf::from);
         let monitoring_addr: SocketAddr = str_to_socketaddr(
             m.value_of("monitoring_addr")
@@ -503,7 +852,6 @@ impl Config {
         } else {
             stderrlog::Timestamp::Off
         });
-        log.init().expect("logging initialization failed");
         let config = Config {
             log,
             network_type,
@@ -514,21 +862,30 @@ impl Config {
             daemon_rpc_addr,
             cookie,
             utxos_limit: value_t_or_exit!(m, "utxos_limit", usize),
+            max_utxos_per_query: value_t_or_exit!(m, "max_utxos_per_query", usize),
             electrum_rpc_addr,
             electrum_txs_limit: value_t_or_exit!(m, "electrum_txs_limit", usize),
             electrum_banner,
-            http_addr,
+            http_addrs,
             http_socket_file,
+            http_tls_cert: m.value_of("http_tls_cert").map(PathBuf::from),
+            http_tls_key: m.value_of("http_tls_key").map(PathBuf::from),
             rpc_socket_file,
             monitoring_addr,
             mempool_backlog_stats_ttl: value_t_or_exit!(m, "mempool_backlog_stats_ttl", u64),
             mempool_recent_txs_size: value_t_or_exit!(m, "mempool_recent_txs_size", usize),
+            mempool_conflicts_history_size: value_t_or_exit!(
+                m,
+                "mempool_conflicts_history_size",
+                usize
+            ),
             rest_default_block_limit: value_t_or_exit!(m, "rest_default_block_limit", usize),
             rest_default_chain_txs_per_page: value_t_or_exit!(
                 m,
                 "rest_default_chain_txs_per_page",
                 usize
             ),
+            rest_max_chain_txs_per_page: value_t_or_exit!(m, "rest_max_chain_txs_per_page", usize),
             rest_default_max_mempool_txs: value_t_or_exit!(
                 m,
                 "rest_default_max_mempool_txs",
@@ -540,6 +897,27 @@ impl Config {
                 usize
             ),
             rest_max_mempool_page_size: value_t_or_exit!(m, "rest_max_mempool_page_size", usize),
+            rest_default_outspends_limit: value_t_or_exit!(
+                m,
+                "rest_default_outspends_limit",
+                usize
+            ),
+            rest_max_outspends_limit: value_t_or_exit!(m, "rest_max_outspends_limit", usize),
+            rest_max_ancestry_depth: value_t_or_exit!(m, "rest_max_ancestry_depth", usize),
+            rest_ready_max_lag: value_t_or_exit!(m, "rest_ready_max_lag", usize),
+            rest_max_lag_blocks: if m.is_present("rest_max_lag_blocks") {
+                Some(value_t_or_exit!(m, "rest_max_lag_blocks", usize))
+            } else {
+                None
+            },
+            rest_request_timeout: value_t_or_exit!(m, "rest_request_timeout", u64),
+            rest_heavy_concurrency: value_t_or_exit!(m, "rest_heavy_concurrency", usize),
+            rest_heavy_queue_timeout: value_t_or_exit!(m, "rest_heavy_queue_timeout", u64),
+            rest_sse_max_connections: value_t_or_exit!(m, "rest_sse_max_connections", usize),
+            rest_sse_max_addresses: value_t_or_exit!(m, "rest_sse_max_addresses", usize),
+            rest_ws_max_connections: value_t_or_exit!(m, "rest_ws_max_connections", usize),
+            rest_max_body_size: value_t_or_exit!(m, "rest_max_body_size", usize),
+            rest_max_tx_body_size: value_t_or_exit!(m, "rest_max_tx_body_size", usize),
             rest_max_mempool_txid_page_size: value_t_or_exit!(
                 m,
                 "rest_max_mempool_txid_page_size",
@@ -549,7 +927,38 @@ impl Config {
             light_mode: m.is_present("light_mode"),
             main_loop_delay: value_t_or_exit!(m, "main_loop_delay", u64),
             address_search: m.is_present("address_search"),
+            txid_search: m.is_present("txid_search"),
             index_unspendables: m.is_present("index_unspendables"),
+            rest_block_tx_fees: m.is_present("rest_block_tx_fees"),
+            rest_block_audit: m.is_present("rest_block_audit"),
+            rest_inscriptions: m.is_present("rest_inscriptions"),
+            rest_json_errors: m.is_present("rest_json_errors"),
+            http_access_log: m.is_present("http_access_log"),
+            http2_enabled: m.is_present("http2"),
+            rest_compression: m.is_present("rest_compression"),
+            rest_compress_binary: m.is_present("rest_compress_binary"),
+            rest_admin_token: m.value_of("rest_admin_token").map(|s| s.to_string()),
+            internal_api_access: value_t_or_exit!(m, "internal_api_access", InternalApiAccess),
+            rest_rate_limit: m
+                .value_of("rest_rate_limit")
+                .map(|s| match s.parse::<f64>() {
+                    Ok(v) if v > 0.0 && v.is_finite() => v,
+                    _ => clap::Error::value_validation_auto(format!(
+                        "The argument '{}' isn't a valid value",
+                        s
+                    ))
+                    .exit(),
+                }),
+            rest_rate_limit_burst: value_t_or_exit!(m, "rest_rate_limit_burst", u32),
+            rest_rate_limit_max_clients: value_t_or_exit!(m, "rest_rate_limit_max_clients", usize),
+            rest_trusted_proxy_header: m
+                .value_of("rest_trusted_proxy_header")
+                .map(|s| s.to_string()),
+            rest_disabled_endpoints: m
+                .values_of("rest_disabled_endpoints")
+                .map_or_else(Vec::new, |values| {
+                    values.map(|s| s.trim().to_string()).collect()
+                }),
             cors: m.value_of("cors").map(|s| s.to_string()),
             precache_scripts: m.value_of("precache_scripts").map(|s| s.to_string()),
             precache_threads: m.value_of("precache_threads").map_or_else(
@@ -69,6 +69,8 @@ fn parse_jsonrpc_reply(mut reply: Value, method: &str, expected_id: u64) -> Resu
                     match code {
                         // RPC_IN_WARMUP -> retry by later reconnection
                         -28 => bail!(ErrorKind::Connection(err.to_string())),
+                        // RPC_METHOD_NOT_FOUND -> not available on this daemon version
+                        -32601 => bail!(ErrorKind::MethodNotFound(method.to_string())),
                         _ => bail!("{} RPC error: {}", method, err),
                     }
                 }
@@ -118,23 +120,42 @@ struct NetworkInfo {
 }
 
 #[derive(Serialize, Deserialize, Debug)]
-struct MempoolFees {
-    base: f64,
+pub struct MempoolFees {
+    pub base: f64,
     #[serde(rename = "effective-feerate")]
-    effective_feerate: f64,
+    pub effective_feerate: f64,
     #[serde(rename = "effective-includes")]
-    effective_includes: Vec<String>,
+    pub effective_includes: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct MempoolAcceptResult {
-    txid: String,
-    wtxid: String,
-    allowed: Option<bool>,
-    vsize: Option<u32>,
-    fees: Option<MempoolFees>,
+    pub txid: String,
+    pub wtxid: String,
+    pub allowed: Option<bool>,
+    pub vsize: Option<u32>,
+    pub fees: Option<MempoolFees>,
     #[serde(rename = "reject-reason")]
-    reject_reason: Option<String>,
+    pub reject_reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SubmitPackageResult {
+    pub package_msg: String,
+    #[serde(rename = "tx-results")]
+    pub tx_results: HashMap<String, SubmitPackageTxResult>,
+    #[serde(rename = "replaced-transactions", default)]
+    pub replaced_transactions: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SubmitPackageTxResult {
+    pub txid: String,
+    #[serde(rename = "other-wtxid")]
+    pub other_wtxid: Option<String>,
+    pub vsize: Option<u32>,
+    pub fees: Option<MempoolFees>,
+    pub error: Option<String>,
 }
 
 pub trait CookieGetter: Send + Sync {
@@ -508,6 +529,11 @@ impl Daemon {
         from_value(info).chain_err(|| "invalid network info")
     }
 
+    /// The daemon's version, encoded like bitcoind's (e.g. 250000 for v25.0.0)
+    pub fn version(&self) -> Result<u64> {
+        Ok(self.getnetworkinfo()?.version)
+    }
+
     pub fn getbestblockhash(&self) -> Result<BlockHash> {
         parse_hash(&self.request("getbestblockhash", json!([]))?)
     }
@@ -598,11 +624,15 @@ impl Daemon {
     }
 
     pub fn broadcast(&self, tx: &Transaction) -> Result<Txid> {
-        self.broadcast_raw(&hex::encode(serialize(tx)))
+        self.broadcast_raw(&hex::encode(serialize(tx)), None)
     }
 
-    pub fn broadcast_raw(&self, txhex: &str) -> Result<Txid> {
-        let txid = self.request("sendrawtransaction", json!([txhex]))?;
+    pub fn broadcast_raw(&self, txhex: &str, maxfeerate: Option<f64>) -> Result<Txid> {
+        let params = match maxfeerate {
+            Some(rate) => json!([txhex, format!("{:.8}", rate)]),
+            None => json!([txhex]),
+        };
+        let txid = self.request("sendrawtransaction", params)?;
         Txid::from_hex(txid.as_str().chain_err(|| "non-string txid")?)
             .chain_err(|| "failed to parse txid")
     }
@@ -621,6 +651,12 @@ impl Daemon {
             .chain_err(|| "invalid testmempoolaccept reply")
     }
 
+    pub fn submit_package(&self, txhex: Vec<String>) -> Result<SubmitPackageResult> {
+        let result = self.request("submitpackage", json!([txhex]))?;
+        serde_json::from_value::<SubmitPackageResult>(result)
+            .chain_err(|| "invalid submitpackage reply")
+    }
+
     // Get estimated feerates for the provided confirmation targets using a batch RPC request
     // Missing estimates are logged but do not cause a failure, whatever is available is returned
     #[allow(clippy::float_cmp)]
@@ -32,8 +32,8 @@ use crate::metrics::{Gauge, HistogramOpts, HistogramVec, MetricOpts, Metrics};
 use crate::new_index::{Query, Utxo};
 use crate::util::electrum_merkle::{get_header_merkle_proof, get_id_from_pos, get_tx_merkle_proof};
 use crate::util::{
-    create_socket, full_hash, spawn_thread, BlockId, BoolThen, Channel, FullHash, HeaderEntry,
-    SyncChannel,
+    create_socket, full_hash, spawn_thread, BlockId, BoolThen, CancelToken, Channel, FullHash,
+    HeaderEntry, SyncChannel,
 };
 
 const PROTOCOL_VERSION: ProtocolVersion = ProtocolVersion::new(1, 4);
@@ -324,7 +324,7 @@ impl Connection {
 
     fn blockchain_scripthash_listunspent(&self, params: &[Value]) -> Result<Value> {
         let script_hash = hash_from_value(params.first()).chain_err(|| "bad script_hash")?;
-        let utxos = self.query.utxo(&script_hash[..])?;
+        let utxos = self.query.utxo(&script_hash[..], &CancelToken::default())?;
 
         let to_json = |utxo: Utxo| {
             let json = json!({
@@ -353,7 +353,7 @@ impl Connection {
     fn blockchain_transaction_broadcast(&self, params: &[Value]) -> Result<Value> {
         let tx = params.first().chain_err(|| "missing tx")?;
         let tx = tx.as_str().chain_err(|| "non-string tx")?.to_string();
-        let txid = self.query.broadcast_raw(&tx)?;
+        let txid = self.query.broadcast_raw(&tx, None)?;
         if let Err(e) = self.chan.sender().try_send(Message::PeriodicUpdate) {
             warn!("failed to issue PeriodicUpdate after broadcast: {}", e);
         }
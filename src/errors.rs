@@ -9,6 +9,11 @@ error_chain! {
             display("Connection error: {}", msg)
         }
 
+        MethodNotFound(method: String) {
+            description("RPC method not supported by the daemon")
+            display("{} RPC method is not supported by the daemon", method)
+        }
+
         Interrupt(sig: i32) {
             description("Interruption by external signal")
             display("Iterrupted by signal {}", sig)
@@ -24,6 +29,11 @@ error_chain! {
             display("Too many history transactions (>{}). Contact support to raise limits.", limit)
         }
 
+        Cancelled {
+            description("Request cancelled")
+            display("Request cancelled")
+        }
+
         #[cfg(feature = "electrum-discovery")]
         ElectrumClient(e: electrum_client::Error) {
             description("Electrum client error")
@@ -11,6 +11,17 @@ use crate::util::{bincode_util, Bytes};
 // 2 = Add tx position to TxHistory rows and place Spending before Funding
 static DB_VERSION: u32 = 2;
 
+#[derive(Serialize, Debug)]
+pub struct DBStats {
+    pub estimate_num_keys: Option<u64>,
+    pub total_sst_files_size: Option<u64>,
+    pub estimate_live_data_size: Option<u64>,
+    pub cur_size_all_mem_tables: Option<u64>,
+    pub num_running_compactions: Option<u64>,
+    pub compaction_pending: Option<u64>,
+    pub estimate_pending_compaction_bytes: Option<u64>,
+}
+
 #[derive(Debug, Eq, PartialEq)]
 pub struct DBRow {
     pub key: Vec<u8>,
@@ -260,6 +271,23 @@ impl DB {
         self.db.get(key).unwrap().map(|v| v.to_vec())
     }
 
+    fn int_property(&self, name: &str) -> Option<u64> {
+        self.db.property_int_value(name).ok().flatten()
+    }
+
+    pub fn stats(&self) -> DBStats {
+        DBStats {
+            estimate_num_keys: self.int_property("rocksdb.estimate-num-keys"),
+            total_sst_files_size: self.int_property("rocksdb.total-sst-files-size"),
+            estimate_live_data_size: self.int_property("rocksdb.estimate-live-data-size"),
+            cur_size_all_mem_tables: self.int_property("rocksdb.cur-size-all-mem-tables"),
+            num_running_compactions: self.int_property("rocksdb.num-running-compactions"),
+            compaction_pending: self.int_property("rocksdb.compaction-pending"),
+            estimate_pending_compaction_bytes: self
+                .int_property("rocksdb.estimate-pending-compaction-bytes"),
+        }
+    }
+
     fn verify_compatibility(&self, config: &Config) {
         let mut compatibility_bytes = bincode_util::serialize_little(&DB_VERSION).unwrap();
 
@@ -10,7 +10,8 @@ use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::iter::FromIterator;
 use std::ops::Bound::{Excluded, Unbounded};
 use std::sync::{Arc, RwLock};
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
 
 use crate::chain::{deserialize, Network, OutPoint, Transaction, TxOut, Txid};
 use crate::config::Config;
@@ -18,11 +19,14 @@ use crate::daemon::Daemon;
 use crate::errors::*;
 use crate::metrics::{GaugeVec, HistogramOpts, HistogramVec, MetricOpts, Metrics};
 use crate::new_index::{
-    compute_script_hash, schema::FullHash, ChainQuery, FundingInfo, ScriptStats, SpendingInfo,
-    SpendingInput, TxHistoryInfo, Utxo,
+    compute_script_hash, schema::FullHash, ChainQuery, FundingInfo, ScriptStats, ScripthashEvent,
+    SpendingInfo, SpendingInput, TxHistoryInfo, Utxo,
 };
 use crate::util::fees::{make_fee_histogram, TxFeeInfo};
-use crate::util::{extract_tx_prevouts, full_hash, has_prevout, is_spendable, Bytes};
+use crate::util::{
+    extract_tx_prevouts, full_hash, has_prevout, is_spendable, signals_rbf, Bytes,
+    TransactionStatus,
+};
 
 #[cfg(feature = "liquid")]
 use crate::elements::asset;
@@ -32,10 +36,15 @@ pub struct Mempool {
     config: Arc<Config>,
     txstore: BTreeMap<Txid, Transaction>,
     feeinfo: HashMap<Txid, TxFeeInfo>,
+    first_seen: HashMap<Txid, u64>, // Txid -> unix timestamp of the tx's arrival
     history: HashMap<FullHash, Vec<TxHistoryInfo>>, // ScriptHash -> {history_entries}
-    edges: HashMap<OutPoint, (Txid, u32)>,          // OutPoint -> (spending_txid, spending_vin)
-    recent: BoundedVecDeque<TxOverview>,            // The N most recent txs to enter the mempool
+    edges: HashMap<OutPoint, (Txid, u32)>, // OutPoint -> (spending_txid, spending_vin)
+    recent: BoundedVecDeque<TxOverview>, // The N most recent txs to enter the mempool
+    conflicts: HashMap<Txid, (TxConflict, Vec<OutPoint>)>, // Txid -> (conflict, inputs)
+    conflicts_order: BoundedVecDeque<Txid>, // The N most recently evicted txs, newest first
+    evicted_spends: HashMap<OutPoint, Txid>, // OutPoint -> evicted spending txid
     backlog_stats: (BacklogStats, Instant),
+    loaded: bool, // whether the daemon's mempool was fully fetched at least once
 
     // monitoring
     latency: HistogramVec, // mempool requests latency
@@ -49,14 +58,95 @@ pub struct Mempool {
     pub asset_issuance: HashMap<AssetId, asset::AssetRow>,
 }
 
+// Mirror bitcoind's default -limitancestorcount and -limitdescendantcount
+const MAX_ANCESTORS: usize = 25;
+const MAX_DESCENDANTS: usize = 25;
+
 // A simplified transaction view used for the list of most recent transactions
-#[derive(Serialize)]
+#[derive(Serialize, Clone)]
 pub struct TxOverview {
     txid: Txid,
     fee: u64,
     vsize: u32,
     #[cfg(not(feature = "liquid"))]
     value: u64,
+    double_spend: bool,
+    // computed when listed, omitted for txs that already left the mempool
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ancestor_feerate: Option<f64>,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictStatus {
+    Active,
+    Replaced,
+    Evicted,
+    Unknown,
+}
+
+// What happened to a transaction that left the mempool without getting confirmed
+#[derive(Serialize, Clone, Debug)]
+pub struct TxConflict {
+    pub status: ConflictStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replaced_by: Option<Txid>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub conflicting_inputs: Vec<OutPoint>,
+}
+
+impl TxConflict {
+    pub fn with_status(status: ConflictStatus) -> Self {
+        TxConflict {
+            status,
+            replaced_by: None,
+            conflicting_inputs: vec![],
+        }
+    }
+}
+
+// The fee information of a mempool transaction
+#[derive(Serialize)]
+pub struct TxFeeEntry {
+    pub txid: Txid,
+    pub fee: u64,
+    pub vsize: u32,
+    pub feerate: f64,
+}
+
+// The CPFP package of a mempool transaction: itself along with its in-mempool ancestors and
+// descendants, which miners evaluate together
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PackageFeerate {
+    pub ancestor_count: usize,
+    pub descendant_count: usize,
+    pub package_vsize: u32,
+    pub package_fee: u64,
+}
+
+// A mempool transaction aggregated with its in-mempool ancestors, which have to be mined
+// along with it
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct AncestorStats {
+    pub ancestor_count: usize,
+    pub ancestor_fee: u64,
+    pub ancestor_vsize: u32,
+    // whether there were more than MAX_ANCESTORS ancestors, which were left out
+    pub truncated: bool,
+}
+
+impl AncestorStats {
+    /// The fee rate of the transaction and its ancestors in sat/vB
+    pub fn feerate(&self) -> f64 {
+        self.ancestor_fee as f64 / self.ancestor_vsize as f64
+    }
+}
+
+impl PackageFeerate {
+    /// The fee rate of the whole package in sat/vB
+    pub fn effective_feerate(&self) -> f64 {
+        self.package_fee as f64 / self.package_vsize as f64
+    }
 }
 
 impl Mempool {
@@ -65,13 +155,18 @@ impl Mempool {
             chain,
             txstore: BTreeMap::new(),
             feeinfo: HashMap::new(),
+            first_seen: HashMap::new(),
             history: HashMap::new(),
             edges: HashMap::new(),
             recent: BoundedVecDeque::new(config.mempool_recent_txs_size),
+            conflicts: HashMap::new(),
+            conflicts_order: BoundedVecDeque::new(config.mempool_conflicts_history_size),
+            evicted_spends: HashMap::new(),
             backlog_stats: (
                 BacklogStats::default(),
                 Instant::now() - Duration::from_secs(config.mempool_backlog_stats_ttl),
             ),
+            loaded: false,
             latency: metrics.histogram_vec(
                 HistogramOpts::new("mempool_latency", "Mempool requests latency (in seconds)"),
                 &["part"],
@@ -97,6 +192,27 @@ impl Mempool {
         self.config.network_type
     }
 
+    pub fn is_loaded(&self) -> bool {
+        self.loaded
+    }
+
+    pub fn tx_count(&self) -> usize {
+        self.txstore.len()
+    }
+
+    /// Subscribe to notifications about transactions touching scripthashes, as they enter the
+    /// mempool and as the index confirms them (or unconfirms them on reorgs)
+    pub fn subscribe_scripthash_events(&self) -> broadcast::Receiver<ScripthashEvent> {
+        self.chain.store().scripthash_events().subscribe()
+    }
+
+    /// Returns what happened to a transaction that recently left the mempool unconfirmed
+    pub fn conflict(&self, txid: &Txid) -> Option<TxConflict> {
+        self.conflicts
+            .get(txid)
+            .map(|(conflict, _)| conflict.clone())
+    }
+
     pub fn lookup_txn(&self, txid: &Txid) -> Option<Transaction> {
         self.txstore.get(txid).cloned()
     }
@@ -105,6 +221,10 @@ impl Mempool {
         self.txstore.get(txid).map(serialize)
     }
 
+    pub fn tx_output_count(&self, txid: &Txid) -> Option<u32> {
+        self.txstore.get(txid).map(|tx| tx.output.len() as u32)
+    }
+
     pub fn lookup_spend(&self, outpoint: &OutPoint) -> Option<SpendingInput> {
         self.edges.get(outpoint).map(|(txid, vin)| SpendingInput {
             txid: *txid,
@@ -114,7 +234,7 @@ impl Mempool {
     }
 
     pub fn has_spend(&self, outpoint: &OutPoint) -> bool {
-        self.edges.contains_key(outpoint)
+        is_spent(outpoint, &self.txstore, &self.edges)
     }
 
     pub fn get_tx_fee(&self, txid: &Txid) -> Option<u64> {
@@ -131,6 +251,146 @@ impl Mempool {
             .any(|txin| self.txstore.contains_key(&txin.previous_output.txid))
     }
 
+    /// Returns the in-mempool ancestors of the transaction, or None if it's not in the mempool.
+    /// Bounded by MAX_ANCESTORS.
+    pub fn ancestors(&self, txid: &Txid) -> Option<Vec<Txid>> {
+        let _timer = self.latency.with_label_values(&["ancestors"]).start_timer();
+        self.ancestors_up_to(txid, MAX_ANCESTORS)
+    }
+
+    fn ancestors_up_to(&self, txid: &Txid, limit: usize) -> Option<Vec<Txid>> {
+        self.traverse(txid, limit, |_, tx| {
+            tx.input
+                .iter()
+                .map(|txin| txin.previous_output.txid)
+                .filter(|parent| self.txstore.contains_key(parent))
+                .collect()
+        })
+    }
+
+    /// Whether any in-mempool ancestor of the transaction signals BIP 125 replaceability,
+    /// which makes the transaction replaceable too
+    pub fn has_rbf_signaling_ancestor(&self, txid: &Txid) -> bool {
+        self.ancestors(txid).map_or(false, |ancestors| {
+            ancestors
+                .iter()
+                .any(|ancestor| signals_rbf(&self.txstore[ancestor]))
+        })
+    }
+
+    /// Returns the in-mempool descendants of the transaction, or None if it's not in the mempool.
+    /// Bounded by MAX_DESCENDANTS.
+    pub fn descendants(&self, txid: &Txid) -> Option<Vec<Txid>> {
+        let _timer = self
+            .latency
+            .with_label_values(&["descendants"])
+            .start_timer();
+        self.traverse(txid, MAX_DESCENDANTS, |txid, tx| {
+            (0..tx.output.len() as u32)
+                .filter_map(|vout| self.edges.get(&OutPoint { txid: *txid, vout }))
+                .map(|(child, _)| *child)
+                .collect()
+        })
+    }
+
+    /// Returns the unix timestamp at which the transaction was first seen in the mempool
+    pub fn first_seen(&self, txid: &Txid) -> Option<u64> {
+        self.first_seen.get(txid).copied()
+    }
+
+    pub fn fee_entry(&self, txid: &Txid) -> Option<TxFeeEntry> {
+        self.feeinfo.get(txid).map(|info| TxFeeEntry {
+            txid: *txid,
+            fee: info.fee,
+            vsize: info.vsize,
+            feerate: info.fee as f64 / info.vsize as f64,
+        })
+    }
+
+    /// Fill in the fee, vsize, feerate and arrival time of an unconfirmed transaction's status
+    pub fn add_status_details(&self, txid: &Txid, status: &mut TransactionStatus) {
+        if status.confirmed {
+            return;
+        }
+        if let Some(entry) = self.fee_entry(txid) {
+            status.fee = Some(entry.fee);
+            status.vsize = Some(entry.vsize);
+            status.feerate = Some(entry.feerate);
+            status.first_seen = self.first_seen(txid);
+        }
+    }
+
+    /// Returns the transaction's fee and vsize summed with its in-mempool ancestors (the counts
+    /// include the transaction itself, like bitcoind's mempool entries), or None if it's not in
+    /// the mempool. Ancestors are bounded by MAX_ANCESTORS, flagging the stats as truncated.
+    pub fn ancestor_stats(&self, txid: &Txid) -> Option<AncestorStats> {
+        let own = self.feeinfo.get(txid)?;
+        // look one past the bound to tell whether any ancestor was left out
+        let mut ancestors = self.ancestors_up_to(txid, MAX_ANCESTORS + 1)?;
+        let truncated = ancestors.len() > MAX_ANCESTORS;
+        ancestors.truncate(MAX_ANCESTORS);
+        let (ancestor_fee, ancestor_vsize) = ancestors
+            .iter()
+            .filter_map(|txid| self.feeinfo.get(txid))
+            .fold((own.fee, own.vsize), |(fee, vsize), info| {
+                (fee + info.fee, vsize + info.vsize)
+            });
+        Some(AncestorStats {
+            ancestor_count: ancestors.len() + 1,
+            ancestor_fee,
+            ancestor_vsize,
+            truncated,
+        })
+    }
+
+    /// Returns the CPFP package of the transaction, or None if it's not in the mempool.
+    /// Ancestors and descendants are bounded by MAX_ANCESTORS and MAX_DESCENDANTS.
+    pub fn package_feerate(&self, txid: &Txid) -> Option<PackageFeerate> {
+        let own = self.feeinfo.get(txid)?;
+        let ancestors = self.ancestors(txid)?;
+        let descendants = self.descendants(txid)?;
+
+        let (package_fee, package_vsize) = ancestors
+            .iter()
+            .chain(descendants.iter())
+            .filter_map(|txid| self.feeinfo.get(txid))
+            .fold((own.fee, own.vsize), |(fee, vsize), info| {
+                (fee + info.fee, vsize + info.vsize)
+            });
+
+        Some(PackageFeerate {
+            ancestor_count: ancestors.len(),
+            descendant_count: descendants.len(),
+            package_vsize,
+            package_fee,
+        })
+    }
+
+    // Traverse the mempool dependency graph, collecting up to `limit` reachable txs
+    // (excluding the starting one)
+    fn traverse<F>(&self, txid: &Txid, limit: usize, neighbours: F) -> Option<Vec<Txid>>
+    where
+        F: Fn(&Txid, &Transaction) -> Vec<Txid>,
+    {
+        let mut stack = vec![(*txid, self.txstore.get(txid)?)];
+        let mut seen = HashSet::new();
+        let mut result = vec![];
+        seen.insert(*txid);
+
+        while let Some((txid, tx)) = stack.pop() {
+            for next in neighbours(&txid, tx) {
+                if seen.insert(next) {
+                    if result.len() == limit {
+                        return Some(result);
+                    }
+                    result.push(next);
+                    stack.push((next, self.txstore.get(&next).expect("missing mempool tx")));
+                }
+            }
+        }
+        Some(result)
+    }
+
     pub fn history(
         &self,
         scripthash: &[u8],
@@ -145,11 +405,16 @@ impl Mempool {
             })
     }
 
+    fn is_replaced(&self, txid: &Txid) -> bool {
+        is_replaced(txid, &self.txstore, &self.edges)
+    }
+
     pub fn history_txids_iter<'a>(&'a self, scripthash: &[u8]) -> impl Iterator<Item = Txid> + 'a {
         self.history
             .get(scripthash)
             .into_iter()
             .flat_map(|v| v.iter().map(|e| e.get_txid()).unique())
+            .filter(move |txid| !self.is_replaced(txid))
     }
 
     fn _history(
@@ -171,6 +436,7 @@ impl Mempool {
                 Some(_) => 1, // skip the last_seen_txid itself
                 None => 0,
             })
+            .filter(|txid| !self.is_replaced(txid))
             .take(limit)
             .map(|txid| self.txstore.get(&txid).expect("missing mempool tx"))
             .cloned()
@@ -202,6 +468,7 @@ impl Mempool {
                 Some(_) => 1, // skip the last_seen_txid itself
                 None => 0,
             })
+            .filter(|txid| !self.is_replaced(txid))
             .take(limit)
             .map(|txid| self.txstore.get(&txid).expect("missing mempool tx"))
             .cloned()
@@ -218,6 +485,7 @@ impl Mempool {
             .flat_map(|entries| entries.iter())
             .map(|entry| entry.get_txid())
             .unique()
+            .filter(move |txid| !self.is_replaced(txid))
     }
 
     pub fn history_txids(&self, scripthash: &[u8], limit: usize) -> Vec<Txid> {
@@ -231,6 +499,7 @@ impl Mempool {
                 .iter()
                 .map(|e| e.get_txid())
                 .unique()
+                .filter(|txid| !self.is_replaced(txid))
                 .take(limit)
                 .collect(),
         }
@@ -245,6 +514,7 @@ impl Mempool {
 
         entries
             .iter()
+            .filter(|entry| !self.is_replaced(&entry.get_txid()))
             .filter_map(|entry| match entry {
                 TxHistoryInfo::Funding(info) => {
                     // Liquid requires some additional information from the txo that's not available in the TxHistoryInfo index.
@@ -276,6 +546,24 @@ impl Mempool {
             .collect()
     }
 
+    /// Returns the unconfirmed outputs funding the scripthash
+    pub fn funded_outpoints(&self, scripthash: &[u8]) -> Vec<OutPoint> {
+        let _timer = self
+            .latency
+            .with_label_values(&["funded_outpoints"])
+            .start_timer();
+        self.history
+            .get(scripthash)
+            .map_or_else(Vec::new, |entries| {
+                entries
+                    .iter()
+                    .filter(|entry| matches!(entry, TxHistoryInfo::Funding(_)))
+                    .filter(|entry| !self.is_replaced(&entry.get_txid()))
+                    .map(TxHistoryInfo::get_funded_outpoint)
+                    .collect()
+            })
+    }
+
     // @XXX avoid code duplication with ChainQuery::stats()?
     pub fn stats(&self, scripthash: &[u8]) -> ScriptStats {
         let _timer = self.latency.with_label_values(&["stats"]).start_timer();
@@ -288,7 +576,11 @@ impl Mempool {
         };
 
         for entry in entries {
-            if seen_txids.insert(entry.get_txid()) {
+            let txid = entry.get_txid();
+            if self.is_replaced(&txid) {
+                continue;
+            }
+            if seen_txids.insert(txid) {
                 stats.tx_count += 1;
             }
 
@@ -375,11 +667,19 @@ impl Mempool {
     }
 
     // Get an overview of the most recent transactions
-    pub fn recent_txs_overview(&self) -> Vec<&TxOverview> {
+    pub fn recent_txs_overview(&self) -> Vec<TxOverview> {
         // We don't bother ever deleting elements from the recent list.
         // It may contain outdated txs that are no longer in the mempool,
         // until they get pushed out by newer transactions.
-        self.recent.iter().collect()
+        self.recent
+            .iter()
+            .map(|overview| TxOverview {
+                ancestor_feerate: self
+                    .ancestor_stats(&overview.txid)
+                    .map(|stats| stats.feerate()),
+                ..overview.clone()
+            })
+            .collect()
     }
 
     pub fn backlog_stats(&self) -> &BacklogStats {
@@ -390,14 +690,17 @@ impl Mempool {
         return HashSet::from_iter(self.txstore.keys().cloned());
     }
 
-    pub fn update(mempool: &RwLock<Mempool>, daemon: &Daemon) -> Result<()> {
+    /// Sync the mempool with the daemon's, returning the number of (added, removed) transactions
+    pub fn update(mempool: &RwLock<Mempool>, daemon: &Daemon) -> Result<(usize, usize)> {
         // 1. Start the metrics timer and get the current mempool txids
         // [LOCK] Takes read lock for whole scope.
-        let (_timer, old_txids) = {
+        let (_timer, old_txids, chain, track_conflicts) = {
             let mempool = mempool.read().unwrap();
             (
                 mempool.latency.with_label_values(&["update"]).start_timer(),
                 mempool.unique_txids(),
+                Arc::clone(&mempool.chain),
+                mempool.config.mempool_conflicts_history_size > 0,
             )
         };
 
@@ -408,26 +711,43 @@ impl Mempool {
             .chain_err(|| "failed to update mempool from daemon")?;
         let txids_to_remove: HashSet<&Txid> = old_txids.difference(&all_txids).collect();
         let txids_to_add: Vec<&Txid> = all_txids.difference(&old_txids).collect();
+        let removed = txids_to_remove.len();
 
-        // 3. Remove missing transactions. Even if we are unable to download new transactions from
+        // 3. Tell the transactions that got confirmed apart from replaced/evicted ones, which are
+        // remembered for the conflicts endpoint. Skip the index lookups when nobody needs them.
+        // [LOCK] No lock taken, the index lookups would otherwise block all mempool readers.
+        let confirmed: HashSet<Txid> = if track_conflicts {
+            txids_to_remove
+                .iter()
+                .filter(|txid| chain.tx_confirming_block(txid).is_some())
+                .map(|txid| **txid)
+                .collect()
+        } else {
+            HashSet::new()
+        };
+
+        // 4. Remove missing transactions. Even if we are unable to download new transactions from
         // the daemon, we still want to remove the transactions that are no longer in the mempool.
         // [LOCK] Write lock is released at the end of the call to remove().
-        mempool.write().unwrap().remove(txids_to_remove);
+        mempool.write().unwrap().remove(txids_to_remove, &confirmed);
 
-        // 4. Download the new transactions from the daemon's mempool
+        // 5. Download the new transactions from the daemon's mempool
         // [LOCK] No lock taken, waiting for RPC response.
         let txs_to_add = daemon
             .gettransactions(&txids_to_add)
             .chain_err(|| format!("failed to get {} transactions", txids_to_add.len()))?;
 
-        // 4. Update local mempool to match daemon's state
+        // 6. Update local mempool to match daemon's state
         // [LOCK] Takes Write lock for whole scope.
         {
             let mut mempool = mempool.write().unwrap();
             // Add new transactions
-            if txs_to_add.len() > mempool.add(txs_to_add) {
+            let expected = txs_to_add.len();
+            let added = mempool.add(txs_to_add);
+            if expected > added {
                 debug!("Mempool update added less transactions than expected");
             }
+            mempool.loaded = true;
 
             mempool
                 .count
@@ -445,7 +765,7 @@ impl Mempool {
                 mempool.backlog_stats = (BacklogStats::new(&mempool.feeinfo), Instant::now());
             }
 
-            Ok(())
+            Ok((added, removed))
         }
     }
 
@@ -505,6 +825,9 @@ impl Mempool {
         // 6. Insert all TxHistory into history.
         // 7. Insert the tx edges into edges (HashMap of (Outpoint, (Txid, vin)))
         // 8. (Liquid only) Parse assets of tx.
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
         for txid in txids {
             let tx = self.txstore.get(&txid).expect("missing tx from txstore");
 
@@ -517,6 +840,28 @@ impl Mempool {
             };
             let txid_bytes = full_hash(&txid[..]);
 
+            // Mark the evicted txs spending the same inputs as replaced by this one
+            let mut replaced: HashMap<Txid, Vec<OutPoint>> = HashMap::new();
+            for txin in &tx.input {
+                if let Some(evicted) = self.evicted_spends.get(&txin.previous_output) {
+                    replaced
+                        .entry(*evicted)
+                        .or_default()
+                        .push(txin.previous_output);
+                }
+            }
+            let double_spend = !replaced.is_empty();
+            for (evicted, conflicting_inputs) in replaced {
+                for outpoint in &conflicting_inputs {
+                    self.evicted_spends.remove(outpoint);
+                }
+                if let Some((conflict, _)) = self.conflicts.get_mut(&evicted) {
+                    conflict.status = ConflictStatus::Replaced;
+                    conflict.replaced_by = Some(txid);
+                    conflict.conflicting_inputs = conflicting_inputs;
+                }
+            }
+
             // Get feeinfo for caching and recent tx overview
             let feeinfo = TxFeeInfo::new(tx, &prevouts, self.config.network_type);
 
@@ -527,9 +872,12 @@ impl Mempool {
                 vsize: feeinfo.vsize,
                 #[cfg(not(feature = "liquid"))]
                 value: prevouts.values().map(|prevout| prevout.value).sum(),
+                double_spend,
+                ancestor_feerate: None,
             });
 
             self.feeinfo.insert(txid, feeinfo);
+            self.first_seen.insert(txid, now);
 
             // An iterator over (ScriptHash, TxHistoryInfo)
             let spending = prevouts.into_iter().map(|(input_index, prevout)| {
@@ -566,12 +914,21 @@ impl Mempool {
                 });
 
             // Index funding/spending history entries and spend edges
+            let mut touched = HashSet::new();
             for (scripthash, entry) in funding.chain(spending) {
                 self.history.entry(scripthash).or_default().push(entry);
+                touched.insert(scripthash);
             }
-            for (i, txi) in tx.input.iter().enumerate() {
-                self.edges.insert(txi.previous_output, (txid, i as u32));
+            let events = self.chain.store().scripthash_events();
+            for scripthash in touched {
+                // Sending only fails when there are no subscribers
+                let _ = events.send(ScripthashEvent {
+                    scripthash,
+                    txid,
+                    confirmed: false,
+                });
             }
+            index_spend_edges(&mut self.edges, txid, tx);
 
             // Index issued assets & native asset pegins/pegouts/burns
             #[cfg(feature = "liquid")]
@@ -647,21 +1004,28 @@ impl Mempool {
             .collect()
     }
 
-    fn remove(&mut self, to_remove: HashSet<&Txid>) {
+    // The confirmed txids are told apart beforehand, they're only needed to track conflicts
+    fn remove(&mut self, to_remove: HashSet<&Txid>, confirmed: &HashSet<Txid>) {
         self.delta
             .with_label_values(&["remove"])
             .observe(to_remove.len() as f64);
         let _timer = self.latency.with_label_values(&["remove"]).start_timer();
 
         for txid in &to_remove {
-            self.txstore
+            let tx = self
+                .txstore
                 .remove(*txid)
                 .unwrap_or_else(|| panic!("missing mempool tx {}", txid));
 
+            if self.config.mempool_conflicts_history_size > 0 && !confirmed.contains(*txid) {
+                self.record_eviction(**txid, &tx);
+            }
+
             self.feeinfo.remove(*txid).or_else(|| {
                 warn!("missing mempool tx feeinfo {}", txid);
                 None
             });
+            self.first_seen.remove(*txid);
         }
 
         // TODO: make it more efficient (currently it takes O(|mempool|) time)
@@ -681,6 +1045,44 @@ impl Mempool {
             .retain(|_outpoint, (txid, _vin)| !to_remove.contains(txid));
     }
 
+    // Remember an unconfirmed tx leaving the mempool as evicted, until a tx spending the same
+    // inputs shows up and marks it as replaced. Replacements added while the tx was still in the
+    // mempool (through add_by_txid, for txs broadcast via /tx) already took over its spend edges.
+    fn record_eviction(&mut self, txid: Txid, tx: &Transaction) {
+        if let Some(forgotten) = self.conflicts_order.push_front(txid) {
+            if let Some((_, inputs)) = self.conflicts.remove(&forgotten) {
+                for outpoint in inputs {
+                    if self.evicted_spends.get(&outpoint) == Some(&forgotten) {
+                        self.evicted_spends.remove(&outpoint);
+                    }
+                }
+            }
+        }
+        let inputs: Vec<OutPoint> = tx
+            .input
+            .iter()
+            .filter(|txin| has_prevout(txin))
+            .map(|txin| txin.previous_output)
+            .collect();
+        let mut conflict = TxConflict::with_status(ConflictStatus::Evicted);
+        for outpoint in &inputs {
+            match self.edges.get(outpoint) {
+                Some((spender, _)) if *spender != txid => {
+                    conflict.status = ConflictStatus::Replaced;
+                    conflict.replaced_by = Some(*spender);
+                    conflict.conflicting_inputs.push(*outpoint);
+                }
+                _ => (),
+            }
+        }
+        if conflict.status == ConflictStatus::Evicted {
+            for outpoint in &inputs {
+                self.evicted_spends.insert(*outpoint, txid);
+            }
+        }
+        self.conflicts.insert(txid, (conflict, inputs));
+    }
+
     #[cfg(feature = "liquid")]
     pub fn asset_history(&self, asset_id: &AssetId, limit: usize) -> Vec<Transaction> {
         let _timer = self
@@ -695,6 +1097,41 @@ impl Mempool {
     }
 }
 
+// Record the tx as the spender of its inputs. A replacement takes over the edges of the txs it
+// conflicts with, which are only removed later on.
+fn index_spend_edges(edges: &mut HashMap<OutPoint, (Txid, u32)>, txid: Txid, tx: &Transaction) {
+    for (i, txi) in tx.input.iter().enumerate() {
+        edges.insert(txi.previous_output, (txid, i as u32));
+    }
+}
+
+// Whether a later mempool tx spends one of the same inputs, which happens while an RBF
+// replacement was added but the tx it replaced wasn't removed yet
+fn is_replaced(
+    txid: &Txid,
+    txstore: &BTreeMap<Txid, Transaction>,
+    edges: &HashMap<OutPoint, (Txid, u32)>,
+) -> bool {
+    txstore.get(txid).map_or(false, |tx| {
+        tx.input.iter().any(|txin| {
+            edges
+                .get(&txin.previous_output)
+                .map_or(false, |(spender, _)| spender != txid)
+        })
+    })
+}
+
+// Whether the outpoint is spent by a mempool tx, ignoring spends by replaced txs
+fn is_spent(
+    outpoint: &OutPoint,
+    txstore: &BTreeMap<Txid, Transaction>,
+    edges: &HashMap<OutPoint, (Txid, u32)>,
+) -> bool {
+    edges
+        .get(outpoint)
+        .map_or(false, |(spender, _)| !is_replaced(spender, txstore, edges))
+}
+
 #[derive(Serialize)]
 pub struct BacklogStats {
     pub count: u32,
@@ -728,3 +1165,63 @@ impl BacklogStats {
         }
     }
 }
+
+#[cfg(all(test, not(feature = "liquid")))]
+mod tests {
+    use super::{index_spend_edges, is_replaced, is_spent};
+    use crate::chain::{OutPoint, Script, Transaction, TxIn, TxOut, Txid, Witness};
+    use std::collections::{BTreeMap, HashMap};
+
+    fn outpoint(vout: u32) -> OutPoint {
+        OutPoint {
+            txid: "0000000000000000000000000000000000000000000000000000000000000001"
+                .parse()
+                .unwrap(),
+            vout,
+        }
+    }
+
+    fn tx(prevouts: &[OutPoint], value: u64) -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: 0,
+            input: prevouts
+                .iter()
+                .map(|prevout| TxIn {
+                    previous_output: *prevout,
+                    script_sig: Script::new(),
+                    sequence: 0xfffffffd,
+                    witness: Witness::default(),
+                })
+                .collect(),
+            output: vec![TxOut {
+                value,
+                script_pubkey: Script::new(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_conflicting_txs() {
+        // the replacement spends one of the original's inputs, and is added before the
+        // original gets removed
+        let original = tx(&[outpoint(0), outpoint(1)], 1000);
+        let replacement = tx(&[outpoint(0), outpoint(2)], 900);
+        let (original_txid, replacement_txid) = (original.txid(), replacement.txid());
+
+        let mut txstore: BTreeMap<Txid, Transaction> = BTreeMap::new();
+        let mut edges = HashMap::new();
+        for (txid, tx) in [(original_txid, original), (replacement_txid, replacement)] {
+            index_spend_edges(&mut edges, txid, &tx);
+            txstore.insert(txid, tx);
+        }
+
+        assert!(is_replaced(&original_txid, &txstore, &edges));
+        assert!(!is_replaced(&replacement_txid, &txstore, &edges));
+        // only the replacement's spends count
+        assert!(is_spent(&outpoint(0), &txstore, &edges));
+        assert!(!is_spent(&outpoint(1), &txstore, &edges));
+        assert!(is_spent(&outpoint(2), &txstore, &edges));
+        assert!(!is_spent(&outpoint(3), &txstore, &edges));
+    }
+}
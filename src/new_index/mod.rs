@@ -7,9 +7,10 @@ pub mod schema;
 
 pub use self::db::{DBRow, DB};
 pub use self::fetch::{BlockEntry, FetchFrom};
-pub use self::mempool::Mempool;
+pub use self::mempool::{ConflictStatus, Mempool, PackageFeerate, TxConflict, TxFeeEntry};
 pub use self::query::Query;
 pub use self::schema::{
-    compute_script_hash, parse_hash, ChainQuery, FundingInfo, Indexer, ScriptStats, SpendingInfo,
-    SpendingInput, Store, TxHistoryInfo, TxHistoryKey, TxHistoryRow, Utxo,
+    compute_script_hash, parse_hash, ChainQuery, FundingInfo, Indexer, ScriptStats,
+    ScripthashEvent, SpendingInfo, SpendingInput, Store, TxHistoryInfo, TxHistoryKey, TxHistoryRow,
+    Utxo,
 };
@@ -1,6 +1,6 @@
 use crate::errors::*;
 use crate::new_index::ChainQuery;
-use crate::util::{full_hash, FullHash};
+use crate::util::{full_hash, CancelToken, FullHash};
 
 use rayon::prelude::*;
 
@@ -32,7 +32,7 @@ pub fn precache(chain: Arc<ChainQuery>, scripthashes: Vec<FullHash>, threads: us
                 .for_each(|scripthash| {
                     // First, cache
                     chain.stats(&scripthash[..], crate::new_index::db::DBFlush::Disable);
-                    let _ = chain.utxo(&scripthash[..], usize::MAX, crate::new_index::db::DBFlush::Disable);
+                    let _ = chain.utxo(&scripthash[..], usize::MAX, crate::new_index::db::DBFlush::Disable, &CancelToken::default());
 
                     // Then, increment the counter
                     let pre_increment = counter.fetch_add(1, std::sync::atomic::Ordering::AcqRel);
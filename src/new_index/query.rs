@@ -1,15 +1,29 @@
 use rayon::prelude::*;
 
-use std::collections::{BTreeSet, HashMap};
+#[cfg(not(feature = "liquid"))]
+use bitcoin::consensus::encode::serialize;
+#[cfg(feature = "liquid")]
+use elements::encode::serialize;
+
+use std::cmp;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::sync::{Arc, RwLock, RwLockReadGuard};
 use std::time::{Duration, Instant};
+use tokio::sync::watch;
 
-use crate::chain::{Network, OutPoint, Transaction, TxOut, Txid};
+use crate::chain::{BlockHash, Network, OutPoint, Transaction, TxOut, Txid};
 use crate::config::Config;
-use crate::daemon::{Daemon, MempoolAcceptResult};
+use crate::daemon::{BlockchainInfo, Daemon, MempoolAcceptResult, SubmitPackageResult};
 use crate::errors::*;
-use crate::new_index::{ChainQuery, Mempool, ScriptStats, SpendingInput, Utxo};
-use crate::util::{is_spendable, BlockId, Bytes, TransactionStatus};
+use crate::metrics::{Counter, MetricOpts, Metrics};
+use crate::new_index::{
+    compute_script_hash, ChainQuery, Mempool, ScriptStats, SpendingInput, TxFeeEntry, Utxo,
+};
+use crate::util::fees::estimate_fees_from_histogram;
+use crate::util::{
+    extract_tx_prevouts, get_tx_fee, has_prevout, is_spendable, raw_tx_output_count, script_type,
+    BlockId, Bytes, CancelToken, FullHash, TransactionStatus,
+};
 
 #[cfg(feature = "liquid")]
 use crate::{
@@ -23,6 +37,39 @@ const CONF_TARGETS: [u16; 28] = [
     1u16, 2u16, 3u16, 4u16, 5u16, 6u16, 7u16, 8u16, 9u16, 10u16, 11u16, 12u16, 13u16, 14u16, 15u16,
     16u16, 17u16, 18u16, 19u16, 20u16, 21u16, 22u16, 23u16, 24u16, 25u16, 144u16, 504u16, 1008u16,
 ];
+const BLOCK_STATS_CACHE_SIZE: usize = 1_000;
+const BLOCK_STATS_PERCENTILES: [usize; 5] = [10, 25, 50, 75, 90];
+const BLOCK_TX_FEES_CACHE_SIZE: usize = 100;
+
+#[derive(Serialize, Default)]
+pub struct UtxoSum {
+    pub confirmed: u64,
+    pub unconfirmed: u64,
+    pub utxo_count: usize,
+    // confidential outputs can't be summed, they are only counted
+    #[cfg(feature = "liquid")]
+    pub confidential_count: usize,
+}
+
+#[derive(Serialize, Default)]
+pub struct ScriptTypeStats {
+    pub count: usize,
+    pub value: u64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct BlockStats {
+    pub height: usize,
+    pub tx_count: usize,
+    pub total_fees: u64,
+    pub subsidy: u64,
+    pub reward: u64,
+    // feerates are in sat/vB and exclude the coinbase transaction
+    pub min_feerate: f64,
+    pub max_feerate: f64,
+    pub median_feerate: f64,
+    pub feerate_percentiles: Vec<f64>,
+}
 
 pub struct Query {
     chain: Arc<ChainQuery>, // TODO: should be used as read-only
@@ -31,6 +78,12 @@ pub struct Query {
     config: Arc<Config>,
     cached_estimates: RwLock<(HashMap<u16, f64>, Option<Instant>)>,
     cached_relayfee: RwLock<Option<f64>>,
+    cached_block_stats: RwLock<HashMap<BlockHash, BlockStats>>,
+    cached_block_tx_fees: RwLock<HashMap<BlockHash, Arc<Vec<TxFeeEntry>>>>,
+    utxo_scans_aborted: Counter,
+    // Bumped whenever the chain tip or the mempool changes, for handlers waiting on updates.
+    // A receiver is kept around so that sending never fails for lack of subscribers.
+    updates: (watch::Sender<u64>, watch::Receiver<u64>),
     #[cfg(feature = "liquid")]
     asset_db: Option<Arc<RwLock<AssetRegistry>>>,
 }
@@ -42,6 +95,7 @@ impl Query {
         mempool: Arc<RwLock<Mempool>>,
         daemon: Arc<Daemon>,
         config: Arc<Config>,
+        metrics: &Metrics,
     ) -> Self {
         Query {
             chain,
@@ -50,9 +104,27 @@ impl Query {
             config,
             cached_estimates: RwLock::new((HashMap::new(), None)),
             cached_relayfee: RwLock::new(None),
+            cached_block_stats: RwLock::new(HashMap::new()),
+            cached_block_tx_fees: RwLock::new(HashMap::new()),
+            updates: watch::channel(0),
+            utxo_scans_aborted: metrics.counter(MetricOpts::new(
+                "query_utxo_scans_aborted",
+                "# of utxo lookups aborted for exceeding max_utxos_per_query",
+            )),
         }
     }
 
+    /// Wake up handlers waiting for the chain tip or the mempool to change
+    pub fn notify_updated(&self) {
+        let (sender, receiver) = &self.updates;
+        let generation = *receiver.borrow();
+        sender.send(generation + 1).ok();
+    }
+
+    pub fn subscribe_updates(&self) -> watch::Receiver<u64> {
+        self.updates.1.clone()
+    }
+
     pub fn chain(&self) -> &ChainQuery {
         &self.chain
     }
@@ -69,8 +141,8 @@ impl Query {
         self.mempool.read().unwrap()
     }
 
-    pub fn broadcast_raw(&self, txhex: &str) -> Result<Txid> {
-        let txid = self.daemon.broadcast_raw(txhex)?;
+    pub fn broadcast_raw(&self, txhex: &str, maxfeerate: Option<f64>) -> Result<Txid> {
+        let txid = self.daemon.broadcast_raw(txhex, maxfeerate)?;
         // The important part is whether we succeeded in broadcasting.
         // Ignore errors in adding to the cache and show an internal warning.
         if let Err(e) = self
@@ -87,6 +159,34 @@ impl Query {
         Ok(txid)
     }
 
+    /// Submit a package of transactions, which must be topologically sorted (parents first)
+    pub fn submit_package(&self, txs: &[Transaction]) -> Result<SubmitPackageResult> {
+        let txhex = txs.iter().map(|tx| hex::encode(serialize(tx))).collect();
+        let result = self.daemon.submit_package(txhex)?;
+
+        // Make the accepted txs visible right away rather than on the next mempool update,
+        // adding them parents-first. As with broadcast_raw(), failures here are only logged.
+        let accepted: HashSet<String> = result
+            .tx_results
+            .values()
+            .filter(|tx_result| tx_result.error.is_none())
+            .map(|tx_result| tx_result.txid.clone())
+            .collect();
+        let mut mempool = self.mempool.write().unwrap();
+        for txid in txs.iter().map(|tx| tx.txid()) {
+            if !accepted.contains(&txid.to_string()) {
+                continue;
+            }
+            if let Err(e) = mempool.add_by_txid(&self.daemon, &txid) {
+                warn!(
+                    "submitpackage of {txid} succeeded to broadcast \
+                    but failed to add to mempool-electrs Mempool cache: {e}"
+                );
+            }
+        }
+        Ok(result)
+    }
+
     pub fn test_mempool_accept(
         &self,
         txhex: Vec<String>,
@@ -95,18 +195,91 @@ impl Query {
         self.daemon.test_mempool_accept(txhex, maxfeerate)
     }
 
-    pub fn utxo(&self, scripthash: &[u8]) -> Result<Vec<Utxo>> {
-        let mut utxos = self.chain.utxo(
-            scripthash,
-            self.config.utxos_limit,
-            super::db::DBFlush::Enable,
-        )?;
+    /// The height of the daemon's best block, which the index may still be catching up to
+    pub fn daemon_height(&self) -> Result<usize> {
+        Ok(self.daemon.getblockchaininfo()?.blocks as usize)
+    }
+
+    pub fn daemon_blockchain_info(&self) -> Result<BlockchainInfo> {
+        self.daemon.getblockchaininfo()
+    }
+
+    pub fn utxo(&self, scripthash: &[u8], cancel: &CancelToken) -> Result<Vec<Utxo>> {
+        let limit = cmp::min(self.config.utxos_limit, self.config.max_utxos_per_query);
+        let mut utxos = self
+            .chain
+            .utxo(scripthash, limit, super::db::DBFlush::Enable, cancel)
+            .map_err(|e| {
+                if let ErrorKind::TooManyUtxos(_) = e.kind() {
+                    self.utxo_scans_aborted.inc();
+                }
+                e
+            })?;
         let mempool = self.mempool();
         utxos.retain(|utxo| !mempool.has_spend(&OutPoint::from(utxo)));
         utxos.extend(mempool.utxo(scripthash));
+        if utxos.len() > self.config.max_utxos_per_query {
+            self.utxo_scans_aborted.inc();
+            bail!(ErrorKind::TooManyUtxos(self.config.max_utxos_per_query));
+        }
         Ok(utxos)
     }
 
+    /// Sum the utxos of multiple scripthashes. Duplicated scripthashes are only counted once.
+    pub fn utxo_sum(&self, scripthashes: &[FullHash], cancel: &CancelToken) -> Result<UtxoSum> {
+        let mut sum = UtxoSum::default();
+        let scripthashes: HashSet<&FullHash> = scripthashes.iter().collect();
+
+        for scripthash in scripthashes {
+            for utxo in self.utxo(&scripthash[..], cancel)? {
+                sum.utxo_count += 1;
+
+                #[cfg(not(feature = "liquid"))]
+                let value = utxo.value;
+                #[cfg(feature = "liquid")]
+                let value = match utxo.value.explicit() {
+                    Some(value) => value,
+                    None => {
+                        sum.confidential_count += 1;
+                        continue;
+                    }
+                };
+
+                if utxo.confirmed.is_some() {
+                    sum.confirmed += value;
+                } else {
+                    sum.unconfirmed += value;
+                }
+            }
+        }
+
+        Ok(sum)
+    }
+
+    /// Returns every output funding the scripthash (confirmed first, then unconfirmed),
+    /// along with whether it is spent by a confirmed or mempool transaction.
+    pub fn funded_outpoints(
+        &self,
+        scripthash: &[u8],
+        limit: usize,
+    ) -> Result<Vec<(OutPoint, bool)>> {
+        let mut outpoints = self.chain.funded_outpoints(scripthash, limit)?;
+        let mempool = self.mempool();
+        for (outpoint, spent) in outpoints.iter_mut() {
+            *spent = *spent || mempool.has_spend(outpoint);
+        }
+        outpoints.extend(
+            mempool
+                .funded_outpoints(scripthash)
+                .into_iter()
+                .map(|outpoint| (outpoint, mempool.has_spend(&outpoint))),
+        );
+        if outpoints.len() > limit {
+            bail!(ErrorKind::TooManyTxs(limit));
+        }
+        Ok(outpoints)
+    }
+
     pub fn history_txids(&self, scripthash: &[u8], limit: usize) -> Vec<(Txid, Option<BlockId>)> {
         let confirmed_txids = self.chain.history_txids(scripthash, limit);
         let confirmed_len = confirmed_txids.len();
@@ -121,6 +294,39 @@ impl Query {
         confirmed_txids.chain(mempool_txids).collect()
     }
 
+    /// Count and sum the outputs of the scripthash's transactions by script type,
+    /// excluding the outputs paying to the scripthash itself.
+    pub fn script_type_stats(
+        &self,
+        scripthash: &[u8],
+        limit: usize,
+    ) -> BTreeMap<&'static str, ScriptTypeStats> {
+        let mut stats: BTreeMap<&'static str, ScriptTypeStats> = BTreeMap::new();
+
+        for (txid, _) in self.history_txids(scripthash, limit) {
+            let tx = match self.lookup_txn(&txid) {
+                Some(tx) => tx,
+                None => continue,
+            };
+            for txout in tx.output {
+                if compute_script_hash(&txout.script_pubkey)[..] == scripthash[..] {
+                    continue;
+                }
+
+                #[cfg(not(feature = "liquid"))]
+                let value = txout.value;
+                #[cfg(feature = "liquid")]
+                let value = txout.value.explicit().unwrap_or(0);
+
+                let entry = stats.entry(script_type(&txout.script_pubkey)).or_default();
+                entry.count += 1;
+                entry.value += value;
+            }
+        }
+
+        stats
+    }
+
     pub fn stats(&self, scripthash: &[u8]) -> (ScriptStats, ScriptStats) {
         (
             self.chain.stats(scripthash, super::db::DBFlush::Enable),
@@ -170,8 +376,56 @@ impl Query {
             .collect()
     }
 
+    /// Get the number of outputs of the transaction, without decoding the whole transaction
+    pub fn tx_output_count(&self, txid: &Txid) -> Option<u32> {
+        self.chain
+            .lookup_raw_txn(txid, None)
+            .and_then(|raw| raw_tx_output_count(&raw))
+            .or_else(|| self.mempool().tx_output_count(txid))
+    }
+
+    /// Look up the spends of `count` outputs of the transaction, starting at vout `start`.
+    /// Outputs that don't exist or are unspendable are reported as unspent.
+    pub fn lookup_spends_range(
+        &self,
+        txid: &Txid,
+        start: u32,
+        count: u32,
+    ) -> Vec<Option<SpendingInput>> {
+        (start..start.saturating_add(count))
+            .into_par_iter()
+            .map(|vout| self.lookup_spend(&OutPoint { txid: *txid, vout }))
+            .collect()
+    }
+
+    /// Find confirmed and mempool txids starting with the given hex prefix, returning up to
+    /// `limit` of each and whether there were more matches
+    pub fn txid_search(
+        &self,
+        prefix: &str,
+        limit: usize,
+    ) -> (Vec<(Txid, BlockId)>, Vec<Txid>, bool) {
+        let mut confirmed = self.chain.txid_search(prefix, limit + 1);
+        let prefix = prefix.to_lowercase();
+        let mut unconfirmed: Vec<Txid> = self
+            .mempool()
+            .txids()
+            .into_iter()
+            .filter(|txid| txid.to_string().starts_with(&prefix))
+            .take(limit + 1)
+            .copied()
+            .collect();
+
+        let truncated = confirmed.len() > limit || unconfirmed.len() > limit;
+        confirmed.truncate(limit);
+        unconfirmed.truncate(limit);
+        (confirmed, unconfirmed, truncated)
+    }
+
     pub fn get_tx_status(&self, txid: &Txid) -> TransactionStatus {
-        TransactionStatus::from(self.chain.tx_confirming_block(txid))
+        let mut status = TransactionStatus::from(self.chain.tx_confirming_block(txid));
+        self.mempool().add_status_details(txid, &mut status);
+        status
     }
 
     pub fn get_mempool_tx_fee(&self, txid: &Txid) -> Option<u64> {
@@ -223,6 +477,24 @@ impl Query {
         }
     }
 
+    /// Fee estimates derived from the local mempool fee histogram, without consulting the
+    /// daemon's estimator. Falls back to the relay fee for targets the mempool can't fill.
+    pub fn mempool_fee_estimates(&self) -> HashMap<u16, f64> {
+        let min_feerate = self.get_relayfee().unwrap_or(1.0);
+        let mempool = self.mempool();
+        estimate_fees_from_histogram(
+            &mempool.backlog_stats().fee_histogram,
+            &CONF_TARGETS,
+            min_feerate,
+        )
+    }
+
+    /// Force a sync of the mempool with the daemon, returning the number of (added, removed)
+    /// transactions
+    pub fn resync_mempool(&self) -> Result<(usize, usize)> {
+        Mempool::update(&self.mempool, &self.daemon)
+    }
+
     pub fn get_relayfee(&self) -> Result<f64> {
         if let Some(cached) = *self.cached_relayfee.read().unwrap() {
             return Ok(cached);
@@ -233,12 +505,136 @@ impl Query {
         Ok(relayfee)
     }
 
+    /// Fee and reward statistics for a block. Results are cached by block hash,
+    /// since the contents of a block never change.
+    /// Returns `Ok(None)` for unknown blocks, and fails if the block's prevouts aren't indexed.
+    pub fn block_stats(&self, blockhash: &BlockHash) -> Result<Option<BlockStats>> {
+        if let Some(stats) = self.cached_block_stats.read().unwrap().get(blockhash) {
+            return Ok(Some(stats.clone()));
+        }
+
+        let (height, txs) = match (
+            self.chain.height_by_hash(blockhash),
+            self.chain.get_block_txs(blockhash),
+        ) {
+            (Some(height), Some(txs)) => (height, txs),
+            _ => return Ok(None),
+        };
+        let fees = self.block_fees(&txs)?;
+
+        let mut total_fees = 0;
+        let mut feerates = vec![];
+        // skip the coinbase, which is always the first transaction
+        for (tx, fee) in txs.iter().zip(fees).skip(1) {
+            total_fees += fee;
+            feerates.push(fee as f64 / (tx.weight() as f64 / 4.0));
+        }
+        feerates.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let percentile = |p: usize| {
+            if feerates.is_empty() {
+                0.0
+            } else {
+                feerates[(feerates.len() - 1) * p / 100]
+            }
+        };
+
+        let subsidy = self.network().block_subsidy(height);
+        let stats = BlockStats {
+            height,
+            tx_count: txs.len(),
+            total_fees,
+            subsidy,
+            reward: subsidy + total_fees,
+            min_feerate: percentile(0),
+            max_feerate: percentile(100),
+            median_feerate: percentile(50),
+            feerate_percentiles: BLOCK_STATS_PERCENTILES
+                .iter()
+                .map(|p| percentile(*p))
+                .collect(),
+        };
+
+        let mut cache = self.cached_block_stats.write().unwrap();
+        if cache.len() >= BLOCK_STATS_CACHE_SIZE {
+            // evict an arbitrary entry to keep the cache bounded
+            if let Some(evict) = cache.keys().next().copied() {
+                cache.remove(&evict);
+            }
+        }
+        cache.insert(*blockhash, stats.clone());
+
+        Ok(Some(stats))
+    }
+
+    /// The fee of every transaction in a block, in block order (the coinbase has no fee).
+    /// Results are cached by block hash, since the contents of a block never change.
+    /// Returns `Ok(None)` for unknown blocks, and fails if the block's prevouts aren't indexed.
+    pub fn block_tx_fees(&self, blockhash: &BlockHash) -> Result<Option<Arc<Vec<TxFeeEntry>>>> {
+        if let Some(fees) = self.cached_block_tx_fees.read().unwrap().get(blockhash) {
+            return Ok(Some(Arc::clone(fees)));
+        }
+
+        let txs = match self.chain.get_block_txs(blockhash) {
+            Some(txs) => txs,
+            None => return Ok(None),
+        };
+        let fees = self
+            .block_fees(&txs)?
+            .into_iter()
+            .zip(&txs)
+            .map(|(fee, tx)| {
+                let vsize = (tx.weight() as u32 + 3) / 4;
+                TxFeeEntry {
+                    txid: tx.txid(),
+                    fee,
+                    vsize,
+                    feerate: fee as f64 / vsize as f64,
+                }
+            })
+            .collect();
+        let fees = Arc::new(fees);
+
+        let mut cache = self.cached_block_tx_fees.write().unwrap();
+        if cache.len() >= BLOCK_TX_FEES_CACHE_SIZE {
+            // evict an arbitrary entry to keep the cache bounded
+            if let Some(evict) = cache.keys().next().copied() {
+                cache.remove(&evict);
+            }
+        }
+        cache.insert(*blockhash, Arc::clone(&fees));
+
+        Ok(Some(fees))
+    }
+
+    // The fee paid by each of the block's transactions, looking up all their prevouts at once
+    fn block_fees(&self, txs: &[Transaction]) -> Result<Vec<u64>> {
+        let outpoints = txs
+            .iter()
+            .flat_map(|tx| {
+                tx.input
+                    .iter()
+                    .filter(|txin| has_prevout(txin))
+                    .map(|txin| txin.previous_output)
+            })
+            .collect();
+        let txos = self.chain.lookup_txos(&outpoints);
+
+        txs.iter()
+            .map(|tx| {
+                let prevouts = extract_tx_prevouts(tx, &txos)?;
+                Ok(get_tx_fee(tx, &prevouts, self.network()))
+            })
+            .collect()
+    }
+
     #[cfg(feature = "liquid")]
     pub fn new(
         chain: Arc<ChainQuery>,
         mempool: Arc<RwLock<Mempool>>,
         daemon: Arc<Daemon>,
         config: Arc<Config>,
+        metrics: &Metrics,
         asset_db: Option<Arc<RwLock<AssetRegistry>>>,
     ) -> Self {
         Query {
@@ -249,6 +645,13 @@ impl Query {
             asset_db,
             cached_estimates: RwLock::new((HashMap::new(), None)),
             cached_relayfee: RwLock::new(None),
+            cached_block_stats: RwLock::new(HashMap::new()),
+            cached_block_tx_fees: RwLock::new(HashMap::new()),
+            updates: watch::channel(0),
+            utxo_scans_aborted: metrics.counter(MetricOpts::new(
+                "query_utxo_scans_aborted",
+                "# of utxo lookups aborted for exceeding max_utxos_per_query",
+            )),
         }
     }
 
@@ -14,10 +14,13 @@ use elements::{
     AssetId,
 };
 
-use std::collections::{BTreeSet, HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
 use std::convert::TryInto;
 use std::path::Path;
-use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
 
 use crate::chain::{
     BlockHash, BlockHeader, Network, OutPoint, Script, Transaction, TxOut, Txid, Value,
@@ -28,10 +31,10 @@ use crate::errors::*;
 use crate::metrics::{Gauge, HistogramOpts, HistogramTimer, HistogramVec, MetricOpts, Metrics};
 use crate::util::{
     bincode_util, full_hash, has_prevout, is_spendable, BlockHeaderMeta, BlockId, BlockMeta,
-    BlockStatus, Bytes, HeaderEntry, HeaderList, ScriptToAddr,
+    BlockStatus, Bytes, CancelToken, HeaderEntry, HeaderList, ScriptToAddr,
 };
 
-use crate::new_index::db::{DBFlush, DBRow, ReverseScanIterator, ScanIterator, DB};
+use crate::new_index::db::{DBFlush, DBRow, DBStats, ReverseScanIterator, ScanIterator, DB};
 use crate::new_index::fetch::{start_fetcher, BlockEntry, FetchFrom};
 
 #[cfg(feature = "liquid")]
@@ -41,6 +44,21 @@ use super::db::ReverseScanGroupIterator;
 
 const MIN_HISTORY_ITEMS_TO_CACHE: usize = 100;
 
+// Number of scripthash events buffered for slow subscribers before they start lagging behind
+const SCRIPTHASH_EVENTS_CAPACITY: usize = 10_000;
+
+// Emitted whenever a transaction touching a scripthash enters the mempool, or gets confirmed (or
+// unconfirmed by a reorg) as the index is updated
+#[derive(Clone, Debug)]
+pub struct ScripthashEvent {
+    pub scripthash: FullHash,
+    pub txid: Txid,
+    pub confirmed: bool,
+}
+
+// How far back the indexing throughput reported by Store::sync_rate() looks
+const SYNC_RATE_WINDOW: Duration = Duration::from_secs(60);
+
 pub struct Store {
     // TODO: should be column families
     txstore_db: DB,
@@ -49,6 +67,10 @@ pub struct Store {
     added_blockhashes: RwLock<HashSet<BlockHash>>,
     indexed_blockhashes: RwLock<HashSet<BlockHash>>,
     indexed_headers: RwLock<HeaderList>,
+    sync_rate: Mutex<SyncRate>,
+    sync_target_height: RwLock<Option<usize>>, // the daemon's tip height as of the last update
+    updated: AtomicBool, // whether the indexer completed an update since startup
+    scripthash_events: broadcast::Sender<ScripthashEvent>,
 }
 
 impl Store {
@@ -83,6 +105,10 @@ impl Store {
             added_blockhashes: RwLock::new(added_blockhashes),
             indexed_blockhashes: RwLock::new(indexed_blockhashes),
             indexed_headers: RwLock::new(headers),
+            sync_rate: Mutex::new(SyncRate::default()),
+            sync_target_height: RwLock::new(None),
+            updated: AtomicBool::new(false),
+            scripthash_events: broadcast::channel(SCRIPTHASH_EVENTS_CAPACITY).0,
         }
     }
 
@@ -98,9 +124,82 @@ impl Store {
         &self.cache_db
     }
 
+    /// The channel both the mempool and the indexer send scripthash events to
+    pub fn scripthash_events(&self) -> &broadcast::Sender<ScripthashEvent> {
+        &self.scripthash_events
+    }
+
+    pub fn db_stats(&self) -> Vec<(&'static str, DBStats)> {
+        vec![
+            ("txstore", self.txstore_db.stats()),
+            ("history", self.history_db.stats()),
+            ("cache", self.cache_db.stats()),
+        ]
+    }
+
     pub fn done_initial_sync(&self) -> bool {
         self.txstore_db.get(b"t").is_some()
     }
+
+    /// Whether the indexer caught up with the daemon's tip at least once since startup. Until
+    /// then, the chain tip may be missing or far behind.
+    pub fn done_first_update(&self) -> bool {
+        self.updated.load(Ordering::Acquire)
+    }
+
+    /// The number of blocks added to the txstore and indexed into the history db so far
+    pub fn synced_blocks(&self) -> (usize, usize) {
+        (
+            self.added_blockhashes.read().unwrap().len(),
+            self.indexed_blockhashes.read().unwrap().len(),
+        )
+    }
+
+    /// The number of blocks added or indexed per second, over the last minute
+    pub fn sync_rate(&self) -> f64 {
+        self.sync_rate
+            .lock()
+            .unwrap()
+            .blocks_per_second(Instant::now())
+    }
+}
+
+// Rolling window of the number of blocks processed by the indexer
+#[derive(Default)]
+struct SyncRate {
+    samples: VecDeque<(Instant, usize)>,
+    since: Option<Instant>,
+}
+
+impl SyncRate {
+    fn record(&mut self, now: Instant, blocks: usize) {
+        self.since.get_or_insert(now);
+        self.samples.push_back((now, blocks));
+        self.prune(now);
+    }
+
+    fn prune(&mut self, now: Instant) {
+        while let Some(&(at, _)) = self.samples.front() {
+            if now.duration_since(at) <= SYNC_RATE_WINDOW {
+                break;
+            }
+            self.samples.pop_front();
+        }
+    }
+
+    fn blocks_per_second(&mut self, now: Instant) -> f64 {
+        self.prune(now);
+        let elapsed = match self.since {
+            // don't underestimate the rate during the first window
+            Some(since) => now.duration_since(since).min(SYNC_RATE_WINDOW),
+            None => return 0.0,
+        };
+        if elapsed.is_zero() {
+            return 0.0;
+        }
+        let blocks: usize = self.samples.iter().map(|(_, blocks)| blocks).sum();
+        blocks as f64 / elapsed.as_secs_f64()
+    }
 }
 
 type UtxoMap = HashMap<OutPoint, (BlockId, Value)>;
@@ -182,6 +281,7 @@ pub struct Indexer {
 struct IndexerConfig {
     light_mode: bool,
     address_search: bool,
+    txid_search: bool,
     index_unspendables: bool,
     network: Network,
     #[cfg(feature = "liquid")]
@@ -193,6 +293,7 @@ impl From<&Config> for IndexerConfig {
         IndexerConfig {
             light_mode: config.light_mode,
             address_search: config.address_search,
+            txid_search: config.txid_search,
             index_unspendables: config.index_unspendables,
             network: config.network_type,
             #[cfg(feature = "liquid")]
@@ -272,6 +373,17 @@ impl Indexer {
         let daemon = daemon.reconnect()?;
         let tip = daemon.getbestblockhash()?;
         let new_headers = self.get_new_headers(&daemon, &tip)?;
+        let target_height = match new_headers.last() {
+            Some(entry) => entry.height(),
+            None => self
+                .store
+                .indexed_headers
+                .read()
+                .unwrap()
+                .len()
+                .saturating_sub(1),
+        };
+        *self.store.sync_target_height.write().unwrap() = Some(target_height);
 
         let to_add = self.headers_to_add(&new_headers);
         debug!(
@@ -302,6 +414,22 @@ impl Indexer {
         debug!("updating synced tip to {:?}", tip);
         self.store.txstore_db.put_sync(b"t", &serialize(&tip));
 
+        // Blocks disconnected by a reorg are reported to scripthash subscribers as unconfirmed,
+        // and all the newly connected ones as confirmed
+        let changed_blocks = if self.store.scripthash_events.receiver_count() > 0 {
+            let headers = self.store.indexed_headers.read().unwrap();
+            let fork_height = new_headers.first().map_or(headers.len(), |e| e.height());
+            let disconnected: Vec<BlockHash> = headers
+                .iter()
+                .skip(fork_height)
+                .map(|e| *e.hash())
+                .collect();
+            let connected: Vec<BlockHash> = new_headers.iter().map(|e| *e.hash()).collect();
+            Some((disconnected, connected))
+        } else {
+            None
+        };
+
         let mut headers = self.store.indexed_headers.write().unwrap();
         headers.apply(new_headers);
         assert_eq!(tip, *headers.tip());
@@ -311,10 +439,66 @@ impl Indexer {
         }
 
         self.tip_metric.set(headers.len() as i64 - 1);
+        self.store.updated.store(true, Ordering::Release);
+        drop(headers);
+
+        if let Some((disconnected, connected)) = changed_blocks {
+            for (blockhashes, confirmed) in [(disconnected, false), (connected, true)] {
+                if let Err(e) = self.send_scripthash_events(&daemon, &blockhashes, confirmed) {
+                    warn!("failed to send scripthash events: {}", e);
+                }
+            }
+        }
 
         Ok(tip)
     }
 
+    // Notify the subscribers about every scripthash funded or spent by the txs of the blocks
+    fn send_scripthash_events(
+        &self,
+        daemon: &Daemon,
+        blockhashes: &[BlockHash],
+        confirmed: bool,
+    ) -> Result<()> {
+        if blockhashes.is_empty() {
+            return Ok(());
+        }
+        let blocks = daemon.getblocks(blockhashes)?;
+        let prevouts = blocks
+            .iter()
+            .flat_map(|b| b.txdata.iter())
+            .flat_map(|tx| tx.input.iter())
+            .filter(|txin| has_prevout(txin))
+            .map(|txin| txin.previous_output)
+            .collect();
+        let prevout_txos = lookup_txos(&self.store.txstore_db, &prevouts, true);
+
+        for tx in blocks.iter().flat_map(|b| b.txdata.iter()) {
+            let txid = tx.txid();
+            let funded = tx
+                .output
+                .iter()
+                .filter(|txo| is_spendable(txo) || self.iconfig.index_unspendables);
+            let spent = tx
+                .input
+                .iter()
+                .filter_map(|txin| prevout_txos.get(&txin.previous_output));
+            let touched: HashSet<FullHash> = funded
+                .chain(spent)
+                .map(|txo| compute_script_hash(&txo.script_pubkey))
+                .collect();
+            for scripthash in touched {
+                // Sending only fails when there are no subscribers
+                let _ = self.store.scripthash_events.send(ScripthashEvent {
+                    scripthash,
+                    txid,
+                    confirmed,
+                });
+            }
+        }
+        Ok(())
+    }
+
     fn add(&self, blocks: &[BlockEntry]) {
         debug!("Adding {} blocks to Indexer", blocks.len());
         // TODO: skip orphaned blocks?
@@ -327,6 +511,7 @@ impl Indexer {
             self.store.txstore_db.write(rows, self.flush);
         }
 
+        self.record_sync_rate(blocks.len());
         self.store
             .added_blockhashes
             .write()
@@ -361,6 +546,12 @@ impl Indexer {
             index_blocks(blocks, &previous_txos_map, &self.iconfig)
         };
         self.store.history_db.write(rows, self.flush);
+        self.record_sync_rate(blocks.len());
+    }
+
+    fn record_sync_rate(&self, blocks: usize) {
+        let mut sync_rate = self.store.sync_rate.lock().unwrap();
+        sync_rate.record(Instant::now(), blocks);
     }
 }
 
@@ -407,6 +598,26 @@ impl ChainQuery {
         }
     }
 
+    /// The txid of the block's coinbase, without deserializing the txids of the rest of the block
+    pub fn get_block_coinbase_txid(&self, hash: &BlockHash) -> Option<Txid> {
+        let _timer = self.start_timer("get_block_coinbase_txid");
+
+        if self.light_mode {
+            let mut blockinfo = self.daemon.getblock_raw(hash, 1).ok()?;
+            Some(serde_json::from_value(blockinfo["tx"][0].take()).unwrap())
+        } else {
+            self.store
+                .txstore_db
+                .get(&BlockRow::txids_key(full_hash(&hash[..])))
+                .map(|val| {
+                    // the txids are serialized as a length-prefixed list, the coinbase first
+                    let (_len, txid): (u64, Txid) = bincode_util::deserialize_little(&val)
+                        .expect("failed to parse block txids");
+                    txid
+                })
+        }
+    }
+
     pub fn get_block_txs(&self, hash: &BlockHash) -> Option<Vec<Transaction>> {
         let _timer = self.start_timer("get_block_txs");
 
@@ -544,6 +755,8 @@ impl ChainQuery {
         iter: impl Iterator<Item = TxHistoryRow>,
         last_seen_txid: Option<&Txid>,
         limit: usize,
+        ascending: bool,
+        cancel: &CancelToken,
     ) -> Vec<TxHistorySummary> {
         // collate utxo funding/spending events by transaction
 
@@ -563,7 +776,7 @@ impl ChainQuery {
             });
         let mut map: HashMap<Txid, TxHistorySummary> = HashMap::new();
         for (txid, info, height, time, tx_position) in rows {
-            if !map.contains_key(&txid) && map.len() == limit {
+            if cancel.is_cancelled() || (!map.contains_key(&txid) && map.len() == limit) {
                 break;
             }
             match info {
@@ -631,6 +844,9 @@ impl ChainQuery {
                 b.height.cmp(&a.height)
             }
         });
+        if ascending {
+            tx_summaries.reverse();
+        }
         tx_summaries
     }
 
@@ -640,9 +856,17 @@ impl ChainQuery {
         last_seen_txid: Option<&Txid>,
         start_height: Option<u32>,
         limit: usize,
+        cancel: &CancelToken,
     ) -> Vec<TxHistorySummary> {
         // scripthash lookup
-        self._summary(b'H', scripthash, last_seen_txid, start_height, limit)
+        self._summary(
+            b'H',
+            scripthash,
+            last_seen_txid,
+            start_height,
+            limit,
+            cancel,
+        )
     }
 
     fn _summary(
@@ -652,13 +876,31 @@ impl ChainQuery {
         last_seen_txid: Option<&Txid>,
         start_height: Option<u32>,
         limit: usize,
+        cancel: &CancelToken,
     ) -> Vec<TxHistorySummary> {
         let _timer_scan = self.start_timer("address_summary");
         let rows = self
             .history_iter_scan_reverse(code, hash, start_height)
             .map(TxHistoryRow::from_row);
 
-        self.collate_summaries(rows, last_seen_txid, limit)
+        self.collate_summaries(rows, last_seen_txid, limit, false, cancel)
+    }
+
+    /// Like summary(), but walks the history oldest-first starting at `start_height`
+    pub fn summary_asc(
+        &self,
+        scripthash: &[u8],
+        last_seen_txid: Option<&Txid>,
+        start_height: usize,
+        limit: usize,
+        cancel: &CancelToken,
+    ) -> Vec<TxHistorySummary> {
+        let _timer_scan = self.start_timer("address_summary_asc");
+        let rows = self
+            .history_iter_scan(b'H', scripthash, start_height)
+            .map(TxHistoryRow::from_row);
+
+        self.collate_summaries(rows, last_seen_txid, limit, true, cancel)
     }
 
     pub fn summary_group(
@@ -667,6 +909,7 @@ impl ChainQuery {
         last_seen_txid: Option<&Txid>,
         start_height: Option<u32>,
         limit: usize,
+        cancel: &CancelToken,
     ) -> Vec<TxHistorySummary> {
         // scripthash lookup
         let _timer_scan = self.start_timer("address_group_summary");
@@ -674,7 +917,7 @@ impl ChainQuery {
             .history_iter_scan_group_reverse(b'H', scripthashes, start_height)
             .map(TxHistoryRow::from_row);
 
-        self.collate_summaries(rows, last_seen_txid, limit)
+        self.collate_summaries(rows, last_seen_txid, limit, false, cancel)
     }
 
     pub fn history<'a>(
@@ -683,9 +926,47 @@ impl ChainQuery {
         last_seen_txid: Option<&'a Txid>,
         start_height: Option<u32>,
         limit: usize,
+        cancel: &CancelToken,
     ) -> impl rayon::iter::ParallelIterator<Item = Result<(Transaction, BlockId)>> + 'a {
         // scripthash lookup
-        self._history(b'H', scripthash, last_seen_txid, start_height, limit)
+        self._history(
+            b'H',
+            scripthash,
+            last_seen_txid,
+            start_height,
+            limit,
+            cancel,
+        )
+    }
+
+    /// Like history(), but walks the history oldest-first starting at `start_height`
+    pub fn history_asc<'a>(
+        &'a self,
+        scripthash: &[u8],
+        last_seen_txid: Option<&'a Txid>,
+        start_height: usize,
+        limit: usize,
+        cancel: &CancelToken,
+    ) -> impl rayon::iter::ParallelIterator<Item = Result<(Transaction, BlockId)>> + 'a {
+        let _timer_scan = self.start_timer("history_asc");
+        let cancel = cancel.clone();
+
+        self.lookup_txns(
+            self.history_iter_scan(b'H', scripthash, start_height)
+                .take_while(move |_| !cancel.is_cancelled())
+                .map(|row| TxHistoryRow::from_row(row).get_txid())
+                .unique()
+                .skip_while(move |txid| {
+                    // skip until we reach the last_seen_txid
+                    last_seen_txid.map_or(false, |last_seen_txid| last_seen_txid != txid)
+                })
+                .skip(match last_seen_txid {
+                    Some(_) => 1, // skip the last_seen_txid itself
+                    None => 0,
+                })
+                .filter_map(move |txid| self.tx_confirming_block(&txid).map(|b| (txid, b))),
+            limit,
+        )
     }
 
     pub fn history_txids_iter<'a>(&'a self, scripthash: &[u8]) -> impl Iterator<Item = Txid> + 'a {
@@ -701,11 +982,14 @@ impl ChainQuery {
         last_seen_txid: Option<&'a Txid>,
         start_height: Option<u32>,
         limit: usize,
+        cancel: &CancelToken,
     ) -> impl rayon::iter::ParallelIterator<Item = Result<(Transaction, BlockId)>> + 'a {
         let _timer_scan = self.start_timer("history");
+        let cancel = cancel.clone();
 
         self.lookup_txns(
             self.history_iter_scan_reverse(code, hash, start_height)
+                .take_while(move |_| !cancel.is_cancelled())
                 .map(|row| TxHistoryRow::from_row(row).get_txid())
                 // XXX: unique() requires keeping an in-memory list of all txids, can we avoid that?
                 .unique()
@@ -744,9 +1028,17 @@ impl ChainQuery {
         last_seen_txid: Option<&'a Txid>,
         start_height: Option<u32>,
         limit: usize,
+        cancel: &CancelToken,
     ) -> impl rayon::iter::ParallelIterator<Item = Result<(Transaction, BlockId)>> + 'a {
         // scripthash lookup
-        self._history_group(b'H', scripthashes, last_seen_txid, start_height, limit)
+        self._history_group(
+            b'H',
+            scripthashes,
+            last_seen_txid,
+            start_height,
+            limit,
+            cancel,
+        )
     }
 
     pub fn history_txids_iter_group(
@@ -766,12 +1058,15 @@ impl ChainQuery {
         last_seen_txid: Option<&'a Txid>,
         start_height: Option<u32>,
         limit: usize,
+        cancel: &CancelToken,
     ) -> impl rayon::iter::ParallelIterator<Item = Result<(Transaction, BlockId)>> + 'a {
         debug!("limit {} | last_seen {:?}", limit, last_seen_txid);
         let _timer_scan = self.start_timer("history_group");
+        let cancel = cancel.clone();
 
         self.lookup_txns(
             self.history_iter_scan_group_reverse(code, hashes, start_height)
+                .take_while(move |_| !cancel.is_cancelled())
                 .map(|row| TxHistoryRow::from_row(row).get_txid())
                 // XXX: unique() requires keeping an in-memory list of all txids, can we avoid that?
                 .unique()
@@ -790,7 +1085,13 @@ impl ChainQuery {
     }
 
     // TODO: avoid duplication with stats/stats_delta?
-    pub fn utxo(&self, scripthash: &[u8], limit: usize, flush: DBFlush) -> Result<Vec<Utxo>> {
+    pub fn utxo(
+        &self,
+        scripthash: &[u8],
+        limit: usize,
+        flush: DBFlush,
+        cancel: &CancelToken,
+    ) -> Result<Vec<Utxo>> {
         let _timer = self.start_timer("utxo");
 
         // get the last known utxo set and the blockhash it was updated for.
@@ -809,8 +1110,10 @@ impl ChainQuery {
 
         // update utxo set with new transactions since
         let (newutxos, lastblock, processed_items) = cache.map_or_else(
-            || self.utxo_delta(scripthash, HashMap::new(), 0, limit),
-            |(oldutxos, blockheight)| self.utxo_delta(scripthash, oldutxos, blockheight + 1, limit),
+            || self.utxo_delta(scripthash, HashMap::new(), 0, limit, cancel),
+            |(oldutxos, blockheight)| {
+                self.utxo_delta(scripthash, oldutxos, blockheight + 1, limit, cancel)
+            },
         )?;
 
         // save updated utxo set to cache
@@ -856,6 +1159,7 @@ impl ChainQuery {
         init_utxos: UtxoMap,
         start_height: usize,
         limit: usize,
+        cancel: &CancelToken,
     ) -> Result<(UtxoMap, Option<BlockHash>, usize)> {
         let _timer = self.start_timer("utxo_delta");
         let history_iter = self
@@ -874,6 +1178,10 @@ impl ChainQuery {
         let mut lastblock = None;
 
         for (history, blockid) in history_iter {
+            // bail out rather than returning (and caching) a partial utxo set
+            if cancel.is_cancelled() {
+                bail!(ErrorKind::Cancelled)
+            }
             processed_items += 1;
             lastblock = Some(blockid.hash);
 
@@ -898,6 +1206,54 @@ impl ChainQuery {
         Ok((utxos, lastblock, processed_items))
     }
 
+    /// Returns every confirmed output funding the scripthash, along with whether it was spent
+    /// on-chain. Spentness is derived from the scripthash's own spending history rows, so no
+    /// transactions are loaded.
+    pub fn funded_outpoints(
+        &self,
+        scripthash: &[u8],
+        limit: usize,
+    ) -> Result<Vec<(OutPoint, bool)>> {
+        let _timer = self.start_timer("funded_outpoints");
+        let history_iter = self
+            .history_iter_scan(b'H', scripthash, 0)
+            .map(TxHistoryRow::from_row)
+            .filter(|history| {
+                // drop history entries from re-orged blocks, like utxo_delta() does
+                self.tx_confirming_block(&history.get_txid())
+                    .map_or(false, |b| b.height == history.key.confirmed_height as usize)
+            });
+
+        let mut funded = vec![];
+        let mut spent = HashSet::new();
+
+        for history in history_iter {
+            match history.key.txinfo {
+                TxHistoryInfo::Funding(_) => funded.push(history.get_funded_outpoint()),
+                TxHistoryInfo::Spending(_) => {
+                    spent.insert(history.get_funded_outpoint());
+                }
+                #[cfg(feature = "liquid")]
+                TxHistoryInfo::Issuing(_)
+                | TxHistoryInfo::Burning(_)
+                | TxHistoryInfo::Pegin(_)
+                | TxHistoryInfo::Pegout(_) => unreachable!(),
+            };
+
+            if funded.len() > limit {
+                bail!(ErrorKind::TooManyTxs(limit))
+            }
+        }
+
+        Ok(funded
+            .into_iter()
+            .map(|outpoint| {
+                let is_spent = spent.contains(&outpoint);
+                (outpoint, is_spent)
+            })
+            .collect())
+    }
+
     pub fn stats(&self, scripthash: &[u8], flush: DBFlush) -> ScriptStats {
         let _timer = self.start_timer("stats");
 
@@ -1011,6 +1367,32 @@ impl ChainQuery {
             .collect()
     }
 
+    /// Find confirmed txids starting with the given hex prefix. Txids of orphaned blocks are
+    /// skipped.
+    pub fn txid_search(&self, prefix: &str, limit: usize) -> Vec<(Txid, BlockId)> {
+        let _timer_scan = self.start_timer("txid_search");
+        // scan by the whole bytes of the prefix, then match the last nibble of odd-sized prefixes
+        let prefix = prefix.to_lowercase();
+        let prefix_bytes = match hex::decode(&prefix[..prefix.len() / 2 * 2]) {
+            Ok(bytes) => bytes,
+            Err(_) => return vec![],
+        };
+        self.store
+            .history_db
+            .iter_scan(&[b"p", &prefix_bytes[..]].concat())
+            .filter_map(|row| {
+                let mut txid = row.key[1..].to_vec();
+                txid.reverse();
+                let txid: Txid = deserialize(&txid).ok()?;
+                if !txid.to_string().starts_with(&prefix) {
+                    return None;
+                }
+                Some((txid, self.tx_confirming_block(&txid)?))
+            })
+            .take(limit)
+            .collect()
+    }
+
     fn header_by_hash(&self, hash: &BlockHash) -> Option<HeaderEntry> {
         self.store
             .indexed_headers
@@ -1068,7 +1450,25 @@ impl ChainQuery {
     }
 
     pub fn best_height(&self) -> usize {
-        self.store.indexed_headers.read().unwrap().len() - 1
+        // the REST server is up before the first sync, when there are no headers yet
+        self.store
+            .indexed_headers
+            .read()
+            .unwrap()
+            .len()
+            .saturating_sub(1)
+    }
+
+    /// How many blocks the index is behind the daemon's tip as of the last index update, or None
+    /// before the first one
+    pub fn blocks_behind(&self) -> Option<usize> {
+        let target_height = (*self.store.sync_target_height.read().unwrap())?;
+        Some(target_height.saturating_sub(self.best_height()))
+    }
+
+    /// Incremented every time the indexed chain is reorged, starting over from 0 on every restart
+    pub fn tip_generation(&self) -> u64 {
+        self.store.indexed_headers.read().unwrap().generation()
     }
 
     pub fn best_hash(&self) -> BlockHash {
@@ -1225,6 +1625,7 @@ impl ChainQuery {
             last_seen_txid,
             None,
             limit,
+            &CancelToken::default(),
         )
     }
 
@@ -1403,7 +1804,12 @@ fn index_transaction(
     //      H{funding-scripthash}{funding-height}{funding-block-pos}F{funding-txid:vout} → ""
     // persist "edges" for fast is-this-TXO-spent check
     //      S{funding-txid:vout}{spending-txid:vin} → ""
+    // and, for txid prefix search:
+    //      p{reversed-txid} → ""
     let txid = full_hash(&tx.txid()[..]);
+    if iconfig.txid_search {
+        rows.push(txid_search_row(&txid));
+    }
     for (txo_index, txo) in tx.output.iter().enumerate() {
         if is_spendable(txo) || iconfig.index_unspendables {
             let history = TxHistoryRow::new(
@@ -1479,6 +1885,17 @@ fn addr_search_filter(prefix: &str) -> Bytes {
     [b"a", prefix.as_bytes()].concat()
 }
 
+// Txids are keyed in their displayed (reversed) byte order, so that they can be looked up
+// by the prefix of their hex representation
+fn txid_search_row(txid: &FullHash) -> DBRow {
+    let mut display = *txid;
+    display.reverse();
+    DBRow {
+        key: [b"p", &display[..]].concat(),
+        value: vec![],
+    }
+}
+
 // TODO: replace by a separate opaque type (similar to Sha256dHash, but without the "double")
 pub type FullHash = [u8; 32]; // serialized SHA256 result
 
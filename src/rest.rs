@@ -1,29 +1,52 @@
-use crate::chain::{address, BlockHash, Network, OutPoint, Script, Transaction, TxIn, TxOut, Txid};
-use crate::config::{Config, VERSION_STRING};
+use crate::chain::{
+    address, Block, BlockHash, Network, OutPoint, Script, Transaction, TxIn, TxOut, Txid,
+};
+use crate::config::{Config, InternalApiAccess, VERSION_STRING};
 use crate::errors;
-use crate::metrics::Metrics;
-use crate::new_index::{compute_script_hash, Query, SpendingInput, Utxo};
+use crate::metrics::{Counter, CounterVec, Gauge, MetricOpts, Metrics};
+use crate::new_index::{
+    compute_script_hash, ConflictStatus, Mempool, PackageFeerate, Query, ScripthashEvent,
+    SpendingInput, TxConflict, Utxo,
+};
+use crate::util::fees::sigop_adjusted_vsize;
+use crate::util::rate_limit::RateLimiter;
 use crate::util::{
     create_socket, electrum_merkle, extract_tx_prevouts, full_hash, get_innerscripts, get_tx_fee,
-    has_prevout, is_coinbase, transaction_sigop_count, BlockHeaderMeta, BlockId, FullHash,
-    ScriptToAddr, ScriptToAsm, TransactionStatus,
+    has_prevout, is_coinbase, is_final_tx, parse_inscription, parse_multisig, parse_op_return,
+    script_type, signals_rbf, transaction_sigop_count, BlockHeaderMeta, BlockId, CancelToken,
+    FullHash, InscriptionInfo, MultisigInfo, OpReturnPayload, ScriptToAddr, ScriptToAsm,
+    TaprootSpend, TransactionStatus,
 };
 
 #[cfg(not(feature = "liquid"))]
-use {bitcoin::consensus::encode, std::str::FromStr};
+use {
+    crate::util::sigop_cost_breakdown, bitcoin::consensus::encode, bitcoin::Wtxid,
+    std::str::FromStr,
+};
 
-use bitcoin::blockdata::opcodes;
 use bitcoin::hashes::hex::{FromHex, ToHex};
 use bitcoin::hashes::Error as HashError;
+use bitcoin::hashes::{sha256, sha256d::Hash as Sha256dHash, Hash};
+use futures_util::{SinkExt, StreamExt};
 use hex::{self, FromHexError};
+use hyper::body::HttpBody;
+use hyper::header::{self, HeaderMap, HeaderValue};
+use hyper::server::conn::{AddrStream, Http};
 use hyper::service::{make_service_fn, service_fn};
-use hyper::{Body, Method, Response, Server, StatusCode};
+use hyper::upgrade::Upgraded;
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
 use prometheus::{HistogramOpts, HistogramVec};
 use rayon::iter::ParallelIterator;
-use tokio::sync::oneshot;
+use tokio::sync::{broadcast, oneshot, watch, OwnedSemaphorePermit, Semaphore};
+use tokio_rustls::{rustls, TlsAcceptor};
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::{CloseFrame, Message, Role};
+use tokio_tungstenite::WebSocketStream;
 
 use hyperlocal::UnixServerExt;
-use std::{cmp, fs};
+use std::path::Path;
+use std::{cmp, fmt, fs, io};
 #[cfg(feature = "liquid")]
 use {
     crate::elements::{peg::PegoutValue, AssetSorting, IssuanceValue},
@@ -35,16 +58,54 @@ use {
 
 use serde::Serialize;
 use serde_json;
-use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
 use std::num::ParseIntError;
 use std::os::unix::fs::FileTypeExt;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use url::form_urlencoded;
 
 const ADDRESS_SEARCH_LIMIT: usize = 10;
+// Limit the number of txids returned by tx-prefix, and bound its scan by a minimum prefix size
+const TXID_SEARCH_LIMIT: usize = 10;
+const TXID_SEARCH_MIN_PREFIX: usize = 8;
 // Limit to 300 addresses
 const MULTI_ADDRESS_LIMIT: usize = 300;
+// Limit the number of asset history txs scanned and utxos returned by asset/:id/utxos
+#[cfg(feature = "liquid")]
+const ASSET_UTXOS_SCAN_LIMIT: usize = 1_000;
+#[cfg(feature = "liquid")]
+const ASSET_UTXOS_LIMIT: usize = 100;
+// Number of blocks before coinbase outputs can be spent
+const COINBASE_MATURITY: u32 = 100;
+// Limit the number of txids in a single txs/fees request
+const TX_FEES_LIMIT: usize = 200;
+// Limit the number of outpoints in a single GET /outspends request
+const OUTSPENDS_OUTPOINTS_LIMIT: usize = 50;
+// Maximum number of seconds a tx/:txid/status long-poll can be held open for
+const TX_STATUS_MAX_WAIT: u64 = 90;
+// Limit the number of sub-requests in a single batch request
+const BATCH_LIMIT: usize = 50;
+// Limit the number of topics (scripthashes, addresses, ...) a single websocket connection can
+// subscribe to
+const WS_SUBSCRIPTIONS_LIMIT: usize = 300;
+// How long open websockets get to close on shutdown
+const WS_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+// Limit the number of funded outputs returned by the spent-bitmap endpoint
+const FUNDED_OUTPUTS_LIMIT: usize = 10_000;
+
+const API_V1_PREFIX: &str = "/v1";
+// Interval of the comments sent on quiet event streams, to keep proxies from timing them out
+const SSE_KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+// Number of seconds clients are asked to wait while the index catches up with the daemon
+const INDEX_BEHIND_RETRY_AFTER: u64 = 30;
+
+const MEMPOOL_ORDER_ASC_ERROR: &str =
+    "order=asc is not supported for routes including mempool transactions";
 
 #[cfg(feature = "liquid")]
 const ASSETS_PER_PAGE: usize = 25;
@@ -59,6 +120,28 @@ const CONF_FINAL: usize = 10; // reorgs deeper than this are considered unlikely
 // internal api prefix
 const INTERNAL_PREFIX: &str = "internal";
 
+// Lets clients tell that data cached for deep blocks may have been invalidated by a reorg. The value
+// is opaque: it's prefixed with STARTUP_NONCE since the reorg counter starts over on every restart
+const CHAIN_GENERATION_HEADER: &str = "X-Chain-Tip-Generation";
+
+// Total number of items of paginated responses
+const TOTAL_COUNT_HEADER: &str = "X-Total-Count";
+
+const REQUEST_ID_HEADER: &str = "X-Request-Id";
+const ACCESS_LOG_TARGET: &str = "electrs::http_access";
+
+lazy_static! {
+    // combined with counters to make up request ids and chain generations that don't repeat
+    // across restarts
+    static ref STARTUP_NONCE: String = format!(
+        "{:x}",
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis())
+    );
+}
+static REQUEST_ID_COUNTER: AtomicU64 = AtomicU64::new(0);
+
 #[derive(Serialize, Deserialize)]
 struct BlockValue {
     id: String,
@@ -68,6 +151,8 @@ struct BlockValue {
     tx_count: u32,
     size: u32,
     weight: u32,
+    // weight / 4 rounded up, the same way as for txs
+    vsize: u32,
     merkle_root: String,
     previousblockhash: Option<String>,
     mediantime: u32,
@@ -101,6 +186,7 @@ impl BlockValue {
             tx_count: blockhm.meta.tx_count,
             size: blockhm.meta.size,
             weight: blockhm.meta.weight,
+            vsize: (blockhm.meta.weight + 3) / 4,
             merkle_root: header.merkle_root.to_hex(),
             previousblockhash: if header.prev_blockhash != BlockHash::default() {
                 Some(header.prev_blockhash.to_hex())
@@ -146,14 +232,39 @@ fn difficulty_new(bh: &bitcoin::BlockHeader) -> f64 {
 #[derive(Serialize, Deserialize)]
 struct TransactionValue {
     txid: Txid,
+    #[cfg(not(feature = "liquid"))]
+    wtxid: Wtxid,
     version: u32,
     locktime: u32,
     vin: Vec<TxInValue>,
     vout: Vec<TxOutValue>,
     size: u32,
     weight: u32,
-    sigops: u32,
-    fee: u64,
+    vsize: u32,
+    // sigops and fee are null for decoded txs with prevouts missing from the index
+    sigops: Option<u32>,
+    fee: Option<u64>,
+    // in sat/vB, omitted when no fee is reported (coinbase or liquid txs without a fee)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    feerate: Option<f64>,
+    // vsize and feerate as accounted by bitcoind's policy, which penalizes sigop-heavy txs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    adjusted_vsize: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    adjusted_feerate: Option<f64>,
+    bip125_replaceable: bool,
+    is_final: bool,
+    // only set for unconfirmed txs on /tx/:txid, see Mempool::package_feerate()
+    #[serde(skip_serializing_if = "Option::is_none")]
+    effective_feerate: Option<f64>,
+    // the fee rate of the tx along with its unconfirmed ancestors, see Mempool::ancestor_stats()
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ancestor_feerate: Option<f64>,
+    // set along with ancestor_feerate, true if it left out ancestors past the bound
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ancestors_truncated: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cpfp: Option<PackageFeerate>,
     #[serde(skip_serializing_if = "Option::is_none")]
     status: Option<TransactionStatus>,
 }
@@ -166,8 +277,32 @@ impl TransactionValue {
         config: &Config,
     ) -> Result<Self, errors::Error> {
         let prevouts = extract_tx_prevouts(&tx, txos)?;
-        let sigops = transaction_sigop_count(&tx, &prevouts)
-            .map_err(|_| errors::Error::from("Couldn't count sigops"))? as u32;
+        Self::from_prevouts(tx, blockid, &prevouts, config)
+    }
+
+    // Build the value with whatever prevouts are available, leaving sigops and fee unset if
+    // some are missing
+    fn from_prevouts(
+        tx: Transaction,
+        blockid: Option<BlockId>,
+        prevouts: &HashMap<u32, &TxOut>,
+        config: &Config,
+    ) -> Result<Self, errors::Error> {
+        let has_all_prevouts = tx
+            .input
+            .iter()
+            .enumerate()
+            .all(|(index, txin)| !has_prevout(txin) || prevouts.contains_key(&(index as u32)));
+
+        let sigops = if has_all_prevouts {
+            Some(
+                transaction_sigop_count(&tx, prevouts)
+                    .map_err(|_| errors::Error::from("Couldn't count sigops"))?
+                    as u32,
+            )
+        } else {
+            None
+        };
 
         let vins: Vec<TxInValue> = tx
             .input
@@ -183,19 +318,49 @@ impl TransactionValue {
             .map(|txout| TxOutValue::new(txout, config))
             .collect();
 
-        let fee = get_tx_fee(&tx, &prevouts, config.network_type);
+        let fee = if has_all_prevouts {
+            Some(get_tx_fee(&tx, prevouts, config.network_type))
+        } else {
+            None
+        };
+        let weight = tx.weight() as u32;
+        let vsize = (weight + 3) / 4;
+        let feerate = match fee {
+            Some(fee) if fee > 0 => Some(fee as f64 / vsize as f64),
+            _ => None,
+        };
+        let adjusted_vsize = sigops.map(|sigops| sigop_adjusted_vsize(weight, sigops));
+        let adjusted_feerate = match (fee, adjusted_vsize) {
+            (Some(fee), Some(adjusted_vsize)) if fee > 0 => {
+                Some(fee as f64 / adjusted_vsize as f64)
+            }
+            _ => None,
+        };
 
         #[allow(clippy::unnecessary_cast)]
         Ok(TransactionValue {
             txid: tx.txid(),
+            #[cfg(not(feature = "liquid"))]
+            wtxid: tx.wtxid(),
             version: tx.version as u32,
             locktime: tx.lock_time,
             vin: vins,
             vout: vouts,
             size: tx.size() as u32,
-            weight: tx.weight() as u32,
+            weight,
+            vsize,
             sigops,
             fee,
+            feerate,
+            adjusted_vsize,
+            adjusted_feerate,
+            bip125_replaceable: signals_rbf(&tx),
+            // confirmed txs are final, unconfirmed ones are checked against the tip by prepare_txs()
+            is_final: true,
+            effective_feerate: None,
+            ancestor_feerate: None,
+            ancestors_truncated: None,
+            cpfp: None,
             status: Some(TransactionStatus::from(blockid)),
         })
     }
@@ -210,6 +375,7 @@ struct TxInValue {
     scriptsig_asm: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     witness: Option<Vec<String>>,
+    has_witness: bool,
     is_coinbase: bool,
     sequence: u32,
 
@@ -217,6 +383,10 @@ struct TxInValue {
     inner_redeemscript_asm: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     inner_witnessscript_asm: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    taproot: Option<TaprootValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    inscription: Option<InscriptionInfo>,
 
     #[cfg(feature = "liquid")]
     is_pegin: bool,
@@ -246,6 +416,7 @@ impl TxInValue {
             vout: txin.previous_output.vout,
             prevout: prevout.map(|prevout| TxOutValue::new(prevout, config)),
             scriptsig_asm: txin.script_sig.to_asm(),
+            has_witness: witness.is_some(),
             witness,
 
             inner_redeemscript_asm: innerscripts
@@ -256,6 +427,19 @@ impl TxInValue {
                 .as_ref()
                 .and_then(|i| i.witness_script.as_ref())
                 .map(ScriptToAsm::to_asm),
+            taproot: innerscripts
+                .as_ref()
+                .and_then(|i| i.taproot.as_ref())
+                .map(TaprootValue::from),
+            inscription: if config.rest_inscriptions {
+                innerscripts
+                    .as_ref()
+                    .and_then(|i| i.taproot.as_ref())
+                    .and_then(|taproot| taproot.leaf_script.as_ref())
+                    .and_then(parse_inscription)
+            } else {
+                None
+            },
 
             is_coinbase,
             sequence: txin.sequence,
@@ -273,6 +457,32 @@ impl TxInValue {
     }
 }
 
+#[derive(Serialize, Deserialize, Clone)]
+struct TaprootValue {
+    spend_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    leaf_script_asm: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    control_block: Option<String>,
+    annex_present: bool,
+}
+
+impl From<&TaprootSpend> for TaprootValue {
+    fn from(spend: &TaprootSpend) -> Self {
+        TaprootValue {
+            spend_type: if spend.is_script_path {
+                "script"
+            } else {
+                "key"
+            }
+            .to_string(),
+            leaf_script_asm: spend.leaf_script.as_ref().map(ScriptToAsm::to_asm),
+            control_block: spend.control_block.as_ref().map(hex::encode),
+            annex_present: spend.annex_present,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 struct TxOutValue {
     scriptpubkey: Script,
@@ -282,6 +492,11 @@ struct TxOutValue {
     #[serde(skip_serializing_if = "Option::is_none")]
     scriptpubkey_address: Option<String>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    op_return: Option<OpReturnPayload>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    multisig: Option<MultisigInfo>,
+
     #[cfg(not(feature = "liquid"))]
     value: u64,
 
@@ -306,6 +521,20 @@ struct TxOutValue {
     pegout: Option<PegoutValue>,
 }
 
+// The scriptpubkey_type reported for an output
+fn output_type(txout: &TxOut) -> &'static str {
+    #[cfg(not(feature = "liquid"))]
+    let is_fee = false;
+    #[cfg(feature = "liquid")]
+    let is_fee = txout.is_fee();
+
+    if is_fee {
+        "fee"
+    } else {
+        script_type(&txout.script_pubkey)
+    }
+}
+
 impl TxOutValue {
     fn new(txout: &TxOut, config: &Config) -> Self {
         #[cfg(not(feature = "liquid"))]
@@ -330,43 +559,14 @@ impl TxOutValue {
             _ => None,
         };
 
-        #[cfg(not(feature = "liquid"))]
-        let is_fee = false;
-        #[cfg(feature = "liquid")]
-        let is_fee = txout.is_fee();
-
         let script = &txout.script_pubkey;
         let script_asm = script.to_asm();
         let script_addr = script.to_address_str(config.network_type);
 
-        // TODO should the following something to put inside rust-elements lib?
-        let script_type = if is_fee {
-            "fee"
-        } else if script.is_empty() {
-            "empty"
-        } else if script.is_op_return() {
-            "op_return"
-        } else if script.is_p2pk() {
-            "p2pk"
-        } else if script.is_p2pkh() {
-            "p2pkh"
-        } else if script.is_p2sh() {
-            "p2sh"
-        } else if script.is_v0_p2wpkh() {
-            "v0_p2wpkh"
-        } else if script.is_v0_p2wsh() {
-            "v0_p2wsh"
-        } else if is_v1_p2tr(script) {
-            "v1_p2tr"
-        } else if is_anchor(script) {
-            "anchor"
-        } else if script.is_provably_unspendable() {
-            "provably_unspendable"
-        } else if is_bare_multisig(script) {
-            "multisig"
-        } else {
-            "unknown"
-        };
+        let script_type = output_type(txout);
+
+        let op_return = parse_op_return(script);
+        let multisig = parse_multisig(script);
 
         #[cfg(feature = "liquid")]
         let pegout = PegoutValue::from_txout(txout, config.network_type, config.parent_network);
@@ -376,6 +576,8 @@ impl TxOutValue {
             scriptpubkey_asm: script_asm,
             scriptpubkey_address: script_addr,
             scriptpubkey_type: script_type.to_string(),
+            op_return,
+            multisig,
             value,
             #[cfg(feature = "liquid")]
             valuecommitment,
@@ -388,43 +590,18 @@ impl TxOutValue {
         }
     }
 }
-fn is_v1_p2tr(script: &Script) -> bool {
-    script.len() == 34
-        && script[0] == opcodes::all::OP_PUSHNUM_1.into_u8()
-        && script[1] == opcodes::all::OP_PUSHBYTES_32.into_u8()
-}
-fn is_bare_multisig(script: &Script) -> bool {
-    let len = script.len();
-    // 1-of-1 multisig is 37 bytes
-    // Max is 15 pubkeys
-    // Min is 1
-    // First byte must be <= the second to last (4-of-2 makes no sense)
-    // We won't check the pubkeys, just assume anything with the form
-    //   OP_M ... OP_N OP_CHECKMULTISIG
-    // is bare multisig
-    len >= 37
-        && script[len - 1] == opcodes::all::OP_CHECKMULTISIG.into_u8()
-        && script[len - 2] >= opcodes::all::OP_PUSHNUM_1.into_u8()
-        && script[len - 2] <= opcodes::all::OP_PUSHNUM_15.into_u8()
-        && script[0] >= opcodes::all::OP_PUSHNUM_1.into_u8()
-        && script[0] <= script[len - 2]
-}
-
-fn is_anchor(script: &Script) -> bool {
-    let len = script.len();
-    len == 4
-        && script[0] == opcodes::all::OP_PUSHNUM_1.into_u8()
-        && script[1] == opcodes::all::OP_PUSHBYTES_2.into_u8()
-        && script[2] == 0x4e
-        && script[3] == 0x73
-}
-
 #[derive(Serialize)]
 struct UtxoValue {
     txid: Txid,
     vout: u32,
     status: TransactionStatus,
 
+    // only set for coinbase outputs, which can't be spent before maturing
+    #[serde(skip_serializing_if = "Option::is_none")]
+    coinbase: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    spendable_height: Option<u32>,
+
     #[cfg(not(feature = "liquid"))]
     value: u64,
 
@@ -466,6 +643,8 @@ impl From<Utxo> for UtxoValue {
             txid: utxo.txid,
             vout: utxo.vout,
             status: TransactionStatus::from(utxo.confirmed),
+            coinbase: None,
+            spendable_height: None,
 
             #[cfg(not(feature = "liquid"))]
             value: utxo.value,
@@ -511,6 +690,94 @@ impl From<Utxo> for UtxoValue {
     }
 }
 
+// Encode utxos as a u32 record count followed by fixed-size records of
+// `txid(32) vout(4) value(8) height(4)`. The txid is in its internal byte order (reversed from
+// its hex representation), integers are little-endian and unconfirmed utxos have a height of 0.
+#[cfg(not(feature = "liquid"))]
+fn utxos_binary(utxos: &[Utxo]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(4 + utxos.len() * 48);
+    buf.extend_from_slice(&(utxos.len() as u32).to_le_bytes());
+    for utxo in utxos {
+        buf.extend_from_slice(&utxo.txid[..]);
+        buf.extend_from_slice(&utxo.vout.to_le_bytes());
+        buf.extend_from_slice(&utxo.value.to_le_bytes());
+        let height = utxo.confirmed.as_ref().map_or(0, |b| b.height as u32);
+        buf.extend_from_slice(&height.to_le_bytes());
+    }
+    buf
+}
+
+// Convert utxos to their values, marking the ones funded by a coinbase tx
+fn utxo_values(utxos: Vec<Utxo>, query: &Query, options: ResponseOptions) -> Vec<UtxoValue> {
+    // a coinbase is the first tx of its block, which is looked up once per block rather than
+    // loading every funding tx
+    let coinbase_txids: HashSet<Txid> = utxos
+        .iter()
+        .filter_map(|utxo| utxo.confirmed.as_ref().map(|b| b.hash))
+        .collect::<HashSet<BlockHash>>()
+        .into_iter()
+        .filter_map(|blockhash| query.chain().get_block_coinbase_txid(&blockhash))
+        .collect();
+
+    let mempool = query.mempool();
+    utxos
+        .into_iter()
+        .map(|utxo| {
+            let is_coinbase = coinbase_txids.contains(&utxo.txid);
+            let height = utxo.confirmed.as_ref().map(|b| b.height as u32);
+            let mut value = UtxoValue::from(utxo);
+            if is_coinbase {
+                value.coinbase = Some(true);
+                value.spendable_height = height.map(|height| height + COINBASE_MATURITY);
+            }
+            if options.v1 {
+                mempool.add_status_details(&value.txid, &mut value.status);
+            }
+            value
+        })
+        .collect()
+}
+
+#[derive(Serialize)]
+struct TxTestValue {
+    allowed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reject_reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    vsize: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fees: Option<TxTestFees>,
+}
+
+#[derive(Serialize)]
+struct TxTestFees {
+    base: f64,
+    effective_feerate: f64,
+}
+
+#[derive(Serialize)]
+struct PackageTxValue {
+    txid: Txid,
+    // the wtxid of the transaction in the mempool, which differs from the submitted one
+    // if a transaction with the same txid but a different witness was already there
+    wtxid: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    vsize: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fees: Option<TxTestFees>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct TxFeeValue {
+    txid: Txid,
+    fee: Option<u64>,
+    vsize: Option<u32>,
+    feerate: Option<f64>,
+    confirmed: Option<bool>,
+}
+
 #[derive(Serialize, Default)]
 struct SpendingValue {
     spent: bool,
@@ -520,6 +787,35 @@ struct SpendingValue {
     vin: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     status: Option<TransactionStatus>,
+    // details of unconfirmed spenders, only included with `?verbose=true`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fee: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    vsize: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    feerate: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ancestor_feerate: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bip125_replaceable: Option<bool>,
+}
+impl SpendingValue {
+    fn add_mempool_details(&mut self, mempool: &Mempool) {
+        let txid = match (self.txid, &self.status) {
+            (Some(txid), Some(status)) if !status.confirmed => txid,
+            _ => return,
+        };
+        if let Some(entry) = mempool.fee_entry(&txid) {
+            self.fee = Some(entry.fee);
+            self.vsize = Some(entry.vsize);
+            self.feerate = Some(entry.feerate);
+        }
+        self.ancestor_feerate = mempool.ancestor_stats(&txid).map(|stats| stats.feerate());
+        if let Some(tx) = mempool.lookup_txn(&txid) {
+            self.bip125_replaceable =
+                Some(signals_rbf(&tx) || mempool.has_rbf_signaling_ancestor(&txid));
+        }
+    }
 }
 impl From<SpendingInput> for SpendingValue {
     fn from(spend: SpendingInput) -> Self {
@@ -528,10 +824,95 @@ impl From<SpendingInput> for SpendingValue {
             txid: Some(spend.txid),
             vin: Some(spend.vin),
             status: Some(TransactionStatus::from(spend.confirmed)),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct OutputSpentValue {
+    txid: Txid,
+    vout: u32,
+    spent: bool,
+}
+impl From<(OutPoint, bool)> for OutputSpentValue {
+    fn from((outpoint, spent): (OutPoint, bool)) -> Self {
+        OutputSpentValue {
+            txid: outpoint.txid,
+            vout: outpoint.vout,
+            spent,
+        }
+    }
+}
+
+// With `?verbose=true`, include fee and replaceability details of unconfirmed spenders
+fn add_spends_details<'a, I>(
+    spends: I,
+    query: &Query,
+    query_params: &HashMap<String, String>,
+    options: ResponseOptions,
+) where
+    I: IntoIterator<Item = &'a mut SpendingValue>,
+{
+    if query_params.get("verbose").map_or(false, |v| v == "true") {
+        let mempool = query.mempool();
+        for spend in spends {
+            spend.add_mempool_details(&mempool);
+            if let (true, Some(txid), Some(status)) =
+                (options.v1, spend.txid, spend.status.as_mut())
+            {
+                mempool.add_status_details(&txid, status);
+            }
+        }
+    } else {
+        add_spends_status_details(spends, query, options);
+    }
+}
+
+// Under /v1/, the statuses of unconfirmed spenders carry their mempool details
+fn add_spends_status_details<'a, I>(spends: I, query: &Query, options: ResponseOptions)
+where
+    I: IntoIterator<Item = &'a mut SpendingValue>,
+{
+    if options.v1 {
+        let mempool = query.mempool();
+        for spend in spends {
+            if let (Some(txid), Some(status)) = (spend.txid, spend.status.as_mut()) {
+                mempool.add_status_details(&txid, status);
+            }
         }
     }
 }
 
+// Respond with one entry per requested txid, in order, which is null for missing txs.
+// With `?strict=true`, any missing tx fails the whole request with a 404 instead.
+fn txs_by_txid_response<T: Serialize>(
+    txids: &[Txid],
+    values: Vec<Option<T>>,
+    query_params: &HashMap<String, String>,
+) -> Result<Response<Body>, HttpError> {
+    if query_params.get("strict").map_or(false, |s| s == "true") {
+        let missing = missing_txids(txids, &values);
+        if !missing.is_empty() {
+            let missing: Vec<String> = missing.iter().map(|txid| txid.to_hex()).collect();
+            bail!(HttpError::not_found(format!(
+                "Transactions not found: {}",
+                missing.join(",")
+            )));
+        }
+    }
+    json_response(values, 0, query_params)
+}
+
+fn missing_txids<T>(txids: &[Txid], values: &[Option<T>]) -> Vec<Txid> {
+    txids
+        .iter()
+        .zip(values)
+        .filter(|(_, value)| value.is_none())
+        .map(|(txid, _)| *txid)
+        .collect()
+}
+
 fn ttl_by_depth(height: Option<usize>, query: &Query) -> u32 {
     height.map_or(TTL_SHORT, |height| {
         if query.chain().best_height() - height >= CONF_FINAL {
@@ -563,6 +944,125 @@ fn find_txid(
     }
 }
 
+/// Serialize a transaction in the shape of bitcoind's verbose `getrawtransaction`, as returned
+/// by Electrum's `blockchain.transaction.get` with `verbose=true`.
+#[cfg(not(feature = "liquid"))]
+fn electrum_verbose_tx(
+    tx: &Transaction,
+    blockid: Option<&BlockId>,
+    query: &Query,
+    config: &Config,
+) -> serde_json::Value {
+    let vin: Vec<serde_json::Value> = tx
+        .input
+        .iter()
+        .map(|txin| {
+            let mut value = if is_coinbase(txin) {
+                json!({ "coinbase": txin.script_sig.to_hex() })
+            } else {
+                json!({
+                    "txid": txin.previous_output.txid,
+                    "vout": txin.previous_output.vout,
+                    "scriptSig": {
+                        "asm": txin.script_sig.to_asm(),
+                        "hex": txin.script_sig.to_hex(),
+                    },
+                })
+            };
+            if !txin.witness.is_empty() {
+                value["txinwitness"] = json!(txin
+                    .witness
+                    .iter()
+                    .map(hex::encode)
+                    .collect::<Vec<String>>());
+            }
+            value["sequence"] = json!(txin.sequence);
+            value
+        })
+        .collect();
+
+    let vout: Vec<serde_json::Value> = tx
+        .output
+        .iter()
+        .enumerate()
+        .map(|(n, txout)| {
+            let mut script_pubkey = json!({
+                "asm": txout.script_pubkey.to_asm(),
+                "hex": txout.script_pubkey.to_hex(),
+                "type": bitcoind_script_type(&txout.script_pubkey),
+            });
+            if let Some(address) = txout.script_pubkey.to_address_str(config.network_type) {
+                script_pubkey["address"] = json!(address);
+            }
+            json!({
+                "value": txout.value as f64 / 100_000_000f64,
+                "n": n,
+                "scriptPubKey": script_pubkey,
+            })
+        })
+        .collect();
+
+    let mut value = json!({
+        "txid": tx.txid(),
+        "hash": tx.wtxid(),
+        "version": tx.version,
+        "size": tx.size(),
+        "vsize": tx.vsize(),
+        "weight": tx.weight(),
+        "locktime": tx.lock_time,
+        "vin": vin,
+        "vout": vout,
+        "hex": hex::encode(encode::serialize(tx)),
+    });
+    if let Some(blockid) = blockid {
+        value["blockhash"] = json!(blockid.hash);
+        value["confirmations"] = json!(query.chain().best_height() - blockid.height + 1);
+        value["time"] = json!(blockid.time);
+        value["blocktime"] = json!(blockid.time);
+    }
+    value
+}
+
+/// Map our script type names onto the ones used by bitcoind's `scriptPubKey.type`
+#[cfg(not(feature = "liquid"))]
+fn bitcoind_script_type(script: &Script) -> &'static str {
+    match script_type(script) {
+        "p2pk" => "pubkey",
+        "p2pkh" => "pubkeyhash",
+        "p2sh" => "scripthash",
+        "v0_p2wpkh" => "witness_v0_keyhash",
+        "v0_p2wsh" => "witness_v0_scripthash",
+        "v1_p2tr" => "witness_v1_taproot",
+        "op_return" => "nulldata",
+        "multisig" => "multisig",
+        "anchor" => "anchor",
+        t if t.ends_with("_unknown") => "witness_unknown",
+        _ => "nonstandard",
+    }
+}
+
+// Response conventions picked by the request's path prefix. Unprefixed paths keep the
+// Esplora-compatible shapes, while /v1/ paths (including /v1/ws and /v1/stream/*) route to the
+// same handlers with the newer ones:
+// - errors always use the `{"error":{"code":..,"message":..}}` envelope, also in batch results
+//   and in websocket and event stream error frames
+// - the status of an unconfirmed tx carries its mempool `fee`, `vsize`, `feerate` and
+//   `first_seen` wherever it appears, like /tx/:txid/status always did
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct ResponseOptions {
+    v1: bool,
+}
+
+// Split the optional API version prefix off a request path
+fn split_api_version(path: &str) -> (&str, ResponseOptions) {
+    match path.strip_prefix(API_V1_PREFIX) {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+            (rest, ResponseOptions { v1: true })
+        }
+        _ => (path, ResponseOptions::default()),
+    }
+}
+
 /// Prepare transactions to be serialized in a JSON response
 ///
 /// Any transactions with missing prevouts will be filtered out of the response, rather than returned with incorrect data.
@@ -570,9 +1070,25 @@ fn prepare_txs(
     txs: Vec<(Transaction, Option<BlockId>)>,
     query: &Query,
     config: &Config,
+    options: ResponseOptions,
 ) -> Vec<TransactionValue> {
+    prepare_txs_aligned(txs.into_iter().map(Some).collect(), query, config, options)
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+// Like prepare_txs(), but keeps a None entry for each tx that is missing or couldn't be
+// prepared, so that results line up with the input
+fn prepare_txs_aligned(
+    txs: Vec<Option<(Transaction, Option<BlockId>)>>,
+    query: &Query,
+    config: &Config,
+    options: ResponseOptions,
+) -> Vec<Option<TransactionValue>> {
     let outpoints = txs
         .iter()
+        .flatten()
         .flat_map(|(tx, _)| {
             tx.input
                 .iter()
@@ -583,8 +1099,31 @@ fn prepare_txs(
 
     let prevouts = query.lookup_txos(&outpoints);
 
+    // unconfirmed txs are checked for finality against the next block
+    let tip_height = query.chain().best_height();
+    let next_height = tip_height as u32 + 1;
+    let tip_mtp = query.chain().get_mtp(tip_height);
+
     txs.into_iter()
-        .filter_map(|(tx, blockid)| TransactionValue::new(tx, blockid, &prevouts, config).ok())
+        .map(|tx| {
+            let (tx, blockid) = tx?;
+            let txid = tx.txid();
+            let is_final = if blockid.is_none() {
+                Some(is_final_tx(&tx, next_height, tip_mtp))
+            } else {
+                None
+            };
+            let mut value = TransactionValue::new(tx, blockid, &prevouts, config).ok()?;
+            if let Some(is_final) = is_final {
+                let mempool = query.mempool();
+                value.bip125_replaceable |= mempool.has_rbf_signaling_ancestor(&txid);
+                value.is_final = is_final;
+                if let (true, Some(status)) = (options.v1, value.status.as_mut()) {
+                    mempool.add_status_details(&txid, status);
+                }
+            }
+            Some(value)
+        })
         .collect()
 }
 
@@ -593,104 +1132,1707 @@ async fn run_server(
     config: Arc<Config>,
     query: Arc<Query>,
     rx: oneshot::Receiver<()>,
-    metric: HistogramVec,
+    metrics: RestMetrics,
+    tls_config: Option<Arc<rustls::ServerConfig>>,
 ) {
-    let addr = &config.http_addr;
-    let socket_file = &config.http_socket_file;
-
-    let config = Arc::clone(&config);
-    let query = Arc::clone(&query);
-
-    let make_service_fn_inn = || {
+    let rate_limiter = config.rest_rate_limit.map(|rate| {
+        Arc::new(RateLimiter::new(
+            rate,
+            config.rest_rate_limit_burst,
+            config.rest_rate_limit_max_clients,
+        ))
+    });
+    let heavy_routes = Some(config.rest_heavy_concurrency)
+        .filter(|limit| *limit > 0)
+        .map(|limit| Arc::new(Semaphore::new(limit)));
+    let stream_slots = Arc::new(Semaphore::new(config.rest_sse_max_connections));
+    let ws_slots = Arc::new(Semaphore::new(config.rest_ws_max_connections));
+
+    // fan out the shutdown signal to all the listeners and event streams
+    let (shutdown_tx, shutdown_rx) = watch::channel(());
+    tokio::spawn(async move {
+        rx.await.ok();
+        shutdown_tx.send(()).ok();
+    });
+    let streams_shutdown = shutdown_rx.clone();
+
+    let service_config = Arc::clone(&config);
+    let service_ws_slots = Arc::clone(&ws_slots);
+    let make_service_fn_inn = Arc::new(move |peer: Peer| {
+        let config = Arc::clone(&service_config);
         let query = Arc::clone(&query);
-        let config = Arc::clone(&config);
-        let metric = metric.clone();
+        let metrics = metrics.clone();
+        let rate_limiter = rate_limiter.clone();
+        let heavy_routes = heavy_routes.clone();
+        let stream_slots = Arc::clone(&stream_slots);
+        let ws_slots = Arc::clone(&service_ws_slots);
+        let streams_shutdown = streams_shutdown.clone();
 
         async move {
             Ok::<_, hyper::Error>(service_fn(move |req| {
                 let query = Arc::clone(&query);
                 let config = Arc::clone(&config);
-                let timer = metric.with_label_values(&["all_methods"]).start_timer();
+                let metrics = metrics.clone();
+                let rate_limiter = rate_limiter.clone();
+                let heavy_routes = heavy_routes.clone();
+                let stream_slots = Arc::clone(&stream_slots);
+                let ws_slots = Arc::clone(&ws_slots);
+                let streams_shutdown = streams_shutdown.clone();
+                let started = Instant::now();
 
                 async move {
+                    let route = route_label(req.uri().path());
+                    let method_label = method_label(req.method());
+                    let request_id = request_id(req.headers());
+                    // every response goes through here, including errors and rejected requests
+                    let observe = |resp: &mut Response<Body>| {
+                        let elapsed = started.elapsed();
+                        metrics
+                            .response_timer
+                            .with_label_values(&[
+                                method_label,
+                                route.as_str(),
+                                resp.status().as_str(),
+                            ])
+                            .observe(elapsed.as_secs_f64());
+                        resp.headers_mut()
+                            .insert(REQUEST_ID_HEADER, request_id.clone());
+                        if let Some(ResponseError(err)) = resp.extensions().get::<ResponseError>() {
+                            warn!(
+                                target: ACCESS_LOG_TARGET,
+                                "request_id={} {}",
+                                request_id.to_str().unwrap_or("-"),
+                                err
+                            );
+                        }
+                        if config.http_access_log {
+                            log_access(method_label, &route, resp, elapsed, peer, &request_id);
+                        }
+                    };
+                    let (_, options) = split_api_version(req.uri().path());
+                    let json_errors =
+                        config.rest_json_errors || accepts_json(req.headers()) || options.v1;
+                    let client_ip = client_ip(peer, req.headers(), &config);
+                    if let (Some(limiter), Some(ip)) = (&rate_limiter, client_ip) {
+                        if let Err(wait) = limiter.check(ip) {
+                            metrics.throttled.with_label_values(&[route.as_str()]).inc();
+                            let mut resp = rate_limited_response(wait, json_errors);
+                            if let Some(ref origins) = config.cors {
+                                set_cors_headers(
+                                    &mut resp,
+                                    origins,
+                                    req.headers().get(header::ORIGIN),
+                                );
+                            }
+                            observe(&mut resp);
+                            return Ok::<_, hyper::Error>(resp);
+                        }
+                    }
+                    // routes dispatched here rather than by handle_request get its checks first
+                    let (api_path, _) = split_api_version(req.uri().path());
+                    let is_websocket = api_path == "/ws";
+                    // event streams never complete, so they can't be handled like other requests
+                    let is_stream = api_path.starts_with("/stream/");
+                    let dispatched_early = is_websocket
+                        || is_stream
+                        || (req.method() == Method::POST && api_path == "/batch")
+                        || (req.method() == Method::GET && is_tx_status_wait(req.uri()));
+                    if dispatched_early {
+                        let path: Vec<&str> = api_path.split('/').skip(1).collect();
+                        let query_params = parse_query_params(req.uri());
+                        let gate =
+                            gate_request(req.method(), &path, &query_params, peer, &query, &config)
+                                .unwrap_or_else(|err| Some(error_response(err, json_errors)));
+                        if let Some(mut resp) = gate {
+                            if let Some(ref origins) = config.cors {
+                                set_cors_headers(
+                                    &mut resp,
+                                    origins,
+                                    req.headers().get(header::ORIGIN),
+                                );
+                            }
+                            observe(&mut resp);
+                            return Ok::<_, hyper::Error>(resp);
+                        }
+                    }
+                    if is_websocket {
+                        let mut resp = handle_websocket(
+                            req,
+                            &query,
+                            &config,
+                            &ws_slots,
+                            streams_shutdown,
+                            &metrics,
+                        )
+                        .unwrap_or_else(|err| error_response(err, json_errors));
+                        observe(&mut resp);
+                        return Ok::<_, hyper::Error>(resp);
+                    }
+                    if is_stream {
+                        let mut resp =
+                            handle_stream(&req, &query, &config, &stream_slots, streams_shutdown)
+                                .unwrap_or_else(|err| error_response(err, json_errors));
+                        if let Some(ref origins) = config.cors {
+                            set_cors_headers(&mut resp, origins, req.headers().get(header::ORIGIN));
+                        }
+                        observe(&mut resp);
+                        return Ok::<_, hyper::Error>(resp);
+                    }
+
+                    // hyper drops this future when the client disconnects, which cancels the
+                    // handler's scans through the token
+                    let cancel = CancelToken::default();
+                    let on_disconnect = CancelOnDrop::new(cancel.clone(), &metrics.cancelled);
+
                     let method = req.method().clone();
                     let uri = req.uri().clone();
-                    let body = hyper::body::to_bytes(req.into_body()).await?;
+                    let path = split_api_version(uri.path()).0.to_string();
+                    let headers = req.headers().clone();
+                    let if_none_match = headers.get(header::IF_NONE_MATCH).cloned();
+                    let origin = headers.get(header::ORIGIN).cloned();
+                    let encoding = negotiate_encoding(&headers);
+                    let cbor = accepts_cbor(&headers);
+                    let limit = body_limit(&method, &path, &config);
+                    let body = read_body_limited(&headers, req.into_body(), limit).await;
+                    if matches!(&body, Err(err) if err.0 == StatusCode::PAYLOAD_TOO_LARGE) {
+                        metrics.oversized_bodies.inc();
+                    }
+                    let body = match (body, &heavy_routes) {
+                        (Ok(body), Some(semaphore)) if is_heavy_route(&path) => {
+                            acquire_heavy_permit(Arc::clone(semaphore), &config, &metrics)
+                                .await
+                                .map(|permit| (body, Some(permit)))
+                        }
+                        (body, _) => body.map(|body| (body, None)),
+                    };
 
-                    let mut resp = tokio::task::block_in_place(|| {
-                        handle_request(method, uri, body, &query, &config)
-                    })
-                    .unwrap_or_else(|err| {
-                        warn!("{:?}", err);
-                        Response::builder()
-                            .status(err.0)
-                            .header("Content-Type", "text/plain")
-                            .header("X-Powered-By", &**VERSION_STRING)
-                            .body(Body::from(err.1))
-                            .unwrap()
-                    });
+                    // status long-polls wait for a change, then get answered like any other
+                    // status request
+                    let body = match body {
+                        Ok(body) if method == Method::GET && is_tx_status_wait(&uri) => {
+                            wait_tx_status_change(&uri, &query, &config)
+                                .await
+                                .map(|()| body)
+                        }
+                        body => body,
+                    };
+                    let is_batch = method == Method::POST && path == "/batch";
+                    let mut resp = match body {
+                        Err(err) => Err(err),
+                        Ok((body, permit)) => {
+                            let operation = format!("{} {}", method, uri.path());
+                            let timeout = Some(config.rest_request_timeout)
+                                .filter(|secs| *secs > 0)
+                                .map(Duration::from_secs);
+                            let handler_cancel = cancel.clone();
+                            let (query, config) = (Arc::clone(&query), Arc::clone(&config));
+                            let handler = move || {
+                                // the permit is only released once the handler is done, even
+                                // if the client was already sent a timeout
+                                let _permit = permit;
+                                if is_batch {
+                                    handle_batch(
+                                        &body,
+                                        options,
+                                        peer,
+                                        &handler_cancel,
+                                        &query,
+                                        &config,
+                                    )
+                                } else {
+                                    handle_request(
+                                        method,
+                                        uri,
+                                        &headers,
+                                        body,
+                                        peer,
+                                        &handler_cancel,
+                                        &query,
+                                        &config,
+                                    )
+                                }
+                            };
+                            run_blocking(operation, timeout, &cancel, &metrics.timeouts, handler)
+                                .await
+                        }
+                    }
+                    .unwrap_or_else(|err| error_response(err, json_errors));
+                    resp.headers_mut().insert(
+                        CHAIN_GENERATION_HEADER,
+                        HeaderValue::from_str(&format!(
+                            "{}-{}",
+                            *STARTUP_NONCE,
+                            query.chain().tip_generation()
+                        ))
+                        .unwrap(),
+                    );
+                    if cbor {
+                        resp = cbor_response(resp).await;
+                    }
+                    if let Some(ref if_none_match) = if_none_match {
+                        revalidate(&mut resp, if_none_match);
+                    }
                     if let Some(ref origins) = config.cors {
-                        resp.headers_mut()
-                            .insert("Access-Control-Allow-Origin", origins.parse().unwrap());
+                        set_cors_headers(&mut resp, origins, origin.as_ref());
+                    }
+                    if config.rest_compression {
+                        resp =
+                            compress_response(resp, encoding, &config, &metrics.compression_saved)
+                                .await;
                     }
-                    timer.observe_duration();
+                    on_disconnect.disarm();
+                    observe(&mut resp);
                     Ok::<_, hyper::Error>(resp)
                 }
             }))
         }
-    };
-
-    let server = match socket_file {
-        None => {
-            info!("REST server running on {}", addr);
+    });
 
-            let socket = create_socket(addr);
-            socket.listen(511).expect("setting backlog failed");
+    let shutdown = move || {
+        let mut shutdown_rx = shutdown_rx.clone();
+        async move {
+            shutdown_rx.changed().await.ok();
+        }
+    };
 
-            Server::from_tcp(socket.into())
-                .expect("Server::from_tcp failed")
-                .serve(make_service_fn(move |_| make_service_fn_inn()))
-                .with_graceful_shutdown(async {
-                    rx.await.ok();
+    let mut servers = vec![];
+    for addr in &config.http_addrs {
+        let socket = create_socket(addr);
+        socket.listen(511).expect("setting backlog failed");
+        let make_service = Arc::clone(&make_service_fn_inn);
+
+        let server = match tls_config {
+            Some(ref tls_config) => {
+                info!("REST server running on {} (TLS)", addr);
+
+                socket
+                    .set_nonblocking(true)
+                    .expect("setting non-blocking mode failed");
+                let listener = tokio::net::TcpListener::from_std(socket.into())
+                    .expect("TcpListener::from_std failed");
+                let acceptor = TlsAcceptor::from(Arc::clone(tls_config));
+                let http2_enabled = config.http2_enabled;
+                let mut shutdown = Box::pin(shutdown());
+
+                // Connections are served on their own tasks, so that a slow TLS handshake doesn't
+                // hold up accepting others. Connections still open at shutdown are dropped.
+                tokio::spawn(async move {
+                    loop {
+                        let (stream, peer) = tokio::select! {
+                            _ = &mut shutdown => break,
+                            accepted = listener.accept() => match accepted {
+                                Ok(accepted) => accepted,
+                                Err(e) => {
+                                    warn!("failed accepting REST connection: {}", e);
+                                    continue;
+                                }
+                            },
+                        };
+                        let acceptor = acceptor.clone();
+                        let service = make_service(Peer::Tcp(peer));
+                        tokio::spawn(async move {
+                            let stream = match acceptor.accept(stream).await {
+                                Ok(stream) => stream,
+                                Err(e) => {
+                                    debug!("TLS handshake with {} failed: {}", peer, e);
+                                    return;
+                                }
+                            };
+                            let served = match service.await {
+                                Ok(service) => {
+                                    Http::new()
+                                        .http1_only(!http2_enabled)
+                                        .serve_connection(stream, service)
+                                        .with_upgrades()
+                                        .await
+                                }
+                                Err(e) => Err(e),
+                            };
+                            if let Err(e) = served {
+                                debug!("REST connection with {} failed: {}", peer, e);
+                            }
+                        });
+                    }
+                    Ok(())
                 })
-                .await
-        }
-        Some(path) => {
-            if let Ok(meta) = fs::metadata(path) {
-                // Cleanup socket file left by previous execution
-                if meta.file_type().is_socket() {
-                    fs::remove_file(path).ok();
-                }
             }
+            None => {
+                info!("REST server running on {}", addr);
+
+                tokio::spawn(
+                    Server::from_tcp(socket.into())
+                        .expect("Server::from_tcp failed")
+                        .http1_only(!config.http2_enabled)
+                        .serve(make_service_fn(move |conn: &AddrStream| {
+                            make_service(Peer::Tcp(conn.remote_addr()))
+                        }))
+                        .with_graceful_shutdown(shutdown()),
+                )
+            }
+        };
+        servers.push(server);
+    }
+
+    if let Some(path) = &config.http_socket_file {
+        if let Ok(meta) = fs::metadata(path) {
+            // Cleanup socket file left by previous execution
+            if meta.file_type().is_socket() {
+                fs::remove_file(path).ok();
+            }
+        }
 
-            info!("REST server running on unix socket {}", path.display());
+        info!("REST server running on unix socket {}", path.display());
 
+        let make_service = Arc::clone(&make_service_fn_inn);
+        servers.push(tokio::spawn(
             Server::bind_unix(path)
                 .expect("Server::bind_unix failed")
-                .serve(make_service_fn(move |_| make_service_fn_inn()))
-                .with_graceful_shutdown(async {
-                    rx.await.ok();
-                })
-                .await
+                .http1_only(!config.http2_enabled)
+                .serve(make_service_fn(move |_| make_service(Peer::Unix)))
+                .with_graceful_shutdown(shutdown()),
+        ));
+    }
+
+    for server in servers {
+        match server.await {
+            Ok(Ok(())) => (),
+            Ok(Err(e)) => error!(target: ACCESS_LOG_TARGET, "server error: {}", e),
+            Err(e) => error!(target: ACCESS_LOG_TARGET, "server task failed: {}", e),
         }
-    };
+    }
 
-    if let Err(e) = server {
-        eprintln!("server error: {}", e);
+    // upgraded websockets aren't tracked by the servers, give them a chance to send their close
+    // frame before the runtime goes away (each one holds a slot until it's done)
+    let all_slots = config.rest_ws_max_connections.min(u32::MAX as usize) as u32;
+    if tokio::time::timeout(WS_SHUTDOWN_TIMEOUT, ws_slots.acquire_many(all_slots))
+        .await
+        .is_err()
+    {
+        warn!("websocket connections did not close in time");
     }
 }
 
-pub fn start(config: Arc<Config>, query: Arc<Query>, metrics: &Metrics) -> Handle {
+// The listener a REST request was received on
+#[derive(Clone, Copy, Debug)]
+enum Peer {
+    Unix,
+    Tcp(SocketAddr),
+}
+
+impl fmt::Display for Peer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Peer::Unix => write!(f, "unix socket"),
+            Peer::Tcp(addr) => write!(f, "{}", addr),
+        }
+    }
+}
+
+fn load_tls_config(cert_path: &Path, key_path: &Path, http2: bool) -> rustls::ServerConfig {
+    let open = |path: &Path| {
+        fs::File::open(path)
+            .map(io::BufReader::new)
+            .unwrap_or_else(|e| panic!("cannot read {}: {}", path.display(), e))
+    };
+    let certs: Vec<rustls::Certificate> = rustls_pemfile::certs(&mut open(cert_path))
+        .unwrap_or_else(|e| panic!("invalid PEM in {}: {}", cert_path.display(), e))
+        .into_iter()
+        .map(rustls::Certificate)
+        .collect();
+    if certs.is_empty() {
+        panic!("no certificate found in {}", cert_path.display());
+    }
+    let key = rustls_pemfile::read_all(&mut open(key_path))
+        .unwrap_or_else(|e| panic!("invalid PEM in {}: {}", key_path.display(), e))
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::ECKey(key) => Some(rustls::PrivateKey(key)),
+            _ => None,
+        })
+        .unwrap_or_else(|| panic!("no private key found in {}", key_path.display()));
+
+    let mut tls_config = rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .unwrap_or_else(|e| panic!("invalid TLS certificate or key: {}", e));
+    if http2 {
+        tls_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    }
+    tls_config
+}
+
+// Transaction submission endpoints accept larger bodies, up to the size of a hex-encoded
+// consensus-valid transaction
+fn body_limit(method: &Method, path: &str, config: &Config) -> usize {
+    match (method, path) {
+        (&Method::POST, "/tx")
+        | (&Method::POST, "/tx/decode")
+        | (&Method::POST, "/tx/test")
+        | (&Method::POST, "/txs/test")
+        | (&Method::POST, "/txs/package") => config.rest_max_tx_body_size,
+        _ => config.rest_max_body_size,
+    }
+}
+
+// Literal path segments used by the REST routes, any other segment is a route parameter
+#[rustfmt::skip]
+const ROUTE_SEGMENTS: &[&str] = &[
+    "address", "address-prefix", "addresses", "all", "ancestors", "ancestry", "asset", "assets",
+    "audit-summary", "batch", "block", "block-height", "blocks", "broadcast", "by-outpoint",
+    "by-txid", "chain", "conflicts", "decimal", "decode", "descendants", "fee-estimates", "fees",
+    "hash", "header", "health", "height", "hex", "index-stats", "info", "internal", "last",
+    "mempool", "merkle-proof", "merkleblock-proof", "output-types", "outputs", "outspend",
+    "outspends", "package", "page", "ping", "raw", "raw-proof", "ready", "recent", "registry",
+    "replaceability", "resync", "scripthash", "scripthashes", "scripttypes", "spent-bitmap",
+    "stats", "status", "stream", "sum", "summary", "supply", "sync-status", "test", "tip", "tx",
+    "tx-fees", "tx-prefix", "txid", "txids", "txs", "utxo", "utxos", "verify-merkle-proof", "vin",
+    "vout", "weight-breakdown", "ws", "wtxid",
+];
+
+// The routes served by the REST API, as labeled by route_label()
+#[rustfmt::skip]
+const ROUTE_LABELS: &[&str] = &[
+    "/ping", "/health", "/info", "/ready", "/ws", "/batch",
+    "/stream/blocks", "/stream/address/:address",
+    "/blocks", "/blocks/:height", "/blocks/tip/hash", "/blocks/tip/height", "/blocks/tip/header",
+    "/block-height/:height", "/block/height/:height",
+    "/block/:hash", "/block/:hash/status", "/block/:hash/stats", "/block/:hash/audit-summary",
+    "/block/:hash/tx-fees", "/block/:hash/txids", "/block/:hash/output-types",
+    "/block/:hash/header", "/block/:hash/raw", "/block/:hash/txid/:param", "/block/:hash/txs",
+    "/block/:hash/txs/:param",
+    "/address/:address", "/address/:address/txs", "/address/:address/txs/last",
+    "/address/:address/txs/chain", "/address/:address/txs/chain/:param",
+    "/address/:address/txs/summary", "/address/:address/txs/summary/:param",
+    "/address/:address/txs/mempool", "/address/:address/utxo",
+    "/address/:address/stats/scripttypes", "/address/:address/outputs/spent-bitmap",
+    "/scripthash/:hash", "/scripthash/:hash/txs", "/scripthash/:hash/txs/last",
+    "/scripthash/:hash/txs/chain", "/scripthash/:hash/txs/chain/:param",
+    "/scripthash/:hash/txs/summary", "/scripthash/:hash/txs/summary/:param",
+    "/scripthash/:hash/txs/mempool", "/scripthash/:hash/utxo",
+    "/scripthash/:hash/stats/scripttypes", "/scripthash/:hash/outputs/spent-bitmap",
+    "/addresses/txs", "/addresses/txs/summary", "/addresses/txs/summary/:param",
+    "/addresses/utxo/sum",
+    "/scripthashes/txs", "/scripthashes/txs/summary", "/scripthashes/txs/summary/:param",
+    "/scripthashes/utxo/sum",
+    "/address-prefix/:prefix", "/tx-prefix/:prefix",
+    "/tx", "/tx/:txid", "/tx/:txid/hex", "/tx/:txid/raw", "/tx/:txid/ancestors",
+    "/tx/:txid/descendants", "/tx/:txid/wtxid", "/tx/:txid/weight-breakdown",
+    "/tx/:txid/ancestry", "/tx/:txid/conflicts", "/tx/:txid/replaceability", "/tx/:txid/status",
+    "/tx/:txid/block", "/tx/:txid/merkle-proof", "/tx/:txid/raw-proof",
+    "/tx/:txid/merkleblock-proof", "/tx/:txid/vin/:param", "/tx/:txid/vout/:param",
+    "/tx/:txid/outspend/:param", "/tx/:txid/outspends",
+    "/broadcast", "/tx/decode", "/tx/verify-merkle-proof", "/tx/test",
+    "/txs/fees", "/txs/package", "/txs/test", "/txs/outspends", "/outspends",
+    "/mempool", "/mempool/ancestors/:param", "/mempool/descendants/:param", "/mempool/txids",
+    "/mempool/txids/page", "/mempool/txids/page/:param", "/mempool/recent",
+    "/fee-estimates", "/fee-estimates/mempool",
+    "/internal/txs", "/internal/block/:hash/txs", "/internal/txs/outspends/by-txid",
+    "/internal/txs/outspends/by-outpoint", "/internal/index-stats", "/internal/sync-status",
+    "/internal/mempool/resync", "/internal/mempool/txs", "/internal/mempool/txs/all",
+    "/internal/mempool/txs/:param",
+    "/assets/registry", "/asset/:asset_id", "/asset/:asset_id/txs", "/asset/:asset_id/txs/chain",
+    "/asset/:asset_id/txs/chain/:param", "/asset/:asset_id/txs/mempool",
+    "/asset/:asset_id/utxos", "/asset/:asset_id/supply", "/asset/:asset_id/supply/:param",
+    "/asset/:asset_id/supply/decimal",
+];
+
+// Normalize a request path into a route label for the response time metrics, replacing
+// parameters with placeholders (e.g. `/tx/:txid/outspends`). To keep the label cardinality
+// bounded, paths that don't normalize to one of ROUTE_LABELS are all labeled as "other".
+fn route_label(path: &str) -> String {
+    let (path, options) = split_api_version(path);
+    let segments: Vec<&str> = path.split('/').skip(1).collect();
+    if segments.len() > 6 {
+        return "other".to_string();
+    }
+    let mut label = String::new();
+    let mut prev = "";
+    for segment in segments {
+        let segment = if ROUTE_SEGMENTS.contains(&segment) {
+            segment
+        } else {
+            match prev {
+                "tx" => ":txid",
+                "block" | "scripthash" => ":hash",
+                "block-height" | "blocks" | "height" => ":height",
+                "address" => ":address",
+                "asset" => ":asset_id",
+                "address-prefix" | "tx-prefix" => ":prefix",
+                _ => ":param",
+            }
+        };
+        label.push('/');
+        label.push_str(segment);
+        prev = segment;
+    }
+    if !ROUTE_LABELS.contains(&label.as_str()) {
+        return "other".to_string();
+    }
+    if options.v1 {
+        label.insert_str(0, API_V1_PREFIX);
+    }
+    label
+}
+
+fn method_label(method: &Method) -> &'static str {
+    match *method {
+        Method::GET => "GET",
+        Method::POST => "POST",
+        Method::HEAD => "HEAD",
+        Method::OPTIONS => "OPTIONS",
+        _ => "other",
+    }
+}
+
+// Buffer the request body, failing with a 413 as soon as it's known to exceed `limit`, either
+// from the Content-Length header or while reading it
+async fn read_body_limited(
+    headers: &HeaderMap,
+    mut body: Body,
+    limit: usize,
+) -> Result<hyper::body::Bytes, HttpError> {
+    let too_large = || {
+        HttpError::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("Request body exceeds the limit of {} bytes", limit),
+        )
+    };
+    let content_length = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+    if content_length.map_or(false, |len| len > limit as u64) {
+        return Err(too_large());
+    }
+
+    let mut buf = Vec::with_capacity(content_length.unwrap_or(0) as usize);
+    while let Some(chunk) = body.data().await {
+        let chunk =
+            chunk.map_err(|e| HttpError::from(format!("Failed reading request body: {}", e)))?;
+        if buf.len() + chunk.len() > limit {
+            return Err(too_large());
+        }
+        buf.extend_from_slice(&chunk);
+    }
+    Ok(buf.into())
+}
+
+// Run a request handler on the blocking thread pool, optionally giving up waiting for it after
+// the configured timeout. The client then gets a timely 504 naming the operation that timed out,
+// while the handler is cancelled and stops at its next cancellation check.
+async fn run_blocking<F>(
+    operation: String,
+    timeout: Option<Duration>,
+    cancel: &CancelToken,
+    timeouts: &CounterVec,
+    handler: F,
+) -> Result<Response<Body>, HttpError>
+where
+    F: FnOnce() -> Result<Response<Body>, HttpError> + Send + 'static,
+{
+    let task = tokio::task::spawn_blocking(handler);
+    let result = match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, task).await,
+        None => Ok(task.await),
+    };
+    match result {
+        Ok(Ok(result)) => result,
+        Ok(Err(e)) => Err(HttpError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Request handler failed: {}", e),
+        )),
+        Err(_) => {
+            let timeout = timeout.unwrap_or_default();
+            cancel.cancel();
+            timeouts.with_label_values(&["handler"]).inc();
+            warn!("request timed out after {:?}: {}", timeout, operation);
+            Err(HttpError::new(
+                StatusCode::GATEWAY_TIMEOUT,
+                format!(
+                    "Request timed out after {} seconds: {}",
+                    timeout.as_secs(),
+                    operation
+                ),
+            ))
+        }
+    }
+}
+
+#[derive(Clone)]
+struct RestMetrics {
+    response_timer: HistogramVec,
+    oversized_bodies: Counter,
+    compression_saved: Counter,
+    throttled: CounterVec,
+    heavy_queue_depth: Gauge,
+    timeouts: CounterVec,
+    cancelled: Counter,
+    ws_connections: Gauge,
+    ws_subscriptions: Gauge,
+}
+
+// Use the X-Request-Id set by a proxy in front of us so that log lines can be correlated,
+// or make one up if there's none (or it doesn't look like an id)
+fn request_id(headers: &HeaderMap) -> HeaderValue {
+    headers
+        .get(REQUEST_ID_HEADER)
+        .filter(|id| {
+            let id = id.as_bytes();
+            !id.is_empty() && id.len() <= 128 && id.iter().all(u8::is_ascii_graphic)
+        })
+        .cloned()
+        .unwrap_or_else(|| {
+            let count = REQUEST_ID_COUNTER.fetch_add(1, Ordering::Relaxed);
+            HeaderValue::from_str(&format!("{}-{:x}", *STARTUP_NONCE, count))
+                .expect("request ids are valid header values")
+        })
+}
+
+fn log_access(
+    method: &str,
+    route: &str,
+    resp: &Response<Body>,
+    elapsed: Duration,
+    peer: Peer,
+    request_id: &HeaderValue,
+) {
+    let bytes = resp
+        .body()
+        .size_hint()
+        .exact()
+        .map_or_else(|| "-".to_string(), |bytes| bytes.to_string());
+    info!(
+        target: ACCESS_LOG_TARGET,
+        "method={} route={} status={} duration_ms={} bytes={} peer=\"{}\" request_id={}",
+        method,
+        route,
+        resp.status().as_u16(),
+        elapsed.as_millis(),
+        bytes,
+        peer,
+        request_id.to_str().unwrap_or("-"),
+    );
+}
+
+// Cancels a request's token unless disarmed once the response is ready
+struct CancelOnDrop<'a> {
+    cancel: CancelToken,
+    cancelled: &'a Counter,
+    armed: bool,
+}
+
+impl<'a> CancelOnDrop<'a> {
+    fn new(cancel: CancelToken, cancelled: &'a Counter) -> Self {
+        CancelOnDrop {
+            cancel,
+            cancelled,
+            armed: true,
+        }
+    }
+
+    fn disarm(mut self) {
+        self.armed = false;
+    }
+}
+
+impl Drop for CancelOnDrop<'_> {
+    fn drop(&mut self) {
+        if self.armed {
+            self.cancel.cancel();
+            self.cancelled.inc();
+        }
+    }
+}
+
+// Stop handling a request whose client went away, rather than building a response nobody reads
+fn check_cancelled(cancel: &CancelToken) -> Result<(), HttpError> {
+    if cancel.is_cancelled() {
+        Err(HttpError::cancelled())
+    } else {
+        Ok(())
+    }
+}
+
+// Routes that can take a lot of db reads to serve, whose concurrency is capped by
+// --rest-heavy-concurrency so that they can't starve cheaper requests
+fn is_heavy_route(path: &str) -> bool {
+    let path: Vec<&str> = path.split('/').skip(1).collect();
+    match path.as_slice() {
+        ["address", _, rest @ ..] | ["scripthash", _, rest @ ..] => {
+            matches!(
+                rest.first(),
+                Some(&"txs") | Some(&"summary") | Some(&"utxo")
+            )
+        }
+        ["addresses", ..] | ["scripthashes", ..] | ["batch"] => true,
+        ["block", _, "txs", ..] => true,
+        [INTERNAL_PREFIX, "block", _, "txs"] | [INTERNAL_PREFIX, "mempool", "txs", ..] => true,
+        _ => false,
+    }
+}
+
+// Keeps the queue depth gauge accurate when a waiting request is dropped by a disconnecting client
+struct QueueSlot<'a>(&'a Gauge);
+
+impl<'a> QueueSlot<'a> {
+    fn enter(gauge: &'a Gauge) -> Self {
+        gauge.inc();
+        QueueSlot(gauge)
+    }
+}
+
+impl Drop for QueueSlot<'_> {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
+}
+
+// Wait for a free slot to run a heavy route, failing with a 503 if none frees up in time
+async fn acquire_heavy_permit(
+    semaphore: Arc<Semaphore>,
+    config: &Config,
+    metrics: &RestMetrics,
+) -> Result<OwnedSemaphorePermit, HttpError> {
+    if let Ok(permit) = Arc::clone(&semaphore).try_acquire_owned() {
+        return Ok(permit);
+    }
+    let _slot = QueueSlot::enter(&metrics.heavy_queue_depth);
+    let timeout = Duration::from_secs(config.rest_heavy_queue_timeout);
+    match tokio::time::timeout(timeout, semaphore.acquire_owned()).await {
+        Ok(permit) => Ok(permit.expect("heavy routes semaphore is never closed")),
+        Err(_) => {
+            metrics.timeouts.with_label_values(&["queue"]).inc();
+            Err(HttpError::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Too many expensive requests in progress, try again later".to_string(),
+            )
+            .with_code("busy"))
+        }
+    }
+}
+
+// The error that an error response was made from, logged by run_server() along with the
+// request id
+struct ResponseError(String);
+
+fn error_response(err: HttpError, json: bool) -> Response<Body> {
+    let logged = ResponseError(format!("{:?}", err));
+    let (content_type, body) = if json {
+        let envelope = json!({ "error": { "code": err.code(), "message": err.1 } });
+        ("application/json", envelope.to_string())
+    } else {
+        ("text/plain", err.1)
+    };
+    let mut resp = Response::builder()
+        .status(err.0)
+        .header("Content-Type", content_type)
+        .header("X-Powered-By", &**VERSION_STRING)
+        .body(Body::from(body))
+        .unwrap();
+    resp.extensions_mut().insert(logged);
+    resp
+}
+
+// Errors reported within a response rather than by its status: websocket and event stream error
+// frames, and failed batch requests under /v1/
+fn error_value(err: HttpError, options: ResponseOptions) -> serde_json::Value {
+    if options.v1 {
+        json!({ "error": { "code": err.code(), "message": err.1 } })
+    } else {
+        json!({ "error": err.1 })
+    }
+}
+
+fn rate_limited_response(wait: Duration, json: bool) -> Response<Body> {
+    let mut resp = error_response(
+        HttpError::new(
+            StatusCode::TOO_MANY_REQUESTS,
+            "Too many requests, slow down".to_string(),
+        )
+        .with_code("rate_limited"),
+        json,
+    );
+    // Retry-After only takes whole seconds
+    let retry_after = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+    resp.headers_mut()
+        .insert(header::RETRY_AFTER, HeaderValue::from(retry_after.max(1)));
+    resp
+}
+
+// Optional capabilities of this instance, as reported by /info
+fn enabled_features(config: &Config) -> Vec<&'static str> {
+    let mut features = vec!["batch", "v1", "websocket"];
+    #[cfg(feature = "liquid")]
+    features.push("liquid");
+    let optional = [
+        ("address_search", config.address_search),
+        ("txid_search", config.txid_search),
+        ("light_mode", config.light_mode),
+        ("block_audit", config.rest_block_audit),
+        ("block_tx_fees", config.rest_block_tx_fees),
+        ("inscriptions", config.rest_inscriptions),
+        ("compression", config.rest_compression),
+        ("http2", config.http2_enabled),
+    ];
+    features.extend(optional.iter().filter(|(_, on)| *on).map(|(name, _)| *name));
+    features
+}
+
+// Routes that keep answering while the index is behind the daemon: the probes, the instance
+// info, and the internal routes to monitor and operate the sync
+fn serves_stale(path: &[&str]) -> bool {
+    matches!(
+        path.first(),
+        Some(&"ping") | Some(&"health") | Some(&"ready") | Some(&"info") | Some(&INTERNAL_PREFIX)
+    )
+}
+
+// Prevouts missing for an indexed block mean the index itself is inconsistent, so this isn't
+// reported as a missing block
+fn missing_prevouts_error(e: errors::Error) -> HttpError {
+    warn!("failed computing block fees: {}", e);
+    HttpError::new(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "Prevouts missing from the index".to_string(),
+    )
+    .with_code("missing_prevouts")
+}
+
+fn index_behind_response(
+    query: &Query,
+    behind_by: Option<usize>,
+    max_lag: usize,
+    query_params: &HashMap<String, String>,
+) -> Result<Response<Body>, HttpError> {
+    let mut resp = json_response(
+        json!({
+            "error": {
+                "code": "index_behind",
+                "message": "The index is catching up with the daemon's tip",
+            },
+            "indexed_height": query.chain().best_height(),
+            "behind_by": behind_by,
+            "max_lag_blocks": max_lag,
+        }),
+        0,
+        query_params,
+    )?;
+    *resp.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+    resp.headers_mut().insert(
+        header::RETRY_AFTER,
+        HeaderValue::from(INDEX_BEHIND_RETRY_AFTER),
+    );
+    Ok(resp)
+}
+
+fn starting_up_response(
+    query: &Query,
+    query_params: &HashMap<String, String>,
+) -> Result<Response<Body>, HttpError> {
+    let mut resp = json_response(
+        json!({
+            "error": {
+                "code": "starting_up",
+                "message": "The index is still being loaded",
+            },
+            "indexed_height": query.chain().best_height(),
+        }),
+        0,
+        query_params,
+    )?;
+    *resp.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+    resp.headers_mut().insert(
+        header::RETRY_AFTER,
+        HeaderValue::from(INDEX_BEHIND_RETRY_AFTER),
+    );
+    Ok(resp)
+}
+
+// The IP requests are rate limited by. Behind a trusted reverse proxy, this is the last address
+// in the configured header, as that's the one the proxy itself appended. Requests over the unix
+// socket without that header aren't limited.
+fn client_ip(peer: Peer, headers: &HeaderMap, config: &Config) -> Option<IpAddr> {
+    let forwarded = config
+        .rest_trusted_proxy_header
+        .as_ref()
+        .and_then(|name| headers.get(name.as_str()))
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.rsplit(',').next())
+        .and_then(|ip| ip.trim().parse().ok());
+    match peer {
+        Peer::Tcp(addr) => forwarded.or_else(|| Some(addr.ip())),
+        Peer::Unix => forwarded,
+    }
+}
+
+#[derive(Deserialize)]
+struct VerifyMerkleProofRequest {
+    txid: String,
+    block_hash: Option<String>,
+    block_height: Option<usize>,
+    merkle: Vec<String>,
+    pos: usize,
+}
+
+fn parse_query_params(uri: &hyper::Uri) -> HashMap<String, String> {
+    match uri.query() {
+        Some(value) => form_urlencoded::parse(value.as_bytes())
+            .into_owned()
+            .collect(),
+        None => HashMap::new(),
+    }
+}
+
+// The checks every route goes through, whether it's routed by handle_request or dispatched
+// before it (/ws, /stream, /batch and the status long-poll). Returns the response to answer
+// with instead of handling the request, if any.
+fn gate_request(
+    method: &Method,
+    path: &[&str],
+    query_params: &HashMap<String, String>,
+    peer: Peer,
+    query: &Query,
+    config: &Config,
+) -> Result<Option<Response<Body>>, HttpError> {
+    if is_endpoint_disabled(method, path, &config.rest_disabled_endpoints) {
+        return Err(HttpError::new(
+            StatusCode::FORBIDDEN,
+            "This endpoint is disabled".to_string(),
+        ));
+    }
+    if path.first() == Some(&INTERNAL_PREFIX)
+        && !is_internal_access_allowed(peer, &config.internal_api_access)
+    {
+        return Err(HttpError::new(
+            StatusCode::FORBIDDEN,
+            "Internal API access denied".to_string(),
+        ));
+    }
+    // the REST server is up during the initial sync, but data routes have nothing consistent
+    // to serve before the first index update and mempool load
+    let started = query.chain().store().done_first_update() && query.mempool().is_loaded();
+    if !started && !serves_stale(path) {
+        return starting_up_response(query, query_params).map(Some);
+    }
+    if let Some(max_lag) = config.rest_max_lag_blocks {
+        let behind_by = query.chain().blocks_behind();
+        let allow_stale = query_params
+            .get("allow_stale")
+            .map_or(false, |v| v == "true");
+        if behind_by.map_or(true, |behind_by| behind_by > max_lag)
+            && !allow_stale
+            && !serves_stale(path)
+        {
+            return index_behind_response(query, behind_by, max_lag, query_params).map(Some);
+        }
+    }
+    Ok(None)
+}
+
+fn is_tx_status_wait(uri: &hyper::Uri) -> bool {
+    let (path, _) = split_api_version(uri.path());
+    let path: Vec<&str> = path.split('/').skip(1).collect();
+    matches!(path.as_slice(), ["tx", _, "status"])
+        && uri.query().map_or(false, |query| {
+            form_urlencoded::parse(query.as_bytes()).any(|(key, _)| key == "wait")
+        })
+}
+
+// Long-poll for /tx/:txid/status?wait=<seconds>[&min_conf=<n>]. Without min_conf, waits until
+// the tx enters the mempool, confirms or gets reorged out. With it, waits until the tx has at
+// least min_conf confirmations. The request is then answered by handle_request() like any other
+// status request, which also happens once the wait (capped by the request timeout) is over.
+async fn wait_tx_status_change(
+    uri: &hyper::Uri,
+    query: &Query,
+    config: &Config,
+) -> Result<(), HttpError> {
+    let (path, _) = split_api_version(uri.path());
+    let path: Vec<&str> = path.split('/').skip(1).collect();
+    let txid = Txid::from_hex(path[1])?;
+    let query_params = parse_query_params(uri);
+    let mut wait = query_params
+        .get("wait")
+        .map_or(Ok(0), |wait| wait.parse::<u64>())?
+        .min(TX_STATUS_MAX_WAIT);
+    if config.rest_request_timeout > 0 {
+        wait = wait.min(config.rest_request_timeout);
+    }
+    let min_conf = query_params
+        .get("min_conf")
+        .map(|min_conf| min_conf.parse::<usize>())
+        .transpose()?;
+
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(wait);
+    let mut updates = query.subscribe_updates();
+    let snapshot = || {
+        tokio::task::block_in_place(|| {
+            let status = query.get_tx_status(&txid);
+            let known = status.confirmed || query.lookup_txn(&txid).is_some();
+            (known, status)
+        })
+    };
+
+    let (initial_known, initial) = snapshot();
+    let initial_block_hash = initial.block_hash;
+    let mut status = initial;
+    let mut known = initial_known;
+    loop {
+        let done = match min_conf {
+            Some(min_conf) => status.block_height.map_or(false, |height| {
+                (query.chain().best_height() + 1).saturating_sub(height) >= min_conf
+            }),
+            None => known != initial_known || status.block_hash != initial_block_hash,
+        };
+        if done {
+            break;
+        }
+        match tokio::time::timeout_at(deadline, updates.changed()).await {
+            Ok(Ok(())) => {
+                let (new_known, new_status) = snapshot();
+                known = new_known;
+                status = new_status;
+            }
+            // timed out (or the update channel closed), answer with the current status
+            _ => break,
+        }
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct BatchRequest {
+    method: String,
+    path: String,
+}
+
+// Dispatch each sub-request through handle_request() sequentially. Failing sub-requests are
+// reported in their own result and don't affect the others. Runs on a blocking thread like any
+// other handler, so the whole batch is bound by the request timeout.
+fn handle_batch(
+    body: &[u8],
+    options: ResponseOptions,
+    peer: Peer,
+    cancel: &CancelToken,
+    query: &Query,
+    config: &Config,
+) -> Result<Response<Body>, HttpError> {
+    let requests: Vec<BatchRequest> = serde_json::from_slice(body)?;
+    if requests.len() > BATCH_LIMIT {
+        bail!(HttpError::from(format!(
+            "Exceeded maximum of {} requests",
+            BATCH_LIMIT
+        )));
+    }
+
+    let mut results = Vec::with_capacity(requests.len());
+    for request in requests {
+        check_cancelled(cancel)?;
+        let result = request
+            .path
+            .parse::<hyper::Uri>()
+            .map_err(|_| HttpError::from("Invalid path".to_string()))
+            .and_then(|uri| match request.method.parse::<Method>() {
+                // only reads can be batched
+                Ok(Method::GET) => handle_request(
+                    Method::GET,
+                    uri,
+                    &HeaderMap::new(),
+                    hyper::body::Bytes::new(),
+                    peer,
+                    cancel,
+                    query,
+                    config,
+                ),
+                _ => Err(HttpError::new(
+                    StatusCode::METHOD_NOT_ALLOWED,
+                    "Only GET requests can be batched".to_string(),
+                )),
+            });
+        let (status, body) = match result {
+            Ok(resp) => {
+                let status = resp.status();
+                let body = hyper::body::to_bytes(resp.into_body());
+                match tokio::runtime::Handle::current().block_on(body) {
+                    Ok(body) => (status, body.to_vec()),
+                    Err(e) => (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        e.to_string().into_bytes(),
+                    ),
+                }
+            }
+            Err(err) if options.v1 => (err.0, error_value(err, options).to_string().into_bytes()),
+            Err(err) => (err.0, err.1.into_bytes()),
+        };
+        // embed json bodies as is, and anything else as a string
+        let body = serde_json::from_slice::<serde_json::Value>(&body)
+            .unwrap_or_else(|_| String::from_utf8_lossy(&body).into());
+        results.push(json!({ "status": status.as_u16(), "body": body }));
+    }
+
+    json_response(results, 0, &HashMap::new())
+}
+
+fn handle_websocket(
+    mut req: Request<Body>,
+    query: &Arc<Query>,
+    config: &Arc<Config>,
+    slots: &Arc<Semaphore>,
+    shutdown: watch::Receiver<()>,
+    metrics: &RestMetrics,
+) -> Result<Response<Body>, HttpError> {
+    if req.method() != Method::GET {
+        return Err(HttpError::new(
+            StatusCode::METHOD_NOT_ALLOWED,
+            "Websocket upgrade requires GET".to_string(),
+        ));
+    }
+    let is_upgrade = req
+        .headers()
+        .get(header::UPGRADE)
+        .and_then(|value| value.to_str().ok())
+        .map_or(false, |value| value.eq_ignore_ascii_case("websocket"));
+    let accept_key = match req.headers().get(header::SEC_WEBSOCKET_KEY) {
+        Some(key) if is_upgrade => derive_accept_key(key.as_bytes()),
+        _ => {
+            return Err(HttpError::new(
+                StatusCode::UPGRADE_REQUIRED,
+                "Expected a websocket upgrade request".to_string(),
+            ))
+        }
+    };
+    let permit = Arc::clone(slots).try_acquire_owned().map_err(|_| {
+        HttpError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Too many websocket connections open, try again later".to_string(),
+        )
+        .with_code("busy")
+    })?;
+    let (_, options) = split_api_version(req.uri().path());
+
+    // Subscribe before responding so that no events are missed in between
+    let conn = WsConnection {
+        query: Arc::clone(query),
+        config: Arc::clone(config),
+        options,
+        events: query.mempool().subscribe_scripthash_events(),
+        updates: query.subscribe_updates(),
+        shutdown,
+        metrics: metrics.clone(),
+        _permit: permit,
+    };
+    let on_upgrade = hyper::upgrade::on(&mut req);
+    tokio::spawn(async move {
+        match on_upgrade.await {
+            Ok(upgraded) => {
+                let ws = WebSocketStream::from_raw_socket(upgraded, Role::Server, None).await;
+                serve_websocket(ws, conn).await;
+            }
+            Err(e) => warn!("websocket upgrade failed: {:?}", e),
+        }
+    });
+
+    Ok(Response::builder()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .header(header::CONNECTION, "Upgrade")
+        .header(header::UPGRADE, "websocket")
+        .header(header::SEC_WEBSOCKET_ACCEPT, accept_key)
+        .header("X-Powered-By", &**VERSION_STRING)
+        .body(Body::empty())
+        .unwrap())
+}
+
+// Everything a websocket connection needs once upgraded. Its slot is released when dropped.
+struct WsConnection {
+    query: Arc<Query>,
+    config: Arc<Config>,
+    options: ResponseOptions,
+    events: broadcast::Receiver<ScripthashEvent>,
+    updates: watch::Receiver<u64>,
+    shutdown: watch::Receiver<()>,
+    metrics: RestMetrics,
+    _permit: OwnedSemaphorePermit,
+}
+
+async fn serve_websocket(mut ws: WebSocketStream<Upgraded>, mut conn: WsConnection) {
+    let query = Arc::clone(&conn.query);
+    let mut subscriptions = WsSubscriptions::default();
+    let mut tip = query.chain().best_hash();
+    conn.metrics.ws_connections.inc();
+    'conn: loop {
+        let subscribed = subscriptions.len();
+        let replies = tokio::select! {
+            _ = conn.shutdown.changed() => {
+                let frame = CloseFrame {
+                    code: CloseCode::Away,
+                    reason: "server shutting down".into(),
+                };
+                ws.close(Some(frame)).await.ok();
+                break;
+            }
+            msg = ws.next() => match msg {
+                Some(Ok(Message::Text(text))) => vec![handle_ws_request(
+                    &text,
+                    &mut subscriptions,
+                    conn.config.network_type,
+                    conn.options,
+                )],
+                // pings are answered by tungstenite itself
+                Some(Ok(Message::Ping(_))) | Some(Ok(Message::Pong(_))) => continue,
+                Some(Ok(Message::Close(_))) | None => break,
+                Some(Ok(_)) => vec![error_value(
+                    HttpError::from("Expected a text message".to_string()),
+                    conn.options,
+                )],
+                Some(Err(e)) => {
+                    debug!("websocket error: {:?}", e);
+                    break;
+                }
+            },
+            event = conn.events.recv() => match event {
+                Ok(event) => {
+                    let mut replies = vec![];
+                    if subscriptions.scripthashes.contains(&event.scripthash) {
+                        replies.push(json!({
+                            "scripthash": hex::encode(event.scripthash),
+                            "txid": event.txid,
+                            "confirmed": event.confirmed,
+                        }));
+                    }
+                    if let Some(address) = subscriptions.addresses.get(&event.scripthash) {
+                        // reads the db (and bitcoind in light mode), off the async worker
+                        let (query, config) = (Arc::clone(&query), Arc::clone(&conn.config));
+                        let options = conn.options;
+                        let tx = lookup_blocking(move || {
+                            ws_transaction(&query, &config, &event.txid, options)
+                        })
+                        .await;
+                        if let Some(tx) = tx {
+                            replies.push(json!({ "address": address, "tx": tx }));
+                        }
+                    }
+                    replies
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => vec![error_value(
+                    HttpError::from(format!(
+                        "Connection lagging behind, {} events dropped",
+                        skipped
+                    ))
+                    .with_code("lagging"),
+                    conn.options,
+                )],
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            changed = conn.updates.changed() => {
+                if changed.is_err() {
+                    break;
+                }
+                // updates are sent for both new blocks and mempool changes
+                let mut replies = vec![];
+                let best_hash = query.chain().best_hash();
+                if best_hash != tip {
+                    tip = best_hash;
+                    if subscriptions.blocks {
+                        let query = Arc::clone(&query);
+                        let blockhm =
+                            lookup_blocking(move || query.chain().get_block_with_meta(&tip)).await;
+                        if let Some(blockhm) = blockhm {
+                            replies.push(json!({ "block": BlockValue::new(blockhm) }));
+                        }
+                    }
+                }
+                if subscriptions.mempool_recent {
+                    let recent = query.mempool().recent_txs_overview();
+                    replies.push(json!({ "mempool-recent": recent }));
+                }
+                replies
+            }
+        };
+        adjust_gauge(
+            &conn.metrics.ws_subscriptions,
+            subscribed,
+            subscriptions.len(),
+        );
+        for reply in replies {
+            if ws.send(Message::Text(reply.to_string())).await.is_err() {
+                break 'conn;
+            }
+        }
+    }
+    adjust_gauge(&conn.metrics.ws_subscriptions, subscriptions.len(), 0);
+    conn.metrics.ws_connections.dec();
+}
+
+fn adjust_gauge(gauge: &Gauge, before: usize, after: usize) {
+    gauge.add(after as i64 - before as i64);
+}
+
+// The full transaction pushed to address subscribers, as served by GET /tx/:txid
+// Run a db lookup for a websocket or event stream on the blocking thread pool, so that it doesn't
+// hold up the async worker shared with other connections
+async fn lookup_blocking<T, F>(lookup: F) -> Option<T>
+where
+    F: FnOnce() -> Option<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(lookup)
+        .await
+        .unwrap_or_else(|err| {
+            warn!("blocking lookup failed: {:?}", err);
+            None
+        })
+}
+
+fn ws_transaction(
+    query: &Query,
+    config: &Config,
+    txid: &Txid,
+    options: ResponseOptions,
+) -> Option<TransactionValue> {
+    let tx = query.lookup_txn(txid)?;
+    let blockid = query.chain().tx_confirming_block(txid);
+    prepare_txs(vec![(tx, blockid)], query, config, options).pop()
+}
+
+// Server-sent event streams: /stream/blocks pushes every new chain tip, and
+// /stream/address/:addresses pushes the txs touching any of the comma-separated addresses as
+// they enter the mempool and get confirmed
+fn handle_stream(
+    req: &Request<Body>,
+    query: &Arc<Query>,
+    config: &Config,
+    slots: &Arc<Semaphore>,
+    shutdown: watch::Receiver<()>,
+) -> Result<Response<Body>, HttpError> {
+    if req.method() != Method::GET {
+        return Err(HttpError::new(
+            StatusCode::METHOD_NOT_ALLOWED,
+            "Event streams require GET".to_string(),
+        ));
+    }
+    let (path, options) = split_api_version(req.uri().path());
+    let path: Vec<&str> = path.split('/').skip(1).collect();
+    let addresses = match path.as_slice() {
+        ["stream", "blocks"] => None,
+        ["stream", "address", addresses] => {
+            let addresses = addresses
+                .split(',')
+                .map(|address| {
+                    let scripthash = to_scripthash("address", address, config.network_type)?;
+                    Ok((scripthash, address.to_string()))
+                })
+                .collect::<Result<HashMap<FullHash, String>, HttpError>>()?;
+            if addresses.len() > config.rest_sse_max_addresses {
+                bail!(HttpError::from(format!(
+                    "Too many addresses, the limit is {}",
+                    config.rest_sse_max_addresses
+                )));
+            }
+            Some(addresses)
+        }
+        _ => {
+            return Err(HttpError::not_found(format!(
+                "endpoint does not exist {:?}",
+                req.uri().path()
+            )))
+        }
+    };
+    let permit = Arc::clone(slots).try_acquire_owned().map_err(|_| {
+        HttpError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Too many event streams open, try again later".to_string(),
+        )
+        .with_code("busy")
+    })?;
+
+    // Subscribe before responding so that no events are missed in between
+    let (sender, body) = Body::channel();
+    match addresses {
+        None => {
+            let updates = query.subscribe_updates();
+            let query = Arc::clone(query);
+            tokio::spawn(stream_blocks(sender, updates, query, shutdown, permit));
+        }
+        Some(addresses) => {
+            let events = query.mempool().subscribe_scripthash_events();
+            tokio::spawn(stream_address_events(
+                sender, events, addresses, options, shutdown, permit,
+            ));
+        }
+    }
+
+    Ok(Response::builder()
+        .header(header::CONTENT_TYPE, "text/event-stream")
+        .header(header::CACHE_CONTROL, "no-cache")
+        .header("X-Powered-By", &**VERSION_STRING)
+        .body(body)
+        .unwrap())
+}
+
+async fn stream_blocks(
+    mut sender: hyper::body::Sender,
+    mut updates: watch::Receiver<u64>,
+    query: Arc<Query>,
+    mut shutdown: watch::Receiver<()>,
+    _permit: OwnedSemaphorePermit,
+) {
+    let mut tip = query.chain().best_hash();
+    let mut keepalive = tokio::time::interval(SSE_KEEPALIVE_INTERVAL);
+    loop {
+        let event = tokio::select! {
+            _ = shutdown.changed() => break,
+            _ = keepalive.tick() => None,
+            changed = updates.changed() => {
+                if changed.is_err() {
+                    break;
+                }
+                // updates are also sent for mempool changes
+                let best_hash = query.chain().best_hash();
+                if best_hash == tip {
+                    continue;
+                }
+                tip = best_hash;
+                let query = Arc::clone(&query);
+                match lookup_blocking(move || query.chain().get_block_with_meta(&tip)).await {
+                    Some(blockhm) => Some(("block", json!(BlockValue::new(blockhm)))),
+                    None => continue,
+                }
+            }
+        };
+        if !send_event(&mut sender, event).await {
+            break;
+        }
+    }
+}
+
+async fn stream_address_events(
+    mut sender: hyper::body::Sender,
+    mut events: broadcast::Receiver<ScripthashEvent>,
+    addresses: HashMap<FullHash, String>,
+    options: ResponseOptions,
+    mut shutdown: watch::Receiver<()>,
+    _permit: OwnedSemaphorePermit,
+) {
+    let mut keepalive = tokio::time::interval(SSE_KEEPALIVE_INTERVAL);
+    loop {
+        let event = tokio::select! {
+            _ = shutdown.changed() => break,
+            _ = keepalive.tick() => None,
+            event = events.recv() => match event {
+                Ok(event) => match addresses.get(&event.scripthash) {
+                    Some(address) => Some(("tx", json!({
+                        "address": address,
+                        "txid": event.txid,
+                        "confirmed": event.confirmed,
+                    }))),
+                    None => continue,
+                },
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    let err = HttpError::from(format!(
+                        "Stream lagging behind, {} events dropped",
+                        skipped
+                    ));
+                    Some(("error", error_value(err.with_code("lagging"), options)))
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+        };
+        if !send_event(&mut sender, event).await {
+            break;
+        }
+    }
+}
+
+// Send an event, or a keep-alive comment without one. Fails once the client went away.
+async fn send_event(
+    sender: &mut hyper::body::Sender,
+    event: Option<(&str, serde_json::Value)>,
+) -> bool {
+    let chunk = match event {
+        Some((name, data)) => format!("event: {}\ndata: {}\n\n", name, data),
+        None => ":\n\n".to_string(),
+    };
+    sender.send_data(chunk.into()).await.is_ok()
+}
+
+#[derive(Deserialize)]
+struct WsRequest {
+    subscribe: Option<String>,
+    unsubscribe: Option<String>,
+    hash: Option<String>,
+}
+
+// The topics a websocket connection is subscribed to
+#[derive(Default)]
+struct WsSubscriptions {
+    blocks: bool,
+    mempool_recent: bool,
+    scripthashes: HashSet<FullHash>,
+    // scripthash => the address as subscribed to, echoed back in the pushed frames
+    addresses: HashMap<FullHash, String>,
+}
+
+impl WsSubscriptions {
+    fn len(&self) -> usize {
+        usize::from(self.blocks)
+            + usize::from(self.mempool_recent)
+            + self.scripthashes.len()
+            + self.addresses.len()
+    }
+
+    fn check_limit(&self) -> Result<(), HttpError> {
+        if self.len() >= WS_SUBSCRIPTIONS_LIMIT {
+            bail!(HttpError::from(format!(
+                "Too many subscriptions, the limit is {}",
+                WS_SUBSCRIPTIONS_LIMIT
+            )));
+        }
+        Ok(())
+    }
+}
+
+// Handle a `{"subscribe": <topic>}` or `{"unsubscribe": <topic>}` request. The topics are
+// `blocks`, `mempool-recent`, `address:<address>`, and `scripthash` along with a `hash`.
+fn handle_ws_request(
+    text: &str,
+    subscriptions: &mut WsSubscriptions,
+    network: Network,
+    options: ResponseOptions,
+) -> serde_json::Value {
+    let req: WsRequest = match serde_json::from_str(text) {
+        Ok(req) => req,
+        Err(e) => {
+            let err = HttpError::from(format!("Invalid request: {}", e));
+            return error_value(err, options);
+        }
+    };
+    let (topic, subscribe) = match (req.subscribe, req.unsubscribe) {
+        (Some(topic), None) => (topic, true),
+        (None, Some(topic)) => (topic, false),
+        _ => {
+            let err = HttpError::from(
+                "Expected either a subscribe or an unsubscribe request".to_string(),
+            );
+            return error_value(err, options);
+        }
+    };
+    let result = match topic.as_str() {
+        "blocks" | "mempool-recent" => {
+            let limit = subscriptions.check_limit();
+            let flag = if topic == "blocks" {
+                &mut subscriptions.blocks
+            } else {
+                &mut subscriptions.mempool_recent
+            };
+            let limit = if subscribe && !*flag { limit } else { Ok(()) };
+            limit.map(|()| {
+                *flag = subscribe;
+                topic.clone()
+            })
+        }
+        "scripthash" => req
+            .hash
+            .ok_or_else(|| HttpError::from("Missing the scripthash to subscribe to".to_string()))
+            .and_then(|hash| {
+                let scripthash = parse_scripthash(&hash)?;
+                if !subscribe {
+                    subscriptions.scripthashes.remove(&scripthash);
+                } else if !subscriptions.scripthashes.contains(&scripthash) {
+                    subscriptions.check_limit()?;
+                    subscriptions.scripthashes.insert(scripthash);
+                }
+                Ok(hash)
+            }),
+        _ => match topic.strip_prefix("address:") {
+            Some(address) => to_scripthash("address", address, network).and_then(|scripthash| {
+                if !subscribe {
+                    subscriptions.addresses.remove(&scripthash);
+                } else {
+                    let within_limit = subscriptions.check_limit();
+                    if let Entry::Vacant(entry) = subscriptions.addresses.entry(scripthash) {
+                        within_limit?;
+                        entry.insert(address.to_string());
+                    }
+                }
+                Ok(topic.clone())
+            }),
+            None => Err(HttpError::from(format!("Unknown topic '{}'", topic))),
+        },
+    };
+    match result {
+        Ok(topic) if subscribe => json!({ "subscribed": topic }),
+        Ok(topic) => json!({ "unsubscribed": topic }),
+        Err(e) => error_value(e, options),
+    }
+}
+
+pub fn start(config: Arc<Config>, query: Arc<Query>, metrics: &Metrics) -> Handle {
     let (tx, rx) = oneshot::channel::<()>();
-    let response_timer = metrics.histogram_vec(
-        HistogramOpts::new("electrs_rest_api", "Electrs REST API response timings"),
-        &["method"],
-    );
+    // loaded upfront so that a bad certificate or key fails the startup
+    let tls_config = match (&config.http_tls_cert, &config.http_tls_key) {
+        (Some(cert), Some(key)) => Some(Arc::new(load_tls_config(cert, key, config.http2_enabled))),
+        _ => None,
+    };
+    let rest_metrics = RestMetrics {
+        response_timer: metrics.histogram_vec(
+            HistogramOpts::new("electrs_rest_api", "Electrs REST API response timings"),
+            &["method", "route", "status"],
+        ),
+        oversized_bodies: metrics.counter(MetricOpts::new(
+            "electrs_rest_oversized_bodies",
+            "# of REST requests rejected for exceeding the body size limit",
+        )),
+        compression_saved: metrics.counter(MetricOpts::new(
+            "electrs_rest_compression_saved_bytes",
+            "# of bytes saved by compressing REST responses",
+        )),
+        throttled: metrics.counter_vec(
+            MetricOpts::new(
+                "electrs_rest_throttled_requests",
+                "# of REST requests rejected by the rate limiter",
+            ),
+            &["route"],
+        ),
+        heavy_queue_depth: metrics.gauge(MetricOpts::new(
+            "electrs_rest_heavy_queue_depth",
+            "# of expensive REST requests waiting for a free slot",
+        )),
+        timeouts: metrics.counter_vec(
+            MetricOpts::new(
+                "electrs_rest_timeouts",
+                "# of REST requests that timed out, waiting in the queue or being handled",
+            ),
+            &["stage"],
+        ),
+        cancelled: metrics.counter(MetricOpts::new(
+            "electrs_rest_cancelled_requests",
+            "# of REST requests abandoned by their client before completing",
+        )),
+        ws_connections: metrics.gauge(MetricOpts::new(
+            "electrs_rest_ws_connections",
+            "# of open websocket connections",
+        )),
+        ws_subscriptions: metrics.gauge(MetricOpts::new(
+            "electrs_rest_ws_subscriptions",
+            "# of topics subscribed to over all open websocket connections",
+        )),
+    };
+    for addr in &config.http_addrs {
+        if config.internal_api_access.is_world_reachable(addr) {
+            warn!(
+                "internal REST routes are reachable by any client on {}, consider restricting them with --internal-api-access",
+                addr
+            );
+        }
+    }
 
     Handle {
         tx,
         thread: crate::util::spawn_thread("rest-server", move || {
-            run_server(config, query, rx, response_timer);
+            run_server(config, query, rx, rest_metrics, tls_config);
         }),
     }
 }
@@ -707,23 +2849,32 @@ impl Handle {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn handle_request(
     method: Method,
     uri: hyper::Uri,
+    headers: &HeaderMap,
     body: hyper::body::Bytes,
+    peer: Peer,
+    cancel: &CancelToken,
     query: &Query,
     config: &Config,
 ) -> Result<Response<Body>, HttpError> {
+    if method == Method::HEAD {
+        return handle_request(Method::GET, uri, headers, body, peer, cancel, query, config)
+            .map(head_response);
+    }
+
     // TODO it looks hyper does not have routing and query parsing :(
-    let path: Vec<&str> = uri.path().split('/').skip(1).collect();
-    let query_params = match uri.query() {
-        Some(value) => form_urlencoded::parse(value.as_bytes())
-            .into_owned()
-            .collect::<HashMap<String, String>>(),
-        None => HashMap::new(),
-    };
+    let (path, options) = split_api_version(uri.path());
+    let path: Vec<&str> = path.split('/').skip(1).collect();
+    let query_params = parse_query_params(&uri);
+
+    debug!("handle {:?} {:?} from {}", method, uri, peer);
+    if let Some(resp) = gate_request(&method, &path, &query_params, peer, query, config)? {
+        return Ok(resp);
+    }
 
-    info!("handle {:?} {:?}", method, uri);
     match (
         &method,
         path.first(),
@@ -732,21 +2883,92 @@ fn handle_request(
         path.get(3),
         path.get(4),
     ) {
-        (&Method::GET, Some(&"blocks"), Some(&"tip"), Some(&"hash"), None, None) => http_message(
-            StatusCode::OK,
-            query.chain().best_hash().to_hex(),
+        // liveness probes
+        (&Method::GET, Some(&"ping"), None, None, None, None) => {
+            http_message(StatusCode::OK, "pong", 0)
+        }
+        (&Method::GET, Some(&"health"), None, None, None, None) => {
+            http_message(StatusCode::OK, "ok", 0)
+        }
+        // lets clients adapt to this instance rather than hardcoding its limits
+        (&Method::GET, Some(&"info"), None, None, None, None) => json_response(
+            json!({
+                "network": config.network_type.name(),
+                "version": &**VERSION_STRING,
+                "features": enabled_features(config),
+                "limits": {
+                    "multi_address_limit": MULTI_ADDRESS_LIMIT,
+                    "address_search_limit": ADDRESS_SEARCH_LIMIT,
+                    "default_txs_per_page": config.rest_default_chain_txs_per_page,
+                    "max_txs_per_page": config.rest_max_chain_txs_per_page,
+                    "max_mempool_txs": config.rest_default_max_mempool_txs,
+                    "max_mempool_page_size": config.rest_max_mempool_page_size,
+                    "max_utxos": cmp::min(config.utxos_limit, config.max_utxos_per_query),
+                    "default_outspends_limit": config.rest_default_outspends_limit,
+                    "max_outspends_limit": config.rest_max_outspends_limit,
+                    "outspends_outpoints_limit": OUTSPENDS_OUTPOINTS_LIMIT,
+                    "tx_fees_limit": TX_FEES_LIMIT,
+                    "batch_limit": BATCH_LIMIT,
+                    "ws_subscriptions_limit": WS_SUBSCRIPTIONS_LIMIT,
+                    "tx_status_max_wait": TX_STATUS_MAX_WAIT,
+                    "max_ancestry_depth": config.rest_max_ancestry_depth,
+                    "max_body_size": config.rest_max_body_size,
+                    "max_tx_body_size": config.rest_max_tx_body_size,
+                },
+            }),
             TTL_SHORT,
+            &query_params,
         ),
+        // readiness probe, ready once the index caught up with the daemon's tip (give or take
+        // --rest-ready-max-lag blocks) and the daemon is reachable
+        (&Method::GET, Some(&"ready"), None, None, None, None) => {
+            let indexed_height = query.chain().best_height();
+            let daemon_height = query.daemon_height().ok();
+            let mempool_loaded = query.mempool().is_loaded();
+            let behind_by = daemon_height.map(|height| height.saturating_sub(indexed_height));
+            let ready = mempool_loaded
+                && behind_by.map_or(false, |behind_by| behind_by <= config.rest_ready_max_lag);
+            let status = if ready {
+                StatusCode::OK
+            } else {
+                StatusCode::SERVICE_UNAVAILABLE
+            };
+            let mut resp = json_response(
+                json!({
+                    "ready": ready,
+                    "indexed_height": indexed_height,
+                    "daemon_height": daemon_height,
+                    "mempool_loaded": mempool_loaded,
+                    "behind_by": behind_by,
+                }),
+                0,
+                &query_params,
+            )?;
+            *resp.status_mut() = status;
+            Ok(resp)
+        }
 
-        (&Method::GET, Some(&"blocks"), Some(&"tip"), Some(&"height"), None, None) => http_message(
-            StatusCode::OK,
-            query.chain().best_height().to_string(),
-            TTL_SHORT,
-        ),
+        (&Method::GET, Some(&"blocks"), Some(&"tip"), Some(&"hash"), None, None) => {
+            let tip = query.chain().best_hash();
+            http_message(StatusCode::OK, tip.to_hex(), TTL_SHORT).map(|resp| with_etag(resp, tip))
+        }
+
+        (&Method::GET, Some(&"blocks"), Some(&"tip"), Some(&"height"), None, None) => {
+            let tip = query.chain().best_header();
+            http_message(StatusCode::OK, tip.height().to_string(), TTL_SHORT)
+                .map(|resp| with_etag(resp, tip.hash()))
+        }
+
+        (&Method::GET, Some(&"blocks"), Some(&"tip"), Some(&"header"), None, None) => {
+            let tip = query.chain().best_header();
+            let header_hex = hex::encode(encode::serialize(tip.header()));
+            http_message(StatusCode::OK, header_hex, TTL_SHORT)
+                .map(|resp| with_etag(resp, tip.hash()))
+        }
 
         (&Method::GET, Some(&"blocks"), start_height, None, None, None) => {
             let start_height = start_height.and_then(|height| height.parse::<usize>().ok());
-            blocks(query, config, start_height)
+            blocks(query, config, start_height, &query_params)
         }
         (&Method::GET, Some(&"block-height"), Some(height), None, None, None) => {
             let height = height.parse::<usize>()?;
@@ -757,6 +2979,20 @@ fn handle_request(
             let ttl = ttl_by_depth(Some(height), query);
             http_message(StatusCode::OK, header.hash().to_hex(), ttl)
         }
+        (&Method::GET, Some(&"block"), Some(&"height"), Some(height), None, None) => {
+            let height = height.parse::<usize>()?;
+            let hash = *query
+                .chain()
+                .header_by_height(height)
+                .ok_or_else(|| HttpError::not_found("Block not found".to_string()))?
+                .hash();
+            let blockhm = query
+                .chain()
+                .get_block_with_meta(&hash)
+                .ok_or_else(|| HttpError::not_found("Block not found".to_string()))?;
+            let ttl = ttl_by_depth(Some(height), query);
+            json_response(BlockValue::new(blockhm), ttl, &query_params)
+        }
         (&Method::GET, Some(&"block"), Some(hash), None, None, None) => {
             let hash = BlockHash::from_hex(hash)?;
             let blockhm = query
@@ -764,13 +3000,80 @@ fn handle_request(
                 .get_block_with_meta(&hash)
                 .ok_or_else(|| HttpError::not_found("Block not found".to_string()))?;
             let block_value = BlockValue::new(blockhm);
-            json_response(block_value, TTL_LONG)
+            json_response(block_value, TTL_LONG, &query_params).map(|resp| with_etag(resp, hash))
         }
         (&Method::GET, Some(&"block"), Some(hash), Some(&"status"), None, None) => {
             let hash = BlockHash::from_hex(hash)?;
             let status = query.chain().get_block_status(&hash);
             let ttl = ttl_by_depth(status.height, query);
-            json_response(status, ttl)
+            json_response(status, ttl, &query_params)
+        }
+        (&Method::GET, Some(&"block"), Some(hash), Some(&"stats"), None, None) => {
+            let hash = BlockHash::from_hex(hash)?;
+            let stats = query
+                .block_stats(&hash)
+                .map_err(missing_prevouts_error)?
+                .ok_or_else(|| HttpError::not_found("Block not found".to_string()))?;
+            json_response(stats, TTL_LONG, &query_params)
+        }
+        (&Method::GET, Some(&"block"), Some(hash), Some(&"audit-summary"), None, None) => {
+            if !config.rest_block_audit {
+                return Err(HttpError::not_found(
+                    "The audit-summary endpoint is disabled".to_string(),
+                ));
+            }
+            let hash = BlockHash::from_hex(hash)?;
+            let chain = query.chain();
+            let meta = chain
+                .get_block_meta(&hash)
+                .ok_or_else(|| HttpError::not_found("Block not found".to_string()))?;
+            let header = chain
+                .get_block_header(&hash)
+                .ok_or_else(|| HttpError::not_found("Block not found".to_string()))?;
+            let txdata = chain
+                .get_block_txs(&hash)
+                .ok_or_else(|| HttpError::not_found("Block transactions not found".to_string()))?;
+            let block = Block { header, txdata };
+
+            let stored = json!({
+                "tx_count": meta.tx_count,
+                "size": meta.size,
+                "weight": meta.weight,
+            });
+            let computed = json!({
+                "tx_count": block.txdata.len() as u32,
+                "size": encode::serialize(&block).len() as u32,
+                "weight": block.weight() as u32,
+            });
+            let mismatches: Vec<&str> = ["tx_count", "size", "weight"]
+                .iter()
+                .filter(|field| stored[**field] != computed[**field])
+                .copied()
+                .collect();
+
+            json_response(
+                json!({
+                    "ok": mismatches.is_empty(),
+                    "mismatches": mismatches,
+                    "stored": stored,
+                    "computed": computed,
+                }),
+                0,
+                &query_params,
+            )
+        }
+        (&Method::GET, Some(&"block"), Some(hash), Some(&"tx-fees"), None, None) => {
+            if !config.rest_block_tx_fees {
+                return Err(HttpError::not_found(
+                    "The tx-fees endpoint is disabled".to_string(),
+                ));
+            }
+            let hash = BlockHash::from_hex(hash)?;
+            let fees = query
+                .block_tx_fees(&hash)
+                .map_err(missing_prevouts_error)?
+                .ok_or_else(|| HttpError::not_found("Block not found".to_string()))?;
+            json_response(&*fees, TTL_LONG, &query_params)
         }
         (&Method::GET, Some(&"block"), Some(hash), Some(&"txids"), None, None) => {
             let hash = BlockHash::from_hex(hash)?;
@@ -778,7 +3081,22 @@ fn handle_request(
                 .chain()
                 .get_block_txids(&hash)
                 .ok_or_else(|| HttpError::not_found("Block not found".to_string()))?;
-            json_response(txids, TTL_LONG)
+            json_response(txids, TTL_LONG, &query_params)
+        }
+        (&Method::GET, Some(&"block"), Some(hash), Some(&"output-types"), None, None) => {
+            let hash = BlockHash::from_hex(hash)?;
+            let txs = query
+                .chain()
+                .get_block_txs(&hash)
+                .ok_or_else(|| HttpError::not_found("Block not found".to_string()))?;
+
+            let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+            for txout in txs.iter().flat_map(|tx| tx.output.iter()) {
+                *counts.entry(output_type(txout)).or_default() += 1;
+            }
+
+            let ttl = ttl_by_depth(query.chain().height_by_hash(&hash), query);
+            json_response(counts, ttl, &query_params)
         }
         (&Method::GET, Some(&INTERNAL_PREFIX), Some(&"block"), Some(hash), Some(&"txs"), None) => {
             let hash = BlockHash::from_hex(hash)?;
@@ -792,7 +3110,7 @@ fn handle_request(
                 .collect();
 
             let ttl = ttl_by_depth(block_id.map(|b| b.height), query);
-            json_response(prepare_txs(txs, query, config), ttl)
+            json_response(prepare_txs(txs, query, config, options), ttl, &query_params)
         }
         (&Method::GET, Some(&"block"), Some(hash), Some(&"header"), None, None) => {
             let hash = BlockHash::from_hex(hash)?;
@@ -816,6 +3134,7 @@ fn handle_request(
                 .header("Content-Type", "application/octet-stream")
                 .header("Cache-Control", format!("public, max-age={:}", TTL_LONG))
                 .header("X-Powered-By", &**VERSION_STRING)
+                .header("ETag", etag_value(hash))
                 .body(Body::from(raw))
                 .unwrap())
         }
@@ -869,7 +3188,7 @@ fn handle_request(
             // XXX orphraned blocks alway get TTL_SHORT
             let ttl = ttl_by_depth(confirmed_blockid.map(|b| b.height), query);
 
-            json_response(prepare_txs(txs, query, config), ttl)
+            json_response(prepare_txs(txs, query, config, options), ttl, &query_params)
         }
         (&Method::GET, Some(script_type @ &"address"), Some(script_str), None, None, None)
         | (&Method::GET, Some(script_type @ &"scripthash"), Some(script_str), None, None, None) => {
@@ -882,6 +3201,7 @@ fn handle_request(
                     "mempool_stats": stats.1,
                 }),
                 TTL_SHORT,
+                &query_params,
             )
         }
         (
@@ -901,6 +3221,9 @@ fn handle_request(
             None,
         ) => {
             let script_hash = to_scripthash(script_type, script_str, config.network_type)?;
+            if is_ascending_order(&query_params)? {
+                bail!(HttpError::from(MEMPOOL_ORDER_ASC_ERROR.to_string()));
+            }
             let max_txs = query_params
                 .get("max_txs")
                 .and_then(|s| s.parse::<usize>().ok())
@@ -929,10 +3252,11 @@ fn handle_request(
                     None
                 }
                 TxidLocation::None => {
-                    return Err(HttpError(
+                    return Err(HttpError::new(
                         StatusCode::UNPROCESSABLE_ENTITY,
                         String::from("after_txid not found"),
-                    ));
+                    )
+                    .with_code("after_txid_not_found"));
                 }
                 TxidLocation::Chain(height) => Some(height),
             };
@@ -953,13 +3277,19 @@ fn handle_request(
                             after_txid_ref,
                             confirmed_block_height,
                             max_txs - txs.len(),
+                            cancel,
                         )
                         .map(|res| res.map(|(tx, blockid)| (tx, Some(blockid))))
                         .collect::<Result<Vec<_>, _>>()?,
                 );
+                check_cancelled(cancel)?;
             }
 
-            json_response(prepare_txs(txs, query, config), TTL_SHORT)
+            json_response(
+                prepare_txs(txs, query, config, options),
+                TTL_SHORT,
+                &query_params,
+            )
         }
 
         (&Method::POST, Some(script_types @ &"addresses"), Some(&"txs"), None, None, None)
@@ -971,20 +3301,22 @@ fn handle_request(
             };
 
             if multi_address_too_long(&body) {
-                return Err(HttpError(
+                return Err(HttpError::new(
                     StatusCode::UNPROCESSABLE_ENTITY,
                     String::from("body too long"),
-                ));
+                )
+                .with_code("body_too_long"));
             }
 
             let script_hashes: Vec<String> =
                 serde_json::from_slice(&body).map_err(|err| HttpError::from(err.to_string()))?;
 
             if script_hashes.len() > MULTI_ADDRESS_LIMIT {
-                return Err(HttpError(
+                return Err(HttpError::new(
                     StatusCode::UNPROCESSABLE_ENTITY,
                     String::from("body too long"),
-                ));
+                )
+                .with_code("body_too_long"));
             }
 
             let script_hashes: Vec<[u8; 32]> = script_hashes
@@ -994,6 +3326,9 @@ fn handle_request(
                 })
                 .collect();
 
+            if is_ascending_order(&query_params)? {
+                bail!(HttpError::from(MEMPOOL_ORDER_ASC_ERROR.to_string()));
+            }
             let max_txs = query_params
                 .get("max_txs")
                 .and_then(|s| s.parse::<usize>().ok())
@@ -1022,10 +3357,11 @@ fn handle_request(
                     None
                 }
                 TxidLocation::None => {
-                    return Err(HttpError(
+                    return Err(HttpError::new(
                         StatusCode::UNPROCESSABLE_ENTITY,
                         String::from("after_txid not found"),
-                    ));
+                    )
+                    .with_code("after_txid_not_found"));
                 }
                 TxidLocation::Chain(height) => Some(height),
             };
@@ -1046,15 +3382,63 @@ fn handle_request(
                             after_txid_ref,
                             confirmed_block_height,
                             max_txs - txs.len(),
+                            cancel,
                         )
                         .map(|res| res.map(|(tx, blockid)| (tx, Some(blockid))))
                         .collect::<Result<Vec<_>, _>>()?,
                 );
+                check_cancelled(cancel)?;
             }
 
-            json_response(prepare_txs(txs, query, config), TTL_SHORT)
+            json_response(
+                prepare_txs(txs, query, config, options),
+                TTL_SHORT,
+                &query_params,
+            )
         }
 
+        (
+            &Method::GET,
+            Some(script_type @ &"address"),
+            Some(script_str),
+            Some(&"txs"),
+            Some(&"last"),
+            None,
+        )
+        | (
+            &Method::GET,
+            Some(script_type @ &"scripthash"),
+            Some(script_str),
+            Some(&"txs"),
+            Some(&"last"),
+            None,
+        ) => {
+            let script_hash = to_scripthash(script_type, script_str, config.network_type)?;
+
+            // mempool history is kept in insertion order, so the most recent entry is the last one
+            let mempool_tx = {
+                let mempool = query.mempool();
+                mempool
+                    .history_txids_iter(&script_hash[..])
+                    .last()
+                    .and_then(|txid| mempool.lookup_txn(&txid))
+            };
+
+            let txs = match mempool_tx {
+                Some(tx) => vec![(tx, None)],
+                None => query
+                    .chain()
+                    .history(&script_hash[..], None, None, 1, cancel)
+                    .map(|res| res.map(|(tx, blockid)| (tx, Some(blockid))))
+                    .collect::<Result<Vec<_>, _>>()?,
+            };
+
+            json_response(
+                prepare_txs(txs, query, config, options).into_iter().next(),
+                TTL_SHORT,
+                &query_params,
+            )
+        }
         (
             &Method::GET,
             Some(script_type @ &"address"),
@@ -1073,18 +3457,70 @@ fn handle_request(
         ) => {
             let script_hash = to_scripthash(script_type, script_str, config.network_type)?;
             let last_seen_txid = last_seen_txid.and_then(|txid| Txid::from_hex(txid).ok());
-            let max_txs = query_params
-                .get("max_txs")
-                .and_then(|s| s.parse::<usize>().ok())
-                .unwrap_or(config.rest_default_chain_txs_per_page);
-
-            let txs = query
-                .chain()
-                .history(&script_hash[..], last_seen_txid.as_ref(), None, max_txs)
-                .map(|res| res.map(|(tx, blockid)| (tx, Some(blockid))))
-                .collect::<Result<Vec<_>, _>>()?;
+            let max_txs = cmp::min(
+                config.rest_max_chain_txs_per_page,
+                query_params
+                    .get("max_txs")
+                    .and_then(|s| s.parse::<usize>().ok())
+                    .unwrap_or(config.rest_default_chain_txs_per_page),
+            );
 
-            json_response(prepare_txs(txs, query, config), TTL_SHORT)
+            let txs = if is_ascending_order(&query_params)? {
+                // start scanning at the height of the last seen tx, the cursor is found from there
+                let start_height = match &last_seen_txid {
+                    Some(txid) => query
+                        .chain()
+                        .tx_confirming_block(txid)
+                        .map(|b| b.height)
+                        .ok_or_else(|| {
+                            HttpError::new(
+                                StatusCode::UNPROCESSABLE_ENTITY,
+                                String::from("last_seen_txid not found"),
+                            )
+                        })?,
+                    None => 0,
+                };
+                query
+                    .chain()
+                    .history_asc(
+                        &script_hash[..],
+                        last_seen_txid.as_ref(),
+                        start_height,
+                        max_txs,
+                        cancel,
+                    )
+                    .map(|res| res.map(|(tx, blockid)| (tx, Some(blockid))))
+                    .collect::<Result<Vec<_>, _>>()?
+            } else {
+                query
+                    .chain()
+                    .history(
+                        &script_hash[..],
+                        last_seen_txid.as_ref(),
+                        None,
+                        max_txs,
+                        cancel,
+                    )
+                    .map(|res| res.map(|(tx, blockid)| (tx, Some(blockid))))
+                    .collect::<Result<Vec<_>, _>>()?
+            };
+            check_cancelled(cancel)?;
+
+            if query_params.get("envelope").map_or(false, |v| v == "true") {
+                // a full page means there may be more, at the cost of an empty last page
+                let has_more = txs.len() == max_txs;
+                json_response(
+                    json!({ "txs": prepare_txs(txs, query, config, options), "has_more": has_more }),
+                    TTL_SHORT,
+                    &query_params,
+                )
+            } else {
+                json_response(
+                    prepare_txs(txs, query, config, options),
+                    TTL_SHORT,
+                    &query_params,
+                )
+            }
         }
         (
             &Method::GET,
@@ -1121,22 +3557,38 @@ fn handle_request(
             let confirmed_block_height = match last_seen_txid_location {
                 TxidLocation::Mempool => None,
                 TxidLocation::None => {
-                    return Err(HttpError(
+                    return Err(HttpError::new(
                         StatusCode::UNPROCESSABLE_ENTITY,
                         String::from("after_txid not found"),
-                    ));
+                    )
+                    .with_code("after_txid_not_found"));
                 }
                 TxidLocation::Chain(height) => Some(height),
             };
 
-            let summary = query.chain().summary(
-                &script_hash[..],
-                last_seen_txid.as_ref(),
-                confirmed_block_height,
-                max_txs,
-            );
+            let summary = if is_ascending_order(&query_params)? {
+                if last_seen_txid.is_some() && confirmed_block_height.is_none() {
+                    bail!(HttpError::from(MEMPOOL_ORDER_ASC_ERROR.to_string()));
+                }
+                query.chain().summary_asc(
+                    &script_hash[..],
+                    last_seen_txid.as_ref(),
+                    confirmed_block_height.unwrap_or(0) as usize,
+                    max_txs,
+                    cancel,
+                )
+            } else {
+                query.chain().summary(
+                    &script_hash[..],
+                    last_seen_txid.as_ref(),
+                    confirmed_block_height,
+                    max_txs,
+                    cancel,
+                )
+            };
+            check_cancelled(cancel)?;
 
-            json_response(summary, TTL_SHORT)
+            json_response(summary, TTL_SHORT, &query_params)
         }
         (
             &Method::POST,
@@ -1161,20 +3613,22 @@ fn handle_request(
             };
 
             if multi_address_too_long(&body) {
-                return Err(HttpError(
+                return Err(HttpError::new(
                     StatusCode::UNPROCESSABLE_ENTITY,
                     String::from("body too long"),
-                ));
+                )
+                .with_code("body_too_long"));
             }
 
             let script_hashes: Vec<String> =
                 serde_json::from_slice(&body).map_err(|err| HttpError::from(err.to_string()))?;
 
             if script_hashes.len() > MULTI_ADDRESS_LIMIT {
-                return Err(HttpError(
+                return Err(HttpError::new(
                     StatusCode::UNPROCESSABLE_ENTITY,
                     String::from("body too long"),
-                ));
+                )
+                .with_code("body_too_long"));
             }
 
             let script_hashes: Vec<[u8; 32]> = script_hashes
@@ -1202,10 +3656,11 @@ fn handle_request(
             let confirmed_block_height = match last_seen_txid_location {
                 TxidLocation::Mempool => None,
                 TxidLocation::None => {
-                    return Err(HttpError(
+                    return Err(HttpError::new(
                         StatusCode::UNPROCESSABLE_ENTITY,
                         String::from("after_txid not found"),
-                    ));
+                    )
+                    .with_code("after_txid_not_found"));
                 }
                 TxidLocation::Chain(height) => Some(height),
             };
@@ -1215,9 +3670,11 @@ fn handle_request(
                 last_seen_txid.as_ref(),
                 confirmed_block_height,
                 max_txs,
+                cancel,
             );
+            check_cancelled(cancel)?;
 
-            json_response(summary, TTL_SHORT)
+            json_response(summary, TTL_SHORT, &query_params)
         }
         (
             &Method::GET,
@@ -1248,7 +3705,11 @@ fn handle_request(
                 .map(|tx| (tx, None))
                 .collect();
 
-            json_response(prepare_txs(txs, query, config), TTL_SHORT)
+            json_response(
+                prepare_txs(txs, query, config, options),
+                TTL_SHORT,
+                &query_params,
+            )
         }
 
         (
@@ -1268,20 +3729,161 @@ fn handle_request(
             None,
         ) => {
             let script_hash = to_scripthash(script_type, script_str, config.network_type)?;
-            let utxos: Vec<UtxoValue> = query
-                .utxo(&script_hash[..])?
+            let utxos = query.utxo(&script_hash[..], cancel)?;
+
+            if query_params.get("format").map(String::as_str) == Some("bin") {
+                #[cfg(not(feature = "liquid"))]
+                return Ok(Response::builder()
+                    .status(StatusCode::OK)
+                    .header("Content-Type", "application/octet-stream")
+                    .header("Cache-Control", format!("public, max-age={:}", TTL_SHORT))
+                    .header("X-Powered-By", &**VERSION_STRING)
+                    .body(Body::from(utxos_binary(&utxos)))
+                    .unwrap());
+                #[cfg(feature = "liquid")]
+                bail!(HttpError::from(
+                    "format=bin is not supported for confidential utxos".to_string()
+                ));
+            }
+
+            // XXX paging?
+            json_response(utxo_values(utxos, query, options), TTL_SHORT, &query_params)
+        }
+        (
+            &Method::GET,
+            Some(script_type @ &"address"),
+            Some(script_str),
+            Some(&"stats"),
+            Some(&"scripttypes"),
+            None,
+        )
+        | (
+            &Method::GET,
+            Some(script_type @ &"scripthash"),
+            Some(script_str),
+            Some(&"stats"),
+            Some(&"scripttypes"),
+            None,
+        ) => {
+            let script_hash = to_scripthash(script_type, script_str, config.network_type)?;
+            let stats = query.script_type_stats(
+                &script_hash[..],
+                config.rest_default_max_address_summary_txs,
+            );
+            json_response(stats, TTL_SHORT, &query_params)
+        }
+        (
+            &Method::GET,
+            Some(script_type @ &"address"),
+            Some(script_str),
+            Some(&"outputs"),
+            Some(&"spent-bitmap"),
+            None,
+        )
+        | (
+            &Method::GET,
+            Some(script_type @ &"scripthash"),
+            Some(script_str),
+            Some(&"outputs"),
+            Some(&"spent-bitmap"),
+            None,
+        ) => {
+            let script_hash = to_scripthash(script_type, script_str, config.network_type)?;
+            let outputs: Vec<OutputSpentValue> = query
+                .funded_outpoints(&script_hash[..], FUNDED_OUTPUTS_LIMIT)?
                 .into_iter()
-                .map(UtxoValue::from)
+                .map(OutputSpentValue::from)
+                .collect();
+            json_response(outputs, TTL_SHORT, &query_params)
+        }
+        (
+            &Method::POST,
+            Some(script_types @ &"addresses"),
+            Some(&"utxo"),
+            Some(&"sum"),
+            None,
+            None,
+        )
+        | (
+            &Method::POST,
+            Some(script_types @ &"scripthashes"),
+            Some(&"utxo"),
+            Some(&"sum"),
+            None,
+            None,
+        ) => {
+            let script_type = match *script_types {
+                "addresses" => "address",
+                "scripthashes" => "scripthash",
+                _ => "",
+            };
+
+            if multi_address_too_long(&body) {
+                return Err(HttpError::new(
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    String::from("body too long"),
+                )
+                .with_code("body_too_long"));
+            }
+
+            let script_hashes: Vec<String> =
+                serde_json::from_slice(&body).map_err(|err| HttpError::from(err.to_string()))?;
+
+            if script_hashes.len() > MULTI_ADDRESS_LIMIT {
+                return Err(HttpError::new(
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    String::from("body too long"),
+                )
+                .with_code("body_too_long"));
+            }
+
+            let script_hashes: Vec<[u8; 32]> = script_hashes
+                .iter()
+                .filter_map(|script_str| {
+                    to_scripthash(script_type, script_str, config.network_type).ok()
+                })
                 .collect();
-            // XXX paging?
-            json_response(utxos, TTL_SHORT)
+
+            json_response(
+                query.utxo_sum(&script_hashes, cancel)?,
+                TTL_SHORT,
+                &query_params,
+            )
         }
         (&Method::GET, Some(&"address-prefix"), Some(prefix), None, None, None) => {
             if !config.address_search {
                 return Err(HttpError::from("address search disabled".to_string()));
             }
             let results = query.chain().address_search(prefix, ADDRESS_SEARCH_LIMIT);
-            json_response(results, TTL_SHORT)
+            json_response(results, TTL_SHORT, &query_params)
+        }
+        (&Method::GET, Some(&"tx-prefix"), Some(prefix), None, None, None) => {
+            if !config.txid_search {
+                return Err(HttpError::from("txid search disabled".to_string()));
+            }
+            if prefix.len() < TXID_SEARCH_MIN_PREFIX
+                || prefix.len() > 64
+                || !prefix.chars().all(|c| c.is_ascii_hexdigit())
+            {
+                bail!(HttpError::from(format!(
+                    "Invalid prefix, expected {} to 64 hex characters",
+                    TXID_SEARCH_MIN_PREFIX
+                )));
+            }
+            let (confirmed, unconfirmed, truncated) = query.txid_search(prefix, TXID_SEARCH_LIMIT);
+            let confirmed: Vec<_> = confirmed
+                .into_iter()
+                .map(|(txid, blockid)| json!({ "txid": txid, "block_height": blockid.height }))
+                .collect();
+            json_response(
+                json!({
+                    "confirmed": confirmed,
+                    "mempool": unconfirmed,
+                    "truncated": truncated,
+                }),
+                TTL_SHORT,
+                &query_params,
+            )
         }
         (&Method::GET, Some(&"tx"), Some(hash), None, None, None) => {
             let hash = Txid::from_hex(hash)?;
@@ -1290,8 +3892,42 @@ fn handle_request(
                 .ok_or_else(|| HttpError::not_found("Transaction not found".to_string()))?;
             let blockid = query.chain().tx_confirming_block(&hash);
             let ttl = ttl_by_depth(blockid.as_ref().map(|b| b.height), query);
+            // confirmed txs only change if their block gets reorged, so tag them by both hashes
+            let etag = blockid.as_ref().map(|b| format!("{}:{}", hash, b.hash));
+
+            match query_params.get("format").map(String::as_str) {
+                None | Some("esplora") => (),
+                #[cfg(not(feature = "liquid"))]
+                Some("electrum") => {
+                    return json_response(
+                        electrum_verbose_tx(&tx, blockid.as_ref(), query, config),
+                        ttl,
+                        &query_params,
+                    );
+                }
+                Some(format) => {
+                    return Err(HttpError::from(format!("Unsupported format '{}'", format)));
+                }
+            }
 
-            let mut tx = prepare_txs(vec![(tx, blockid)], query, config);
+            let is_confirmed = blockid.is_some();
+            let mut tx = prepare_txs(vec![(tx, blockid)], query, config, options);
+
+            if !is_confirmed {
+                let (package, ancestor_stats) = {
+                    let mempool = query.mempool();
+                    (
+                        mempool.package_feerate(&hash),
+                        mempool.ancestor_stats(&hash),
+                    )
+                };
+                if let (Some(value), Some(package)) = (tx.first_mut(), package) {
+                    value.effective_feerate = Some(package.effective_feerate());
+                    value.cpfp = Some(package);
+                    value.ancestor_feerate = ancestor_stats.map(|stats| stats.feerate());
+                    value.ancestors_truncated = ancestor_stats.map(|stats| stats.truncated);
+                }
+            }
 
             if tx.is_empty() {
                 http_message(
@@ -1300,7 +3936,11 @@ fn handle_request(
                     0,
                 )
             } else {
-                json_response(tx.remove(0), ttl)
+                let resp = json_response(tx.remove(0), ttl, &query_params)?;
+                Ok(match etag {
+                    Some(etag) => with_etag(resp, etag),
+                    None => resp,
+                })
             }
         }
         (&Method::POST, Some(&INTERNAL_PREFIX), Some(&"txs"), None, None, None) => {
@@ -1313,15 +3953,16 @@ fn handle_request(
                 .collect::<Result<Vec<Txid>, _>>()
             {
                 Ok(txids) => {
-                    let txs: Vec<(Transaction, Option<BlockId>)> = txids
+                    let txs: Vec<Option<(Transaction, Option<BlockId>)>> = txids
                         .iter()
-                        .filter_map(|txid| {
+                        .map(|txid| {
                             query
                                 .lookup_txn(txid)
                                 .map(|tx| (tx, query.chain().tx_confirming_block(txid)))
                         })
                         .collect();
-                    json_response(prepare_txs(txs, query, config), 0)
+                    let values = prepare_txs_aligned(txs, query, config, options);
+                    txs_by_txid_response(&txids, values, &query_params)
                 }
                 Err(err) => http_message(StatusCode::BAD_REQUEST, err.to_string(), 0),
             }
@@ -1333,30 +3974,299 @@ fn handle_request(
                 .lookup_raw_txn(&hash)
                 .ok_or_else(|| HttpError::not_found("Transaction not found".to_string()))?;
 
-            let (content_type, body) = match *out_type {
-                "raw" => ("application/octet-stream", Body::from(rawtx)),
-                "hex" => ("text/plain", Body::from(hex::encode(rawtx))),
-                _ => unreachable!(),
-            };
             let ttl = ttl_by_depth(query.get_tx_status(&hash).block_height, query);
+            // /raw serves hex too when asked for with `Accept: text/plain`
+            let as_hex = *out_type == "hex" || accepts(headers, "text/plain");
 
-            Ok(Response::builder()
-                .status(StatusCode::OK)
-                .header("Content-Type", content_type)
-                .header("Cache-Control", format!("public, max-age={:}", ttl))
-                .header("X-Powered-By", &**VERSION_STRING)
-                .body(body)
-                .unwrap())
+            // `?witness=false` serves the legacy serialization, with witness data stripped
+            if query_params.get("witness").map_or(false, |w| w == "false") {
+                #[cfg(not(feature = "liquid"))]
+                {
+                    let mut tx: Transaction = encode::deserialize(&rawtx)
+                        .map_err(|_| HttpError::from("Invalid transaction".to_string()))?;
+                    for txin in tx.input.iter_mut() {
+                        txin.witness.clear();
+                    }
+                    let mut resp = raw_tx_response(encode::serialize(&tx), as_hex, headers, ttl);
+                    resp.headers_mut()
+                        .insert("X-Serialization", HeaderValue::from_static("legacy"));
+                    return Ok(resp);
+                }
+                #[cfg(feature = "liquid")]
+                bail!(HttpError::from(
+                    "Witness stripping is not supported on liquid".to_string()
+                ));
+            }
+
+            Ok(raw_tx_response(rawtx, as_hex, headers, ttl))
+        }
+        (&Method::GET, Some(&"tx"), Some(hash), Some(relation @ &"ancestors"), None, None)
+        | (&Method::GET, Some(&"tx"), Some(hash), Some(relation @ &"descendants"), None, None) => {
+            let txid = Txid::from_hex(hash)?;
+            let (own, entries) = {
+                let mempool = query.mempool();
+                let own = mempool.fee_entry(&txid).ok_or_else(|| {
+                    HttpError::not_found("Transaction not found in mempool".to_string())
+                })?;
+                let related = match *relation {
+                    "ancestors" => mempool.ancestors(&txid),
+                    _ => mempool.descendants(&txid),
+                }
+                .unwrap_or_default();
+                let entries: Vec<_> = related
+                    .iter()
+                    .filter_map(|txid| mempool.fee_entry(txid))
+                    .collect();
+                (own, entries)
+            };
+
+            // aggregates include the transaction itself, like bitcoind's mempool entries
+            let count = entries.len() + 1;
+            let fee = own.fee + entries.iter().map(|e| e.fee).sum::<u64>();
+            let vsize = own.vsize + entries.iter().map(|e| e.vsize).sum::<u32>();
+            let prefix = match *relation {
+                "ancestors" => "ancestor",
+                _ => "descendant",
+            };
+
+            let mut value = json!({
+                "txs": entries,
+                "effective_feerate": fee as f64 / vsize as f64,
+            });
+            value[format!("{}_count", prefix)] = json!(count);
+            value[format!("{}_fee", prefix)] = json!(fee);
+            value[format!("{}_vsize", prefix)] = json!(vsize);
+
+            json_response(value, TTL_SHORT, &query_params)
+        }
+        #[cfg(not(feature = "liquid"))]
+        (&Method::GET, Some(&"tx"), Some(hash), Some(&"wtxid"), None, None) => {
+            let hash = Txid::from_hex(hash)?;
+            let tx = query
+                .lookup_txn(&hash)
+                .ok_or_else(|| HttpError::not_found("Transaction not found".to_string()))?;
+            let blockid = query.chain().tx_confirming_block(&hash);
+            let ttl = ttl_by_depth(blockid.map(|b| b.height), query);
+            http_message(StatusCode::OK, tx.wtxid().to_hex(), ttl)
+        }
+        #[cfg(not(feature = "liquid"))]
+        (&Method::GET, Some(&"tx"), Some(hash), Some(&"weight-breakdown"), None, None) => {
+            let hash = Txid::from_hex(hash)?;
+            let tx = query
+                .lookup_txn(&hash)
+                .ok_or_else(|| HttpError::not_found("Transaction not found".to_string()))?;
+            let blockid = query.chain().tx_confirming_block(&hash);
+            let ttl = ttl_by_depth(blockid.map(|b| b.height), query);
+
+            let outpoints: BTreeSet<OutPoint> = tx
+                .input
+                .iter()
+                .filter(|txin| has_prevout(txin))
+                .map(|txin| txin.previous_output)
+                .collect();
+            let txos = query.lookup_txos(&outpoints);
+            let prevouts = extract_tx_prevouts(&tx, &txos)?;
+            let (input_sigops, output_sigops) = sigop_cost_breakdown(&tx, &prevouts)
+                .map_err(|_| HttpError::from("Couldn't count sigops".to_string()))?;
+
+            // when any input has a witness, every input gets one serialized (even if empty)
+            let has_witness = tx.input.iter().any(|txin| !txin.witness.is_empty());
+            let inputs: Vec<_> = tx
+                .input
+                .iter()
+                .zip(input_sigops.iter())
+                .map(|(txin, sigops)| {
+                    let base_size = encode::serialize(txin).len();
+                    let witness_size = if has_witness {
+                        encode::serialize(&txin.witness).len()
+                    } else {
+                        0
+                    };
+                    json!({
+                        "base_size": base_size,
+                        "witness_size": witness_size,
+                        "weight": base_size * 4 + witness_size,
+                        "sigops": sigops,
+                    })
+                })
+                .collect();
+            let outputs: Vec<_> = tx
+                .output
+                .iter()
+                .map(|txout| {
+                    let size = encode::serialize(txout).len();
+                    json!({ "size": size, "weight": size * 4 })
+                })
+                .collect();
+
+            let weight = tx.weight();
+            let inputs_weight: u64 = inputs.iter().filter_map(|i| i["weight"].as_u64()).sum();
+            let outputs_weight: u64 = outputs.iter().filter_map(|o| o["weight"].as_u64()).sum();
+            json_response(
+                json!({
+                    "inputs": inputs,
+                    "outputs": outputs,
+                    "size": tx.size(),
+                    "weight": weight,
+                    "vsize": (weight + 3) / 4,
+                    "sigops": input_sigops.iter().chain(output_sigops.iter()).sum::<usize>(),
+                    // version, locktime, input/output counts and the segwit marker/flag
+                    "overhead_weight": weight as u64 - inputs_weight - outputs_weight,
+                }),
+                ttl,
+                &query_params,
+            )
+        }
+        // Follow the funding chain of the first input back towards a coinbase, for up to
+        // ?depth= hops (capped by --rest-max-ancestry-depth)
+        (&Method::GET, Some(&"tx"), Some(hash), Some(&"ancestry"), None, None) => {
+            let txid = Txid::from_hex(hash)?;
+            let max_depth = query_params
+                .get("depth")
+                .map(|depth| depth.parse::<usize>())
+                .transpose()?
+                .unwrap_or(config.rest_max_ancestry_depth)
+                .min(config.rest_max_ancestry_depth);
+
+            let mut tx = query
+                .lookup_txn(&txid)
+                .ok_or_else(|| HttpError::not_found("Transaction not found".to_string()))?;
+            let mut seen = HashSet::new();
+            seen.insert(txid);
+            let mut chain = vec![];
+            let (mut reached_coinbase, mut truncated, mut cycle) = (false, false, false);
+            loop {
+                let first_input = match tx.input.first() {
+                    Some(txin) if !is_coinbase(txin) => txin,
+                    _ => {
+                        reached_coinbase = true;
+                        break;
+                    }
+                };
+                if chain.len() == max_depth {
+                    truncated = true;
+                    break;
+                }
+                let parent_txid = first_input.previous_output.txid;
+                if !seen.insert(parent_txid) {
+                    cycle = true;
+                    break;
+                }
+                chain.push(parent_txid);
+                // the parent may be unknown, like for peg-in inputs
+                tx = match query.lookup_txn(&parent_txid) {
+                    Some(parent) => parent,
+                    None => break,
+                };
+            }
+
+            let blockid = query.chain().tx_confirming_block(&txid);
+            let ttl = ttl_by_depth(blockid.map(|b| b.height), query);
+            json_response(
+                json!({
+                    "txid": txid,
+                    "chain": chain,
+                    "hops": chain.len(),
+                    "reached_coinbase": reached_coinbase,
+                    "truncated": truncated,
+                    "cycle": cycle,
+                }),
+                ttl,
+                &query_params,
+            )
+        }
+        (&Method::GET, Some(&"tx"), Some(hash), Some(&"conflicts"), None, None) => {
+            let txid = Txid::from_hex(hash)?;
+            let conflict = if query.lookup_txn(&txid).is_some() {
+                TxConflict::with_status(ConflictStatus::Active)
+            } else {
+                query
+                    .mempool()
+                    .conflict(&txid)
+                    .unwrap_or_else(|| TxConflict::with_status(ConflictStatus::Unknown))
+            };
+            json_response(conflict, TTL_SHORT, &query_params)
+        }
+        (&Method::GET, Some(&"tx"), Some(hash), Some(&"replaceability"), None, None) => {
+            let txid = Txid::from_hex(hash)?;
+            let tx = query
+                .lookup_txn(&txid)
+                .ok_or_else(|| HttpError::not_found("Transaction not found".to_string()))?;
+            // confirmed transactions can no longer be replaced
+            let (rbf, inherited) = if query.chain().tx_confirming_block(&txid).is_some() {
+                (false, false)
+            } else {
+                let inherited = query.mempool().has_rbf_signaling_ancestor(&txid);
+                (signals_rbf(&tx), inherited)
+            };
+            json_response(
+                json!({ "rbf": rbf, "inherited": inherited }),
+                TTL_SHORT,
+                &query_params,
+            )
         }
         (&Method::GET, Some(&"tx"), Some(hash), Some(&"status"), None, None) => {
             let hash = Txid::from_hex(hash)?;
             let status = query.get_tx_status(&hash);
             let ttl = ttl_by_depth(status.block_height, query);
-            json_response(status, ttl)
+            json_response(status, ttl, &query_params)
+        }
+        // a thinner /tx/:txid/status for confirmed txs, 404 otherwise
+        (&Method::GET, Some(&"tx"), Some(hash), Some(&"block"), None, None) => {
+            let hash = Txid::from_hex(hash)?;
+            let blockid = query.chain().tx_confirming_block(&hash).ok_or_else(|| {
+                HttpError::not_found("Transaction not found or not confirmed".to_string())
+            })?;
+            let ttl = ttl_by_depth(Some(blockid.height), query);
+            json_response(
+                json!({
+                    "block_hash": blockid.hash,
+                    "block_height": blockid.height,
+                    "block_time": blockid.time,
+                }),
+                ttl,
+                &query_params,
+            )
         }
 
+        // Supports `?format=electrum` (the default), `merkleblock` (BIP37 hex, same as
+        // /merkleblock-proof) and `json_full` (electrum-style plus the block header hex)
         (&Method::GET, Some(&"tx"), Some(hash), Some(&"merkle-proof"), None, None) => {
             let hash = Txid::from_hex(hash)?;
+            let format = query_params
+                .get("format")
+                .map_or("electrum", String::as_str);
+            if !matches!(format, "electrum" | "merkleblock" | "json_full") {
+                return Err(HttpError::from(format!(
+                    "Invalid format '{}', expected one of: electrum, merkleblock, json_full",
+                    format
+                )));
+            }
+
+            if format == "merkleblock" {
+                #[cfg(not(feature = "liquid"))]
+                {
+                    let merkleblock =
+                        query.chain().get_merkleblock_proof(&hash).ok_or_else(|| {
+                            HttpError::not_found(
+                                "Transaction not found or is unconfirmed".to_string(),
+                            )
+                        })?;
+                    let height = query
+                        .chain()
+                        .height_by_hash(&merkleblock.header.block_hash());
+                    return http_message(
+                        StatusCode::OK,
+                        hex::encode(encode::serialize(&merkleblock)),
+                        ttl_by_depth(height, query),
+                    );
+                }
+                #[cfg(feature = "liquid")]
+                return Err(HttpError::from(
+                    "merkleblock proofs are not supported on liquid".to_string(),
+                ));
+            }
+
             let blockid = query.chain().tx_confirming_block(&hash).ok_or_else(|| {
                 HttpError::not_found("Transaction not found or is unconfirmed".to_string())
             })?;
@@ -1364,11 +4274,69 @@ fn handle_request(
                 electrum_merkle::get_tx_merkle_proof(query.chain(), &hash, &blockid.hash)?;
             let merkle: Vec<String> = merkle.into_iter().map(|txid| txid.to_hex()).collect();
             let ttl = ttl_by_depth(Some(blockid.height), query);
+
+            if format == "json_full" {
+                let header = query
+                    .chain()
+                    .get_block_header(&blockid.hash)
+                    .ok_or_else(|| HttpError::not_found("Block not found".to_string()))?;
+                return json_response(
+                    json!({
+                        "block_hash": blockid.hash,
+                        "block_height": blockid.height,
+                        "header": hex::encode(encode::serialize(&header)),
+                        "merkle": merkle,
+                        "pos": pos,
+                    }),
+                    ttl,
+                    &query_params,
+                );
+            }
+
             json_response(
                 json!({ "block_height": blockid.height, "merkle": merkle, "pos": pos }),
                 ttl,
+                &query_params,
             )
         }
+        // Binary inclusion proof, concatenating:
+        // - the consensus-serialized block header (80 bytes on Bitcoin)
+        // - the position of the tx in the block (u32, little-endian)
+        // - the number of merkle branch hashes (u32, little-endian)
+        // - the merkle branch hashes (32 bytes each, in internal byte order, bottom-up)
+        (&Method::GET, Some(&"tx"), Some(hash), Some(&"raw-proof"), None, None) => {
+            let hash = Txid::from_hex(hash)?;
+            let blockid = query.chain().tx_confirming_block(&hash).ok_or_else(|| {
+                HttpError::not_found("Transaction not found or is unconfirmed".to_string())
+            })?;
+            let (merkle, pos) =
+                electrum_merkle::get_tx_merkle_proof(query.chain(), &hash, &blockid.hash)?;
+            let header = query
+                .chain()
+                .get_block_header(&blockid.hash)
+                .ok_or_else(|| HttpError::not_found("Block not found".to_string()))?;
+
+            let mut proof = encode::serialize(&header);
+            proof.extend_from_slice(&(pos as u32).to_le_bytes());
+            proof.extend_from_slice(&(merkle.len() as u32).to_le_bytes());
+            for node in merkle {
+                proof.extend_from_slice(&node[..]);
+            }
+
+            Ok(Response::builder()
+                .status(StatusCode::OK)
+                .header("Content-Type", "application/octet-stream")
+                .header(
+                    "Cache-Control",
+                    format!(
+                        "public, max-age={:}",
+                        ttl_by_depth(Some(blockid.height), query)
+                    ),
+                )
+                .header("X-Powered-By", &**VERSION_STRING)
+                .body(Body::from(proof))
+                .unwrap())
+        }
         #[cfg(not(feature = "liquid"))]
         (&Method::GET, Some(&"tx"), Some(hash), Some(&"merkleblock-proof"), None, None) => {
             let hash = Txid::from_hex(hash)?;
@@ -1387,39 +4355,104 @@ fn handle_request(
                 ttl_by_depth(height, query),
             )
         }
+        (&Method::GET, Some(&"tx"), Some(hash), Some(&"vin"), Some(index), None) => {
+            let hash = Txid::from_hex(hash)?;
+            let index: usize = index.parse()?;
+            let tx = query
+                .lookup_txn(&hash)
+                .ok_or_else(|| HttpError::not_found("Transaction not found".to_string()))?;
+            let txin = tx
+                .input
+                .get(index)
+                .ok_or_else(|| HttpError::not_found("vin index out of range".to_string()))?;
+
+            let txos = if has_prevout(txin) {
+                let outpoints: BTreeSet<OutPoint> = std::iter::once(txin.previous_output).collect();
+                query.lookup_txos(&outpoints)
+            } else {
+                HashMap::new()
+            };
+            let value = TxInValue::new(txin, txos.get(&txin.previous_output), config);
+            let ttl = ttl_by_depth(query.get_tx_status(&hash).block_height, query);
+            json_response(value, ttl, &query_params)
+        }
+        (&Method::GET, Some(&"tx"), Some(hash), Some(&"vout"), Some(index), None) => {
+            let hash = Txid::from_hex(hash)?;
+            let index: usize = index.parse()?;
+            let tx = query
+                .lookup_txn(&hash)
+                .ok_or_else(|| HttpError::not_found("Transaction not found".to_string()))?;
+            let txout = tx
+                .output
+                .get(index)
+                .ok_or_else(|| HttpError::not_found("vout index out of range".to_string()))?;
+
+            let value = TxOutValue::new(txout, config);
+            let ttl = ttl_by_depth(query.get_tx_status(&hash).block_height, query);
+            json_response(value, ttl, &query_params)
+        }
         (&Method::GET, Some(&"tx"), Some(hash), Some(&"outspend"), Some(index), None) => {
             let hash = Txid::from_hex(hash)?;
             let outpoint = OutPoint {
                 txid: hash,
                 vout: index.parse::<u32>()?,
             };
-            let spend = query
+            let mut spend = query
                 .lookup_spend(&outpoint)
                 .map_or_else(SpendingValue::default, SpendingValue::from);
+            add_spends_details(Some(&mut spend), query, &query_params, options);
             let ttl = ttl_by_depth(
                 spend.status.as_ref().and_then(|status| status.block_height),
                 query,
             );
-            json_response(spend, ttl)
+            json_response(spend, ttl, &query_params)
         }
+        // Transactions with more than rest_default_outspends_limit outputs are paginated,
+        // using `?start=<vout>&limit=<n>` (or `count=<n>`). The total number of outputs is
+        // returned in the X-Total-Count header.
         (&Method::GET, Some(&"tx"), Some(hash), Some(&"outspends"), None, None) => {
             let hash = Txid::from_hex(hash)?;
-            let tx = query
-                .lookup_txn(&hash)
+            let output_count = query
+                .tx_output_count(&hash)
                 .ok_or_else(|| HttpError::not_found("Transaction not found".to_string()))?;
-            let spends: Vec<SpendingValue> = query
-                .lookup_tx_spends(tx)
+
+            let start: u32 = query_params
+                .get("start")
+                .map_or(Ok(0), |start| start.parse())?;
+            let limit: u32 = query_params
+                .get("limit")
+                .or_else(|| query_params.get("count"))
+                .map_or(Ok(config.rest_default_outspends_limit), |limit| {
+                    limit.parse()
+                })?
+                .min(config.rest_max_outspends_limit) as u32;
+            if start >= output_count {
+                bail!(HttpError::not_found("start index out of range".to_string()));
+            }
+            let count = limit.min(output_count - start);
+
+            let mut spends: Vec<SpendingValue> = query
+                .lookup_spends_range(&hash, start, count)
                 .into_iter()
                 .map(|spend| spend.map_or_else(SpendingValue::default, SpendingValue::from))
                 .collect();
+            add_spends_details(&mut spends, query, &query_params, options);
             // @TODO long ttl if all outputs are either spent long ago or unspendable
-            json_response(spends, TTL_SHORT)
+            let mut resp = json_response(spends, TTL_SHORT, &query_params)?;
+            resp.headers_mut()
+                .insert(TOTAL_COUNT_HEADER, HeaderValue::from(output_count));
+            Ok(resp)
         }
         (&Method::GET, Some(&"broadcast"), None, None, None, None)
         | (&Method::POST, Some(&"tx"), None, None, None, None) => {
             // accept both POST and GET for backward compatibility.
             // GET will eventually be removed in favor of POST.
             let txhex = match method {
+                // html forms post the tx as a form field rather than as the raw body
+                Method::POST if is_form_urlencoded(headers) => form_urlencoded::parse(&body)
+                    .find(|(key, _)| key == "tx")
+                    .map(|(_, value)| value.into_owned())
+                    .ok_or_else(|| HttpError::from("Missing tx".to_string()))?,
                 Method::POST => String::from_utf8(body.to_vec())?,
                 Method::GET => query_params
                     .get("tx")
@@ -1427,52 +4460,257 @@ fn handle_request(
                     .ok_or_else(|| HttpError::from("Missing tx".to_string()))?,
                 _ => return http_message(StatusCode::METHOD_NOT_ALLOWED, "Invalid method", 0),
             };
-            let txid = query
-                .broadcast_raw(&txhex)
-                .map_err(|err| HttpError::from(err.description().to_string()))?;
-            http_message(StatusCode::OK, txid.to_hex(), 0)
+            let maxfeerate = parse_maxfeerate(&query_params)?;
+            let txid = match query.broadcast_raw(&txhex, maxfeerate) {
+                Ok(txid) => txid,
+                Err(err) => {
+                    let (status, value) = broadcast_error(&err);
+                    let mut resp = json_response(value, 0, &query_params)?;
+                    *resp.status_mut() = status;
+                    return Ok(resp);
+                }
+            };
+            if accepts_json(headers) {
+                json_response(json!({ "txid": txid }), 0, &query_params)
+            } else {
+                http_message(StatusCode::OK, txid.to_hex(), 0)
+            }
+        }
+        (&Method::POST, Some(&"tx"), Some(&"decode"), None, None, None) => {
+            let txhex = String::from_utf8(body.to_vec())?;
+            let tx: Transaction = encode::deserialize(&hex::decode(txhex.trim())?)
+                .map_err(|_| HttpError::from("Invalid transaction".to_string()))?;
+
+            let outpoints: BTreeSet<OutPoint> = tx
+                .input
+                .iter()
+                .filter(|txin| has_prevout(txin))
+                .map(|txin| txin.previous_output)
+                .collect();
+            let txos = query.lookup_txos(&outpoints);
+            let prevouts: HashMap<u32, &TxOut> = tx
+                .input
+                .iter()
+                .enumerate()
+                .filter_map(|(index, txin)| Some((index as u32, txos.get(&txin.previous_output)?)))
+                .collect();
+
+            let value = TransactionValue::from_prevouts(tx, None, &prevouts, config)?;
+            // not broadcast yet, so report an explicit null status rather than omitting it
+            let mut value = serde_json::to_value(&value)?;
+            value["status"] = serde_json::Value::Null;
+            json_response(value, 0, &query_params)
+        }
+        (&Method::POST, Some(&"tx"), Some(&"verify-merkle-proof"), None, None, None) => {
+            let req: VerifyMerkleProofRequest = serde_json::from_slice(&body)
+                .map_err(|err| HttpError::from(format!("Invalid request: {}", err)))?;
+
+            let txid = Sha256dHash::from_hex(&req.txid)
+                .map_err(|_| HttpError::from("Invalid hex for txid".to_string()))?;
+            let merkle = req
+                .merkle
+                .iter()
+                .map(|node| Sha256dHash::from_hex(node))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| HttpError::from("Invalid hex in merkle branch".to_string()))?;
+
+            let block_hash = match (req.block_hash, req.block_height) {
+                (Some(hash), _) => BlockHash::from_hex(&hash)
+                    .map_err(|_| HttpError::from("Invalid hex for block_hash".to_string()))?,
+                (None, Some(height)) => query
+                    .chain()
+                    .hash_by_height(height)
+                    .ok_or_else(|| HttpError::from("Unknown block height".to_string()))?,
+                (None, None) => bail!(HttpError::from(
+                    "Either block_hash or block_height is required".to_string()
+                )),
+            };
+            let meta = query
+                .chain()
+                .get_block_meta(&block_hash)
+                .ok_or_else(|| HttpError::from("Unknown block".to_string()))?;
+            let header = query
+                .chain()
+                .get_block_header(&block_hash)
+                .ok_or_else(|| HttpError::from("Unknown block".to_string()))?;
+
+            if req.pos >= meta.tx_count as usize {
+                bail!(HttpError::from(format!(
+                    "Position {} out of range for block with {} transactions",
+                    req.pos, meta.tx_count
+                )));
+            }
+
+            let computed_root = electrum_merkle::compute_merkle_root(txid, &merkle, req.pos);
+            let header_root = Sha256dHash::from_inner(header.merkle_root.into_inner());
+            json_response(
+                json!({
+                    "valid": computed_root == header_root,
+                    "computed_root": computed_root.to_hex(),
+                    "header_root": header_root.to_hex(),
+                }),
+                0,
+                &query_params,
+            )
+        }
+        (&Method::POST, Some(&"txs"), Some(&"fees"), None, None, None) => {
+            let txids: Vec<Txid> = serde_json::from_slice(&body)?;
+            if txids.len() > TX_FEES_LIMIT {
+                bail!(HttpError::from(format!(
+                    "Exceeded maximum of {} txids",
+                    TX_FEES_LIMIT
+                )));
+            }
+
+            let txs: Vec<Option<Transaction>> =
+                txids.iter().map(|txid| query.lookup_txn(txid)).collect();
+            // gather the prevouts of all the txs in a single lookup
+            let outpoints: BTreeSet<OutPoint> = txs
+                .iter()
+                .flatten()
+                .flat_map(|tx| tx.input.iter())
+                .filter(|txin| has_prevout(txin))
+                .map(|txin| txin.previous_output)
+                .collect();
+            let txos = query.lookup_txos(&outpoints);
+
+            // unknown txids are kept with null values, so that results align with the request
+            let fees: Vec<TxFeeValue> = txids
+                .into_iter()
+                .zip(txs)
+                .map(|(txid, tx)| {
+                    let fee = tx.as_ref().and_then(|tx| {
+                        let prevouts = extract_tx_prevouts(tx, &txos).ok()?;
+                        Some(get_tx_fee(tx, &prevouts, config.network_type))
+                    });
+                    let vsize = tx.as_ref().map(|tx| (tx.weight() as u32 + 3) / 4);
+                    TxFeeValue {
+                        txid,
+                        fee,
+                        vsize,
+                        feerate: fee.zip(vsize).map(|(fee, vsize)| fee as f64 / vsize as f64),
+                        confirmed: tx.map(|_| query.chain().tx_confirming_block(&txid).is_some()),
+                    }
+                })
+                .collect();
+
+            json_response(fees, TTL_SHORT, &query_params)
+        }
+        (&Method::POST, Some(&"tx"), Some(&"test"), None, None, None) => {
+            let txhex = String::from_utf8(body.to_vec())?.trim().to_string();
+            let tx: Transaction = Vec::<u8>::from_hex(&txhex)
+                .ok()
+                .and_then(|bytes| encode::deserialize(&bytes).ok())
+                .ok_or_else(|| HttpError::from("Invalid transaction".to_string()))?;
+
+            let maxfeerate = parse_maxfeerate(&query_params)?;
+            let maxburnamount = query_params
+                .get("maxburnamount")
+                .map(|s| {
+                    s.parse::<f64>()
+                        .map_err(|_| HttpError::from("Invalid maxburnamount".to_string()))
+                })
+                .transpose()?;
+
+            // testmempoolaccept takes no maxburnamount (only sendrawtransaction does, since
+            // bitcoind v25.0), so it is checked here the same way, whatever the daemon version
+            let burn_exceeded = maxburnamount.map_or(false, |maxburnamount| {
+                exceeds_burn_amount(&tx, (maxburnamount * 100_000_000f64) as u64)
+            });
+
+            let result = query
+                .test_mempool_accept(vec![txhex], maxfeerate)
+                .map_err(|err| HttpError::from(err.description().to_string()))?
+                .pop()
+                .ok_or_else(|| HttpError::from("Empty testmempoolaccept reply".to_string()))?;
+
+            let value = if burn_exceeded {
+                TxTestValue {
+                    allowed: false,
+                    reject_reason: Some("max-burn-amount-exceeded".to_string()),
+                    vsize: None,
+                    fees: None,
+                }
+            } else {
+                TxTestValue {
+                    allowed: result.allowed.unwrap_or(false),
+                    reject_reason: result.reject_reason,
+                    vsize: result.vsize,
+                    fees: result.fees.map(|fees| TxTestFees {
+                        base: fees.base,
+                        effective_feerate: fees.effective_feerate,
+                    }),
+                }
+            };
+            // the verdict depends on the current mempool, it mustn't be cached
+            json_response(value, 0, &query_params)
+        }
+        (&Method::POST, Some(&"txs"), Some(&"package"), None, None, None) => {
+            let txhexes: Vec<String> =
+                serde_json::from_str(String::from_utf8(body.to_vec())?.as_str())?;
+            precheck_txhexes(&txhexes)?;
+
+            let txs = txhexes
+                .iter()
+                .enumerate()
+                .map(|(index, txhex)| {
+                    encode::deserialize::<Transaction>(&Vec::<u8>::from_hex(txhex).unwrap())
+                        .map_err(|_| {
+                            HttpError::from(format!("Invalid transaction for item {}", index))
+                        })
+                })
+                .collect::<Result<Vec<Transaction>, HttpError>>()?;
+
+            let mut result = query.submit_package(&txs)?;
+
+            // report results in the same order as the submitted transactions
+            let tx_results: Vec<PackageTxValue> = txs
+                .iter()
+                .map(|tx| {
+                    let wtxid = tx.wtxid().to_string();
+                    let tx_result = result.tx_results.remove(&wtxid);
+                    PackageTxValue {
+                        txid: tx.txid(),
+                        wtxid: tx_result
+                            .as_ref()
+                            .and_then(|r| r.other_wtxid.clone())
+                            .unwrap_or(wtxid),
+                        vsize: tx_result.as_ref().and_then(|r| r.vsize),
+                        fees: tx_result
+                            .as_ref()
+                            .and_then(|r| r.fees.as_ref())
+                            .map(|fees| TxTestFees {
+                                base: fees.base,
+                                effective_feerate: fees.effective_feerate,
+                            }),
+                        error: tx_result.and_then(|r| r.error),
+                    }
+                })
+                .collect();
+
+            json_response(
+                json!({
+                    "package_msg": result.package_msg,
+                    "tx_results": tx_results,
+                    "replaced_transactions": result.replaced_transactions,
+                }),
+                0,
+                &query_params,
+            )
         }
         (&Method::POST, Some(&"txs"), Some(&"test"), None, None, None) => {
             let txhexes: Vec<String> =
                 serde_json::from_str(String::from_utf8(body.to_vec())?.as_str())?;
 
-            if txhexes.len() > 25 {
-                Result::Err(HttpError::from(
-                    "Exceeded maximum of 25 transactions".to_string(),
-                ))?
-            }
-
-            let maxfeerate = query_params
-                .get("maxfeerate")
-                .map(|s| {
-                    s.parse::<f64>()
-                        .map_err(|_| HttpError::from("Invalid maxfeerate".to_string()))
-                })
-                .transpose()?;
+            let maxfeerate = parse_maxfeerate(&query_params)?;
 
-            // pre-checks
-            txhexes.iter().enumerate().try_for_each(|(index, txhex)| {
-                // each transaction must be of reasonable size (more than 60 bytes, within 400kWU standardness limit)
-                if !(120..800_000).contains(&txhex.len()) {
-                    Result::Err(HttpError::from(format!(
-                        "Invalid transaction size for item {}",
-                        index
-                    )))
-                } else {
-                    // must be a valid hex string
-                    Vec::<u8>::from_hex(txhex)
-                        .map_err(|_| {
-                            HttpError::from(format!("Invalid transaction hex for item {}", index))
-                        })
-                        .map(|_| ())
-                }
-            })?;
+            precheck_txhexes(&txhexes)?;
 
             let result = query
                 .test_mempool_accept(txhexes, maxfeerate)
                 .map_err(|err| HttpError::from(err.description().to_string()))?;
 
-            json_response(result, TTL_SHORT)
+            json_response(result, TTL_SHORT, &query_params)
         }
         (&Method::GET, Some(&"txs"), Some(&"outspends"), None, None, None) => {
             let txid_strings: Vec<&str> = query_params
@@ -1486,7 +4724,7 @@ fn handle_request(
                 return http_message(StatusCode::BAD_REQUEST, "Too many txids requested", 0);
             }
 
-            let spends: Vec<Vec<SpendingValue>> = txid_strings
+            let mut spends: Vec<Vec<SpendingValue>> = txid_strings
                 .into_iter()
                 .map(|txid_str| {
                     Txid::from_hex(txid_str)
@@ -1503,8 +4741,36 @@ fn handle_request(
                         })
                 })
                 .collect();
+            add_spends_status_details(spends.iter_mut().flatten(), query, options);
+
+            json_response(spends, TTL_SHORT, &query_params)
+        }
+        (&Method::GET, Some(&"outspends"), None, None, None, None) => {
+            let outpoints = query_params
+                .get("outpoints")
+                .ok_or_else(|| HttpError::from("No outpoints specified".to_string()))?
+                .split(',')
+                .map(parse_outpoint)
+                .collect::<Result<Vec<OutPoint>, HttpError>>()?;
+
+            if outpoints.len() > OUTSPENDS_OUTPOINTS_LIMIT {
+                bail!(HttpError::from(format!(
+                    "Exceeded maximum of {} outpoints",
+                    OUTSPENDS_OUTPOINTS_LIMIT
+                )));
+            }
+
+            let mut spends: Vec<SpendingValue> = outpoints
+                .iter()
+                .map(|outpoint| {
+                    query
+                        .lookup_spend(outpoint)
+                        .map_or_else(SpendingValue::default, SpendingValue::from)
+                })
+                .collect();
+            add_spends_details(&mut spends, query, &query_params, options);
 
-            json_response(spends, TTL_SHORT)
+            json_response(spends, TTL_SHORT, &query_params)
         }
         (
             &Method::POST,
@@ -1517,7 +4783,7 @@ fn handle_request(
             let txid_strings: Vec<String> =
                 serde_json::from_slice(&body).map_err(|err| HttpError::from(err.to_string()))?;
 
-            let spends: Vec<Vec<SpendingValue>> = txid_strings
+            let mut spends: Vec<Vec<SpendingValue>> = txid_strings
                 .into_iter()
                 .map(|txid_str| {
                     Txid::from_hex(&txid_str)
@@ -1534,8 +4800,9 @@ fn handle_request(
                         })
                 })
                 .collect();
+            add_spends_status_details(spends.iter_mut().flatten(), query, options);
 
-            json_response(spends, TTL_SHORT)
+            json_response(spends, TTL_SHORT, &query_params)
         }
         (
             &Method::POST,
@@ -1548,7 +4815,7 @@ fn handle_request(
             let outpoint_strings: Vec<String> =
                 serde_json::from_slice(&body).map_err(|err| HttpError::from(err.to_string()))?;
 
-            let spends: Vec<SpendingValue> = outpoint_strings
+            let mut spends: Vec<SpendingValue> = outpoint_strings
                 .into_iter()
                 .map(|outpoint_str| {
                     let mut parts = outpoint_str.split(':');
@@ -1566,15 +4833,56 @@ fn handle_request(
                     SpendingValue::default()
                 })
                 .collect();
+            add_spends_status_details(&mut spends, query, options);
 
-            json_response(spends, TTL_SHORT)
+            json_response(spends, TTL_SHORT, &query_params)
         }
 
         (&Method::GET, Some(&"mempool"), None, None, None, None) => {
-            json_response(query.mempool().backlog_stats(), TTL_SHORT)
+            json_response_etag(query.mempool().backlog_stats(), TTL_SHORT, &query_params)
+        }
+        (&Method::GET, Some(&"mempool"), Some(relation @ &"ancestors"), Some(hash), None, None)
+        | (
+            &Method::GET,
+            Some(&"mempool"),
+            Some(relation @ &"descendants"),
+            Some(hash),
+            None,
+            None,
+        ) => {
+            let txid = Txid::from_hex(hash)?;
+            let txids = {
+                let mempool = query.mempool();
+                match *relation {
+                    "ancestors" => mempool.ancestors(&txid),
+                    _ => mempool.descendants(&txid),
+                }
+            }
+            .ok_or_else(|| HttpError::not_found("Transaction not found in mempool".to_string()))?;
+
+            if query_params.get("verbose").map_or(false, |v| v == "true") {
+                let txs = {
+                    let mempool = query.mempool();
+                    txids
+                        .iter()
+                        .filter_map(|txid| mempool.lookup_txn(txid))
+                        .map(|tx| (tx, None))
+                        .collect()
+                };
+                let mut values = prepare_txs(txs, query, config, options);
+                let mempool = query.mempool();
+                for value in values.iter_mut() {
+                    let stats = mempool.ancestor_stats(&value.txid);
+                    value.ancestor_feerate = stats.map(|stats| stats.feerate());
+                    value.ancestors_truncated = stats.map(|stats| stats.truncated);
+                }
+                json_response(values, TTL_SHORT, &query_params)
+            } else {
+                json_response(txids, TTL_SHORT, &query_params)
+            }
         }
         (&Method::GET, Some(&"mempool"), Some(&"txids"), None, None, None) => {
-            json_response(query.mempool().txids(), TTL_SHORT)
+            json_response(query.mempool().txids(), TTL_SHORT, &query_params)
         }
         (&Method::GET, Some(&"mempool"), Some(&"txids"), Some(&"page"), last_seen_txid, None) => {
             let last_seen_txid = last_seen_txid.and_then(|txid| Txid::from_hex(txid).ok());
@@ -1585,6 +4893,7 @@ fn handle_request(
             json_response(
                 query.mempool().txids_page(max_txs, last_seen_txid),
                 TTL_SHORT,
+                &query_params,
             )
         }
         (
@@ -1602,7 +4911,95 @@ fn handle_request(
                 .map(|tx| (tx, None))
                 .collect();
 
-            json_response(prepare_txs(txs, query, config), TTL_SHORT)
+            json_response(
+                prepare_txs(txs, query, config, options),
+                TTL_SHORT,
+                &query_params,
+            )
+        }
+        (&Method::GET, Some(&INTERNAL_PREFIX), Some(&"index-stats"), None, None, None) => {
+            if !is_admin_request(headers, peer, config) {
+                return Err(HttpError::new(
+                    StatusCode::FORBIDDEN,
+                    "Admin access required".to_string(),
+                ));
+            }
+            let chain = query.chain();
+            let dbs: serde_json::Map<String, serde_json::Value> = chain
+                .store()
+                .db_stats()
+                .into_iter()
+                .map(|(name, stats)| (name.to_string(), json!(stats)))
+                .collect();
+            json_response(
+                json!({
+                    "best_height": chain.best_height(),
+                    "dbs": dbs,
+                }),
+                0,
+                &query_params,
+            )
+        }
+        // initial sync progress, cheap enough to poll every few seconds
+        (&Method::GET, Some(&INTERNAL_PREFIX), Some(&"sync-status"), None, None, None) => {
+            let chain = query.chain();
+            let store = chain.store();
+            let indexed_height = chain.best_height();
+            let (daemon_height, headers_height) = match query.daemon_blockchain_info() {
+                Ok(info) => (Some(info.blocks as usize), Some(info.headers as usize)),
+                Err(_) => (None, None),
+            };
+            let initial_sync = !store.done_initial_sync();
+            let blocks_per_second = store.sync_rate();
+
+            // blocks are first added to the txstore, then indexed into the history db
+            let eta_seconds = match daemon_height {
+                Some(height) if initial_sync && blocks_per_second > 0.0 => {
+                    let (added, indexed) = store.synced_blocks();
+                    let remaining = (2 * (height + 1)).saturating_sub(added + indexed);
+                    Some((remaining as f64 / blocks_per_second).ceil() as u64)
+                }
+                _ => None,
+            };
+            let db_size_bytes: u64 = store
+                .db_stats()
+                .iter()
+                .filter_map(|(_, stats)| stats.total_sst_files_size)
+                .sum();
+
+            json_response(
+                json!({
+                    "indexed_height": indexed_height,
+                    "daemon_height": daemon_height,
+                    "headers_height": headers_height,
+                    "initial_sync": initial_sync,
+                    "blocks_per_second_recent": blocks_per_second,
+                    "eta_seconds": eta_seconds,
+                    "mempool_tx_count": query.mempool().tx_count(),
+                    "db_size_bytes": db_size_bytes,
+                }),
+                0,
+                &query_params,
+            )
+        }
+        (&Method::POST, Some(&INTERNAL_PREFIX), Some(&"mempool"), Some(&"resync"), None, None) => {
+            if !is_admin_request(headers, peer, config) {
+                return Err(HttpError::new(
+                    StatusCode::FORBIDDEN,
+                    "Admin access required".to_string(),
+                ));
+            }
+            let start = Instant::now();
+            let (added, removed) = query.resync_mempool()?;
+            json_response(
+                json!({
+                    "added": added,
+                    "removed": removed,
+                    "duration_ms": start.elapsed().as_millis() as u64,
+                }),
+                0,
+                &query_params,
+            )
         }
         (&Method::POST, Some(&INTERNAL_PREFIX), Some(&"mempool"), Some(&"txs"), None, None) => {
             let txid_strings: Vec<String> =
@@ -1614,15 +5011,16 @@ fn handle_request(
                 .collect::<Result<Vec<Txid>, _>>()
             {
                 Ok(txids) => {
-                    let txs: Vec<(Transaction, Option<BlockId>)> = {
+                    let txs: Vec<Option<(Transaction, Option<BlockId>)>> = {
                         let mempool = query.mempool();
                         txids
                             .iter()
-                            .filter_map(|txid| mempool.lookup_txn(txid).map(|tx| (tx, None)))
+                            .map(|txid| mempool.lookup_txn(txid).map(|tx| (tx, None)))
                             .collect()
                     };
 
-                    json_response(prepare_txs(txs, query, config), 0)
+                    let values = prepare_txs_aligned(txs, query, config, options);
+                    txs_by_txid_response(&txids, values, &query_params)
                 }
                 Err(err) => http_message(StatusCode::BAD_REQUEST, err.to_string(), 0),
             }
@@ -1647,16 +5045,23 @@ fn handle_request(
                 .map(|tx| (tx, None))
                 .collect();
 
-            json_response(prepare_txs(txs, query, config), TTL_SHORT)
+            json_response(
+                prepare_txs(txs, query, config, options),
+                TTL_SHORT,
+                &query_params,
+            )
         }
         (&Method::GET, Some(&"mempool"), Some(&"recent"), None, None, None) => {
             let mempool = query.mempool();
             let recent = mempool.recent_txs_overview();
-            json_response(recent, TTL_MEMPOOL_RECENT)
+            json_response(recent, TTL_MEMPOOL_RECENT, &query_params)
         }
 
         (&Method::GET, Some(&"fee-estimates"), None, None, None, None) => {
-            json_response(query.estimate_fee_map(), TTL_SHORT)
+            fee_estimates_response(query.estimate_fee_map(), &query_params)
+        }
+        (&Method::GET, Some(&"fee-estimates"), Some(&"mempool"), None, None, None) => {
+            fee_estimates_response(query.mempool_fee_estimates(), &query_params)
         }
 
         #[cfg(feature = "liquid")]
@@ -1693,7 +5098,7 @@ fn handle_request(
                 .lookup_asset(&asset_id)?
                 .ok_or_else(|| HttpError::not_found("Asset id not found".to_string()))?;
 
-            json_response(asset_entry, TTL_SHORT)
+            json_response(asset_entry, TTL_SHORT, &query_params)
         }
 
         #[cfg(feature = "liquid")]
@@ -1718,7 +5123,11 @@ fn handle_request(
                     .collect::<Result<Vec<_>, _>>()?,
             );
 
-            json_response(prepare_txs(txs, query, config), TTL_SHORT)
+            json_response(
+                prepare_txs(txs, query, config, options),
+                TTL_SHORT,
+                &query_params,
+            )
         }
 
         #[cfg(feature = "liquid")]
@@ -1743,7 +5152,11 @@ fn handle_request(
                 .map(|res| res.map(|(tx, blockid)| (tx, Some(blockid))))
                 .collect::<Result<Vec<_>, _>>()?;
 
-            json_response(prepare_txs(txs, query, config), TTL_SHORT)
+            json_response(
+                prepare_txs(txs, query, config, options),
+                TTL_SHORT,
+                &query_params,
+            )
         }
 
         #[cfg(feature = "liquid")]
@@ -1757,7 +5170,83 @@ fn handle_request(
                 .map(|tx| (tx, None))
                 .collect();
 
-            json_response(prepare_txs(txs, query, config), TTL_SHORT)
+            json_response(
+                prepare_txs(txs, query, config, options),
+                TTL_SHORT,
+                &query_params,
+            )
+        }
+
+        // The asset index only tracks issuance, peg and burn events, so this lists the unspent
+        // outputs created by those transactions (i.e. freshly issued or pegged-in coins that
+        // didn't move since), not every holder of the asset. Outputs with a confidential value
+        // are listed with their value blinded. Pages continue after the `after_outpoint` cursor,
+        // which is rejected unless it's one of those outputs.
+        #[cfg(feature = "liquid")]
+        (&Method::GET, Some(&"asset"), Some(asset_str), Some(&"utxos"), None, None) => {
+            let asset_id = AssetId::from_hex(asset_str)?;
+            let after_outpoint = query_params
+                .get("after_outpoint")
+                .map(String::as_str)
+                .map(parse_outpoint)
+                .transpose()?;
+
+            let mut txs: Vec<(Transaction, Option<BlockId>)> = query
+                .mempool()
+                .asset_history(&asset_id, config.rest_default_max_mempool_txs)
+                .into_iter()
+                .map(|tx| (tx, None))
+                .collect();
+            txs.extend(
+                query
+                    .chain()
+                    .asset_history(&asset_id, None, ASSET_UTXOS_SCAN_LIMIT)
+                    .map(|res| res.map(|(tx, blockid)| (tx, Some(blockid))))
+                    .collect::<Result<Vec<_>, _>>()?,
+            );
+
+            let outputs: Vec<Utxo> = txs
+                .iter()
+                .flat_map(move |(tx, blockid)| {
+                    let txid = tx.txid();
+                    tx.output
+                        .iter()
+                        .enumerate()
+                        .filter(move |(_, txout)| txout.asset == Asset::Explicit(asset_id))
+                        .map(move |(vout, txout)| Utxo {
+                            txid,
+                            vout: vout as u32,
+                            confirmed: blockid.clone(),
+                            value: txout.value,
+                            asset: txout.asset,
+                            nonce: txout.nonce,
+                            witness: txout.witness.clone(),
+                        })
+                })
+                .collect();
+            // the cursor has to be one of the listed outputs, spent since or not
+            let start = match after_outpoint {
+                Some(after) => {
+                    let position = outputs
+                        .iter()
+                        .position(|utxo| OutPoint::from(utxo) == after)
+                        .ok_or_else(|| {
+                            HttpError::from(format!("Unknown after_outpoint {}", after))
+                        })?;
+                    position + 1
+                }
+                None => 0,
+            };
+
+            let utxos = outputs
+                .into_iter()
+                .skip(start)
+                .filter(|utxo| query.lookup_spend(&OutPoint::from(utxo)).is_none())
+                .take(ASSET_UTXOS_LIMIT)
+                .map(UtxoValue::from)
+                .collect::<Vec<_>>();
+
+            json_response(utxos, TTL_SHORT, &query_params)
         }
 
         #[cfg(feature = "liquid")]
@@ -1800,14 +5289,542 @@ where
         .unwrap())
 }
 
-fn json_response<T: Serialize>(value: T, ttl: u32) -> Result<Response<Body>, HttpError> {
-    let value = serde_json::to_string(&value)?;
-    Ok(Response::builder()
+// Answer a HEAD request with the headers of the GET response, including its Content-Length
+fn head_response(mut resp: Response<Body>) -> Response<Body> {
+    if let Some(len) = resp.body().size_hint().exact() {
+        resp.headers_mut()
+            .insert(header::CONTENT_LENGTH, HeaderValue::from(len));
+    }
+    *resp.body_mut() = Body::empty();
+    resp
+}
+
+fn etag_value(tag: impl fmt::Display) -> String {
+    format!("\"{}\"", tag)
+}
+
+// Tag a response, letting clients revalidate it with If-None-Match
+fn with_etag(mut resp: Response<Body>, tag: impl fmt::Display) -> Response<Body> {
+    resp.headers_mut()
+        .insert(header::ETAG, etag_value(tag).parse().unwrap());
+    resp
+}
+
+// Mark the ETag of another representation of the same response, e.g. `"abcd"` becomes
+// `"abcd-gzip"`
+fn suffix_etag(headers: &mut HeaderMap, suffix: &str) {
+    let etag = headers
+        .get(header::ETAG)
+        .and_then(|etag| etag.to_str().ok())
+        .and_then(|etag| etag.strip_suffix('"'))
+        .and_then(|etag| HeaderValue::from_str(&format!("{}-{}\"", etag, suffix)).ok());
+    if let Some(etag) = etag {
+        headers.insert(header::ETAG, etag);
+    }
+}
+
+// Check whether an If-None-Match request header matches the response ETag. Responses are
+// revalidated before being compressed, so tags of compressed representations match too.
+fn etag_matches(if_none_match: &HeaderValue, etag: &HeaderValue) -> bool {
+    let etag = match etag.to_str() {
+        Ok(etag) => etag.trim_start_matches("W/"),
+        Err(_) => return false,
+    };
+    let uncompressed = |tag: &str| {
+        [ContentEncoding::Brotli, ContentEncoding::Gzip]
+            .iter()
+            .find_map(|encoding| tag.strip_suffix(&format!("-{}\"", encoding.name())[..]))
+            .map(|tag| format!("{}\"", tag))
+    };
+    if_none_match.to_str().map_or(false, |if_none_match| {
+        if_none_match
+            .split(',')
+            .map(|tag| tag.trim().trim_start_matches("W/"))
+            .any(|tag| {
+                tag == "*" || tag == etag || uncompressed(tag).map_or(false, |tag| tag == etag)
+            })
+    })
+}
+
+// Allow cross-origin requests from a comma-separated allowlist of origins, or from any origin
+// with `*`. Browsers only accept a single origin, so the request's Origin is echoed back when
+// it's in the list, and the response is marked as varying by it.
+fn set_cors_headers(resp: &mut Response<Body>, origins: &str, origin: Option<&HeaderValue>) {
+    resp.headers_mut().insert(
+        header::ACCESS_CONTROL_EXPOSE_HEADERS,
+        HeaderValue::from_str(&[CHAIN_GENERATION_HEADER, TOTAL_COUNT_HEADER].join(", ")).unwrap(),
+    );
+    let mut allowed = origins.split(',').map(str::trim);
+    if allowed.clone().any(|allowed| allowed == "*") {
+        resp.headers_mut().insert(
+            header::ACCESS_CONTROL_ALLOW_ORIGIN,
+            HeaderValue::from_static("*"),
+        );
+        return;
+    }
+    resp.headers_mut()
+        .append(header::VARY, HeaderValue::from_static("Origin"));
+    if let Some(origin) = origin {
+        let is_allowed = origin.to_str().map_or(false, |origin| {
+            allowed.any(|allowed| allowed.eq_ignore_ascii_case(origin))
+        });
+        if is_allowed {
+            resp.headers_mut()
+                .insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin.clone());
+        }
+    }
+}
+
+// Responses smaller than this aren't worth compressing
+const MIN_COMPRESS_SIZE: usize = 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ContentEncoding {
+    Brotli,
+    Gzip,
+}
+
+impl ContentEncoding {
+    fn name(self) -> &'static str {
+        match self {
+            ContentEncoding::Brotli => "br",
+            ContentEncoding::Gzip => "gzip",
+        }
+    }
+}
+
+// Pick the preferred supported encoding listed in the Accept-Encoding header, ignoring the ones
+// explicitly refused with q=0
+fn negotiate_encoding(headers: &HeaderMap) -> Option<ContentEncoding> {
+    let accept_encoding = headers.get(header::ACCEPT_ENCODING)?.to_str().ok()?;
+    let accepts = |name: &str| {
+        accept_encoding.split(',').any(|coding| {
+            let mut parts = coding.split(';').map(str::trim);
+            parts
+                .next()
+                .map_or(false, |coding| coding.eq_ignore_ascii_case(name))
+                && !parts.any(|param| {
+                    param
+                        .strip_prefix("q=")
+                        .and_then(|q| q.parse::<f32>().ok())
+                        .map_or(false, |q| q == 0.0)
+                })
+        })
+    };
+    if accepts("br") {
+        Some(ContentEncoding::Brotli)
+    } else if accepts("gzip") {
+        Some(ContentEncoding::Gzip)
+    } else {
+        None
+    }
+}
+
+fn encode_body(data: &[u8], encoding: ContentEncoding) -> std::io::Result<Vec<u8>> {
+    use std::io::Write;
+    match encoding {
+        ContentEncoding::Brotli => {
+            // favor speed over ratio, the responses are produced on the fly
+            let mut encoder = brotli::CompressorWriter::new(Vec::new(), 4096, 5, 22);
+            encoder.write_all(data)?;
+            Ok(encoder.into_inner())
+        }
+        ContentEncoding::Gzip => {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(data)?;
+            encoder.finish()
+        }
+    }
+}
+
+// Compress successful responses for clients that accept it. Small responses, partial content
+// and (unless enabled) binary bodies are sent as is.
+async fn compress_response(
+    mut resp: Response<Body>,
+    encoding: Option<ContentEncoding>,
+    config: &Config,
+    saved_bytes: &Counter,
+) -> Response<Body> {
+    let headers = resp.headers();
+    let compressible = resp.status() == StatusCode::OK
+        && !headers.contains_key(header::CONTENT_ENCODING)
+        && !headers.contains_key(header::CONTENT_RANGE)
+        && (config.rest_compress_binary
+            || headers
+                .get(header::CONTENT_TYPE)
+                .map_or(true, |content_type| {
+                    content_type != "application/octet-stream"
+                }));
+    if !compressible {
+        return resp;
+    }
+    resp.headers_mut()
+        .append(header::VARY, HeaderValue::from_static("Accept-Encoding"));
+    let encoding = match encoding {
+        Some(encoding) => encoding,
+        None => return resp,
+    };
+
+    let (mut parts, body) = resp.into_parts();
+    let body = match hyper::body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(e) => {
+            warn!("failed reading response body for compression: {}", e);
+            return error_response(
+                HttpError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+                false,
+            );
+        }
+    };
+    if body.len() < MIN_COMPRESS_SIZE {
+        return Response::from_parts(parts, Body::from(body));
+    }
+    match tokio::task::block_in_place(|| encode_body(&body, encoding)) {
+        Ok(compressed) if compressed.len() < body.len() => {
+            saved_bytes.inc_by((body.len() - compressed.len()) as u64);
+            parts.headers.insert(
+                header::CONTENT_ENCODING,
+                HeaderValue::from_static(encoding.name()),
+            );
+            parts.headers.remove(header::CONTENT_LENGTH);
+            // strong validators must differ between the encoded and identity representations
+            suffix_etag(&mut parts.headers, encoding.name());
+            Response::from_parts(parts, Body::from(compressed))
+        }
+        Ok(_) => Response::from_parts(parts, Body::from(body)),
+        Err(e) => {
+            warn!("failed compressing response body: {}", e);
+            Response::from_parts(parts, Body::from(body))
+        }
+    }
+}
+
+// Re-encode a JSON response as CBOR. This goes through the JSON representation rather than
+// serializing the values directly, as hashes and scripts serialize as raw bytes rather than hex
+// strings to non human-readable formats, which would break field parity between the encodings.
+async fn cbor_response(resp: Response<Body>) -> Response<Body> {
+    let is_json = resp
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .map_or(false, |content_type| content_type == "application/json");
+    if !is_json {
+        return resp;
+    }
+    let (mut parts, body) = resp.into_parts();
+    parts
+        .headers
+        .append(header::VARY, HeaderValue::from_static("Accept"));
+    let body = match hyper::body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(e) => {
+            warn!("failed reading response body for CBOR encoding: {}", e);
+            return error_response(
+                HttpError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+                false,
+            );
+        }
+    };
+    // HEAD responses have no body to re-encode
+    if body.is_empty() {
+        return Response::from_parts(parts, Body::from(body));
+    }
+    match tokio::task::block_in_place(|| json_to_cbor(&body)) {
+        Ok(cbor) => {
+            parts.headers.insert(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("application/cbor"),
+            );
+            parts.headers.remove(header::CONTENT_LENGTH);
+            // the CBOR representation gets its own validator
+            suffix_etag(&mut parts.headers, "cbor");
+            Response::from_parts(parts, Body::from(cbor))
+        }
+        Err(e) => {
+            warn!("failed encoding response body as CBOR: {}", e);
+            Response::from_parts(parts, Body::from(body))
+        }
+    }
+}
+
+fn json_to_cbor(json: &[u8]) -> Result<Vec<u8>, String> {
+    let value: serde_json::Value = serde_json::from_slice(json).map_err(|e| e.to_string())?;
+    let mut cbor = Vec::with_capacity(json.len());
+    ciborium::ser::into_writer(&value, &mut cbor).map_err(|e| e.to_string())?;
+    Ok(cbor)
+}
+
+// Turn the response into a 304 Not Modified if the client already has its current version.
+// Cache-Control and ETag are kept, as required for 304 responses.
+fn revalidate(resp: &mut Response<Body>, if_none_match: &HeaderValue) {
+    let not_modified = resp.status() == StatusCode::OK
+        && resp
+            .headers()
+            .get(header::ETAG)
+            .map_or(false, |etag| etag_matches(if_none_match, etag));
+    if not_modified {
+        *resp.status_mut() = StatusCode::NOT_MODIFIED;
+        *resp.body_mut() = Body::empty();
+        resp.headers_mut().remove(header::CONTENT_TYPE);
+    }
+}
+
+fn to_json_string<T: Serialize>(
+    value: &T,
+    query_params: &HashMap<String, String>,
+) -> Result<String, HttpError> {
+    Ok(
+        if query_params
+            .get("prettyprint")
+            .map_or(false, |v| v == "true")
+        {
+            serde_json::to_string_pretty(value)?
+        } else {
+            serde_json::to_string(value)?
+        },
+    )
+}
+
+// Keep only the given top-level fields of an object, or of each object in an array, for
+// `?fields=a,b,c`. Unknown field names are ignored.
+fn filter_fields(value: &mut serde_json::Value, fields: &HashSet<&str>) {
+    match value {
+        serde_json::Value::Object(map) => map.retain(|key, _| fields.contains(key.as_str())),
+        serde_json::Value::Array(items) => {
+            for item in items {
+                if let serde_json::Value::Object(map) = item {
+                    map.retain(|key, _| fields.contains(key.as_str()));
+                }
+            }
+        }
+        _ => (),
+    }
+}
+
+// Serve a raw transaction as binary or hex, honoring single-range `Range: bytes=` requests
+// (applied to the chosen representation) so that large downloads can be resumed
+fn raw_tx_response(rawtx: Vec<u8>, as_hex: bool, headers: &HeaderMap, ttl: u32) -> Response<Body> {
+    let (content_type, body) = if as_hex {
+        ("text/plain", hex::encode(rawtx).into_bytes())
+    } else {
+        ("application/octet-stream", rawtx)
+    };
+
+    let builder = Response::builder()
+        .header("Content-Type", content_type)
+        .header("Cache-Control", format!("public, max-age={:}", ttl))
+        .header("Accept-Ranges", "bytes")
+        .header("Vary", "Accept")
+        .header("X-Powered-By", &**VERSION_STRING);
+
+    let range = headers.get(header::RANGE).map(|range| {
+        range
+            .to_str()
+            .map_err(|_| ())
+            .and_then(|r| parse_range(r, body.len()))
+    });
+
+    match range {
+        None => builder.status(StatusCode::OK).body(Body::from(body)),
+        Some(Ok((start, end))) => builder
+            .status(StatusCode::PARTIAL_CONTENT)
+            .header(
+                "Content-Range",
+                format!("bytes {}-{}/{}", start, end, body.len()),
+            )
+            .body(Body::from(body[start..=end].to_vec())),
+        Some(Err(())) => builder
+            .status(StatusCode::RANGE_NOT_SATISFIABLE)
+            .header("Content-Range", format!("bytes */{}", body.len()))
+            .body(Body::empty()),
+    }
+    .unwrap()
+}
+
+// Parse a `Range: bytes=` header value into an inclusive (start, end) byte range within a body
+// of the given length. Supports `start-end`, open-ended `start-` and suffix `-len` ranges.
+// Multiple ranges are not supported and, like unsatisfiable ranges, are rejected.
+fn parse_range(range: &str, len: usize) -> Result<(usize, usize), ()> {
+    let spec = range.trim().strip_prefix("bytes=").ok_or(())?;
+    if spec.contains(',') {
+        return Err(());
+    }
+    let (start, end) = spec.split_once('-').ok_or(())?;
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", "") => return Err(()),
+        ("", suffix) => {
+            let suffix: usize = suffix.parse().map_err(|_| ())?;
+            if suffix == 0 {
+                return Err(());
+            }
+            (len.saturating_sub(suffix), len.checked_sub(1).ok_or(())?)
+        }
+        (start, "") => (
+            start.parse().map_err(|_| ())?,
+            len.checked_sub(1).ok_or(())?,
+        ),
+        (start, end) => {
+            let end: usize = end.parse().map_err(|_| ())?;
+            (
+                start.parse().map_err(|_| ())?,
+                end.min(len.checked_sub(1).ok_or(())?),
+            )
+        }
+    };
+    if start > end || start >= len {
+        return Err(());
+    }
+    Ok((start, end))
+}
+
+// Whether the Accept header explicitly lists the given mime type
+fn accepts(headers: &HeaderMap, mime_type: &str) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .map_or(false, |accept| {
+            accept
+                .split(',')
+                .filter_map(|mime| mime.split(';').next())
+                .any(|mime| mime.trim().eq_ignore_ascii_case(mime_type))
+        })
+}
+
+fn accepts_json(headers: &HeaderMap) -> bool {
+    accepts(headers, "application/json")
+}
+
+fn accepts_cbor(headers: &HeaderMap) -> bool {
+    accepts(headers, "application/cbor")
+}
+
+#[derive(Serialize, Debug)]
+struct BroadcastErrorValue {
+    code: Option<i64>,
+    message: String,
+    reject_reason: Option<String>,
+}
+
+fn broadcast_error(err: &errors::Error) -> (StatusCode, BroadcastErrorValue) {
+    match err.kind() {
+        errors::ErrorKind::Connection(_) | errors::ErrorKind::Interrupt(_) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            BroadcastErrorValue {
+                code: None,
+                message: "Daemon unavailable".to_string(),
+                reject_reason: None,
+            },
+        ),
+        _ => parse_broadcast_rpc_error(err.description()),
+    }
+}
+
+// Map the daemon's sendrawtransaction JSON-RPC error onto an HTTP status, based on the
+// error codes defined in bitcoind's rpc/protocol.h
+fn parse_broadcast_rpc_error(description: &str) -> (StatusCode, BroadcastErrorValue) {
+    let rpc_error: Option<serde_json::Value> = description
+        .split_once("RPC error: ")
+        .and_then(|(_, error)| serde_json::from_str(error).ok());
+    let code = rpc_error.as_ref().and_then(|error| error["code"].as_i64());
+    let message = rpc_error
+        .as_ref()
+        .and_then(|error| error["message"].as_str())
+        .unwrap_or(description)
+        .to_string();
+    // policy/consensus rejections look like "<reason>, <details>"
+    let reject_reason = match code {
+        Some(-25) | Some(-26) => message.split(", ").next().map(str::to_string),
+        _ => None,
+    };
+
+    let status = match (code, reject_reason.as_deref()) {
+        // RPC_DESERIALIZATION_ERROR
+        (Some(-22), _) => StatusCode::BAD_REQUEST,
+        // RPC_VERIFY_ERROR (e.g. missing inputs) and RPC_VERIFY_ALREADY_IN_CHAIN
+        (Some(-25), _) | (Some(-27), _) => StatusCode::CONFLICT,
+        // RPC_VERIFY_REJECTED
+        (Some(-26), Some("txn-mempool-conflict"))
+        | (Some(-26), Some("txn-already-known"))
+        | (Some(-26), Some("txn-already-in-mempool")) => StatusCode::CONFLICT,
+        (Some(-26), _) => StatusCode::UNPROCESSABLE_ENTITY,
+        _ => StatusCode::BAD_REQUEST,
+    };
+
+    (
+        status,
+        BroadcastErrorValue {
+            code,
+            message,
+            reject_reason,
+        },
+    )
+}
+
+fn is_form_urlencoded(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        // ignore parameters such as the charset
+        .and_then(|value| value.split(';').next())
+        .map_or(false, |mime| {
+            mime.trim()
+                .eq_ignore_ascii_case("application/x-www-form-urlencoded")
+        })
+}
+
+fn json_response<T: Serialize>(
+    value: T,
+    ttl: u32,
+    query_params: &HashMap<String, String>,
+) -> Result<Response<Body>, HttpError> {
+    let body = json_body(value, query_params)?;
+    Ok(json_body_response(body, ttl))
+}
+
+// Like json_response(), but tagged with a hash of the serialized body so that pollers of
+// tip-dependent endpoints can revalidate with If-None-Match and get a 304 when nothing changed
+fn json_response_etag<T: Serialize>(
+    value: T,
+    ttl: u32,
+    query_params: &HashMap<String, String>,
+) -> Result<Response<Body>, HttpError> {
+    let body = json_body(value, query_params)?;
+    let tag = sha256::Hash::hash(body.as_bytes()).to_hex();
+    Ok(with_etag(json_body_response(body, ttl), &tag[..32]))
+}
+
+// HashMaps serialize in an arbitrary order that differs between instances, so the estimates are
+// sorted by target to get the same body (and ETag) for the same estimates
+fn fee_estimates_response(
+    estimates: HashMap<u16, f64>,
+    query_params: &HashMap<String, String>,
+) -> Result<Response<Body>, HttpError> {
+    let estimates: BTreeMap<u16, f64> = estimates.into_iter().collect();
+    json_response_etag(estimates, TTL_SHORT, query_params)
+}
+
+fn json_body<T: Serialize>(
+    value: T,
+    query_params: &HashMap<String, String>,
+) -> Result<String, HttpError> {
+    // only go through serde_json::Value when filtering, to keep the common path cheap
+    match query_params.get("fields") {
+        Some(fields) => {
+            let mut value = serde_json::to_value(&value)?;
+            filter_fields(&mut value, &fields.split(',').map(str::trim).collect());
+            to_json_string(&value, query_params)
+        }
+        None => to_json_string(&value, query_params),
+    }
+}
+
+fn json_body_response(body: String, ttl: u32) -> Response<Body> {
+    Response::builder()
         .header("Content-Type", "application/json")
         .header("Cache-Control", format!("public, max-age={:}", ttl))
         .header("X-Powered-By", &**VERSION_STRING)
-        .body(Body::from(value))
-        .unwrap())
+        .body(Body::from(body))
+        .unwrap()
 }
 
 // fn json_maybe_error_response<T: Serialize>(
@@ -1835,6 +5852,7 @@ fn blocks(
     query: &Query,
     config: &Config,
     start_height: Option<usize>,
+    query_params: &HashMap<String, String>,
 ) -> Result<Response<Body>, HttpError> {
     let mut values = Vec::new();
     let mut current_hash = match start_height {
@@ -1868,7 +5886,7 @@ fn blocks(
             break;
         }
     }
-    json_response(values, TTL_SHORT)
+    json_response_etag(values, TTL_SHORT, query_params)
 }
 
 fn to_scripthash(
@@ -1877,12 +5895,133 @@ fn to_scripthash(
     network: Network,
 ) -> Result<FullHash, HttpError> {
     match script_type {
-        "address" => address_to_scripthash(script_str, network),
-        "scripthash" => parse_scripthash(script_str),
+        "address" => address_to_scripthash(script_str, network).map_err(|err| {
+            if parse_scripthash(script_str).is_ok() {
+                HttpError::from("Looks like a scripthash; use /scripthash/ instead".to_string())
+            } else {
+                err
+            }
+        }),
+        "scripthash" => parse_scripthash(script_str).map_err(|err| {
+            if looks_like_address(script_str) {
+                HttpError::from("Looks like an address; use /address/ instead".to_string())
+            } else {
+                err
+            }
+        }),
         _ => bail!("Invalid script type".to_string()),
     }
 }
 
+// Sanity checks for raw transactions submitted to testmempoolaccept/submitpackage, which both
+// take at most 25 transactions
+fn precheck_txhexes(txhexes: &[String]) -> Result<(), HttpError> {
+    if txhexes.len() > 25 {
+        bail!(HttpError::from(
+            "Exceeded maximum of 25 transactions".to_string()
+        ));
+    }
+
+    txhexes.iter().enumerate().try_for_each(|(index, txhex)| {
+        // each transaction must be of reasonable size (more than 60 bytes, within 400kWU standardness limit)
+        if !(120..800_000).contains(&txhex.len()) {
+            Result::Err(HttpError::from(format!(
+                "Invalid transaction size for item {}",
+                index
+            )))
+        } else {
+            // must be a valid hex string
+            Vec::<u8>::from_hex(txhex)
+                .map_err(|_| HttpError::from(format!("Invalid transaction hex for item {}", index)))
+                .map(|_| ())
+        }
+    })
+}
+
+// Disabled endpoints are given as path prefixes matched segment by segment (so that `/tx`
+// covers `/tx/:txid/status` but not `/txs`), optionally preceded by the method they apply to.
+fn is_endpoint_disabled(method: &Method, path: &[&str], disabled: &[String]) -> bool {
+    disabled.iter().any(|entry| {
+        let (entry_method, prefix) = match entry.split_once(' ') {
+            Some((entry_method, prefix)) => (Some(entry_method.trim()), prefix.trim()),
+            None => (None, entry.as_str()),
+        };
+        let prefix: Vec<&str> = prefix
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .collect();
+        entry_method.map_or(true, |m| m.eq_ignore_ascii_case(method.as_str()))
+            && !prefix.is_empty()
+            && path.len() >= prefix.len()
+            && path.iter().zip(&prefix).all(|(a, b)| a == b)
+    })
+}
+
+fn is_internal_access_allowed(peer: Peer, access: &InternalApiAccess) -> bool {
+    match (peer, access) {
+        (Peer::Unix, _) | (_, InternalApiAccess::Any) => true,
+        (Peer::Tcp(_), InternalApiAccess::UnixOnly) => false,
+        (Peer::Tcp(addr), InternalApiAccess::Cidrs(cidrs)) => {
+            cidrs.iter().any(|cidr| cidr.contains(addr.ip()))
+        }
+    }
+}
+
+// Admin endpoints are always allowed over the unix socket, which is only reachable locally.
+// Over TCP, they require the X-Admin-Token header to match the configured secret.
+fn is_admin_request(headers: &HeaderMap, peer: Peer, config: &Config) -> bool {
+    if let Peer::Unix = peer {
+        return true;
+    }
+    match (&config.rest_admin_token, headers.get("X-Admin-Token")) {
+        (Some(token), Some(provided)) => constant_time_eq(provided.as_bytes(), token.as_bytes()),
+        _ => false,
+    }
+}
+
+// Compare secrets without returning early at the first differing byte, so that the time taken
+// doesn't reveal how much of a guessed token is right. Only the length may leak.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+// The maxfeerate passed through to bitcoind, in BTC/kvB (0 disables the node's fee rate check)
+fn parse_maxfeerate(query_params: &HashMap<String, String>) -> Result<Option<f64>, HttpError> {
+    query_params
+        .get("maxfeerate")
+        .map(|s| {
+            s.parse::<f64>()
+                .ok()
+                .filter(|rate| rate.is_finite() && *rate >= 0.0)
+                .ok_or_else(|| {
+                    HttpError::from(format!(
+                        "Invalid maxfeerate '{}', expected a non-negative number in BTC/kvB",
+                        s
+                    ))
+                    .with_code("invalid_number")
+                })
+        })
+        .transpose()
+}
+
+// Mirrors bitcoind's sendrawtransaction maxburnamount check, which rejects transactions
+// with provably unspendable outputs worth more than the given amount (in sats)
+fn exceeds_burn_amount(tx: &Transaction, max_burn: u64) -> bool {
+    tx.output.iter().any(|txout| {
+        #[cfg(not(feature = "liquid"))]
+        let value = Some(txout.value);
+        #[cfg(feature = "liquid")]
+        let value = txout.value.explicit();
+        txout.script_pubkey.is_provably_unspendable() && value.map_or(false, |v| v > max_burn)
+    })
+}
+
+// Whether the string parses as an address, regardless of its network
+fn looks_like_address(addr: &str) -> bool {
+    use std::str::FromStr;
+    address::Address::from_str(addr).is_ok()
+}
+
 fn address_to_scripthash(addr: &str, network: Network) -> Result<FullHash, HttpError> {
     #[cfg(not(feature = "liquid"))]
     let addr = address::Address::from_str(addr)?;
@@ -1907,12 +6046,21 @@ fn address_to_scripthash(addr: &str, network: Network) -> Result<FullHash, HttpE
     let is_expected_net = addr.params == network.address_params();
 
     if !is_expected_net {
-        bail!(HttpError::from("Address on invalid network".to_string()))
+        bail!(HttpError::from("Address on invalid network".to_string()).with_code("wrong_network"))
     }
 
     Ok(compute_script_hash(&addr.script_pubkey()))
 }
 
+fn parse_outpoint(outpoint: &str) -> Result<OutPoint, HttpError> {
+    let invalid = || HttpError::from(format!("Invalid outpoint '{}'", outpoint));
+    let (txid, vout) = outpoint.split_once(':').ok_or_else(invalid)?;
+    Ok(OutPoint {
+        txid: Txid::from_hex(txid).map_err(|_| invalid())?,
+        vout: vout.parse().map_err(|_| invalid())?,
+    })
+}
+
 fn parse_scripthash(scripthash: &str) -> Result<FullHash, HttpError> {
     let bytes = hex::decode(scripthash)?;
     if bytes.len() != 32 {
@@ -1922,6 +6070,20 @@ fn parse_scripthash(scripthash: &str) -> Result<FullHash, HttpError> {
     }
 }
 
+// Parse the ?order= query param, returning true for ascending (oldest-first) order.
+//
+// The `last_seen_txid` cursor of the chain history routes always refers to the last tx of the
+// previous page in the chosen order: with `order=asc` the next page continues with the txs
+// confirmed after it, with the default `order=desc` it continues with the txs confirmed before it.
+// Cursors therefore shouldn't be reused across orders.
+fn is_ascending_order(query_params: &HashMap<String, String>) -> Result<bool, HttpError> {
+    match query_params.get("order").map(String::as_str) {
+        None | Some("desc") => Ok(false),
+        Some("asc") => Ok(true),
+        Some(order) => Err(HttpError::from(format!("invalid order: {}", order))),
+    }
+}
+
 #[inline]
 fn multi_address_too_long(body: &hyper::body::Bytes) -> bool {
     // ("",) (3) (quotes and comma between each entry)
@@ -1931,53 +6093,105 @@ fn multi_address_too_long(body: &hyper::body::Bytes) -> bool {
     body.len() > (8 + 64) * MULTI_ADDRESS_LIMIT
 }
 
+// The optional third field is a stable, machine-readable error code for the JSON error envelope.
+// Errors without one get a generic code derived from their status.
 #[derive(Debug)]
-struct HttpError(StatusCode, String);
+struct HttpError(StatusCode, String, Option<&'static str>);
 
 impl HttpError {
+    fn new(status: StatusCode, msg: String) -> Self {
+        HttpError(status, msg, None)
+    }
+
     fn not_found(msg: String) -> Self {
-        HttpError(StatusCode::NOT_FOUND, msg)
+        HttpError(StatusCode::NOT_FOUND, msg, Some("not_found"))
+    }
+
+    fn cancelled() -> Self {
+        HttpError(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Request cancelled".to_string(),
+            Some("cancelled"),
+        )
+    }
+
+    fn with_code(mut self, code: &'static str) -> Self {
+        self.2 = Some(code);
+        self
+    }
+
+    fn code(&self) -> &'static str {
+        self.2.unwrap_or(match self.0 {
+            StatusCode::BAD_REQUEST => "bad_request",
+            StatusCode::NOT_FOUND => "not_found",
+            StatusCode::PAYLOAD_TOO_LARGE => "body_too_long",
+            StatusCode::UNPROCESSABLE_ENTITY => "unprocessable",
+            StatusCode::NOT_IMPLEMENTED => "not_implemented",
+            StatusCode::SERVICE_UNAVAILABLE => "unavailable",
+            StatusCode::GATEWAY_TIMEOUT => "timeout",
+            status if status.is_server_error() => "internal_error",
+            _ => "error",
+        })
     }
 }
 
 impl From<String> for HttpError {
     fn from(msg: String) -> Self {
-        HttpError(StatusCode::BAD_REQUEST, msg)
+        HttpError::new(StatusCode::BAD_REQUEST, msg)
     }
 }
 impl From<ParseIntError> for HttpError {
     fn from(_e: ParseIntError) -> Self {
         //HttpError::from(e.description().to_string())
-        HttpError::from("Invalid number".to_string())
+        HttpError::from("Invalid number".to_string()).with_code("invalid_number")
     }
 }
 impl From<HashError> for HttpError {
     fn from(_e: HashError) -> Self {
         //HttpError::from(e.description().to_string())
-        HttpError::from("Invalid hash string".to_string())
+        HttpError::from("Invalid hash string".to_string()).with_code("invalid_hash")
     }
 }
 impl From<FromHexError> for HttpError {
     fn from(_e: FromHexError) -> Self {
         //HttpError::from(e.description().to_string())
-        HttpError::from("Invalid hex string".to_string())
+        HttpError::from("Invalid hex string".to_string()).with_code("invalid_hex")
     }
 }
 impl From<bitcoin::hashes::hex::Error> for HttpError {
     fn from(_e: bitcoin::hashes::hex::Error) -> Self {
         //HttpError::from(e.description().to_string())
-        HttpError::from("Invalid hex string".to_string())
+        HttpError::from("Invalid hex string".to_string()).with_code("invalid_hex")
     }
 }
 impl From<bitcoin::util::address::Error> for HttpError {
     fn from(_e: bitcoin::util::address::Error) -> Self {
         //HttpError::from(e.description().to_string())
-        HttpError::from("Invalid Bitcoin address".to_string())
+        HttpError::from("Invalid Bitcoin address".to_string()).with_code("invalid_address")
     }
 }
 impl From<errors::Error> for HttpError {
     fn from(e: errors::Error) -> Self {
         warn!("errors::Error: {:?}", e);
+        if let errors::ErrorKind::MethodNotFound(method) = e.kind() {
+            return HttpError::new(
+                StatusCode::NOT_IMPLEMENTED,
+                format!("The daemon does not support the {} RPC", method),
+            );
+        }
+        if let errors::ErrorKind::Cancelled = e.kind() {
+            return HttpError::cancelled();
+        }
+        if let errors::ErrorKind::TooManyUtxos(limit) = e.kind() {
+            return HttpError::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!(
+                    "Too many unspent transaction outputs (>{}). \
+                    Use the paginated /txs/chain history endpoints instead.",
+                    limit
+                ),
+            );
+        }
         match e.description().to_string().as_ref() {
             "getblock RPC error: {\"code\":-5,\"message\":\"Block not found\"}" => {
                 HttpError::not_found("Block not found".to_string())
@@ -2004,7 +6218,7 @@ impl From<std::string::FromUtf8Error> for HttpError {
 #[cfg(feature = "liquid")]
 impl From<address::AddressError> for HttpError {
     fn from(e: address::AddressError) -> Self {
-        HttpError::from(e.to_string())
+        HttpError::from(e.to_string()).with_code("invalid_address")
     }
 }
 
@@ -2076,6 +6290,700 @@ mod tests {
         assert!(err.is_err());
     }
 
+    #[test]
+    fn test_etag_matches() {
+        use super::etag_matches;
+        use hyper::header::HeaderValue;
+
+        let etag = HeaderValue::from_static("\"abcd\"");
+
+        assert!(etag_matches(&HeaderValue::from_static("\"abcd\""), &etag));
+        assert!(etag_matches(&HeaderValue::from_static("W/\"abcd\""), &etag));
+        assert!(etag_matches(
+            &HeaderValue::from_static("\"ef\", \"abcd\""),
+            &etag
+        ));
+        assert!(etag_matches(&HeaderValue::from_static("*"), &etag));
+        assert!(!etag_matches(&HeaderValue::from_static("\"ef\""), &etag));
+        assert!(!etag_matches(&HeaderValue::from_static("abcd"), &etag));
+        // tags of compressed representations
+        assert!(etag_matches(
+            &HeaderValue::from_static("\"abcd-gzip\""),
+            &etag
+        ));
+        assert!(etag_matches(
+            &HeaderValue::from_static("W/\"abcd-br\""),
+            &etag
+        ));
+        assert!(!etag_matches(
+            &HeaderValue::from_static("\"abcd-cbor\""),
+            &etag
+        ));
+    }
+
+    #[test]
+    fn test_fee_estimates_etag() {
+        use super::fee_estimates_response;
+        use std::collections::HashMap;
+
+        let targets: Vec<u16> = (1..=25).chain(vec![144, 504, 1008]).collect();
+        let first: HashMap<u16, f64> = targets.iter().map(|&t| (t, 1.0 + t as f64)).collect();
+        let second: HashMap<u16, f64> =
+            targets.iter().rev().map(|&t| (t, 1.0 + t as f64)).collect();
+
+        let etag = |estimates| {
+            fee_estimates_response(estimates, &HashMap::new())
+                .unwrap()
+                .headers()
+                .get(hyper::header::ETAG)
+                .cloned()
+                .unwrap()
+        };
+        assert_eq!(etag(first), etag(second));
+    }
+
+    #[test]
+    fn test_route_label() {
+        use super::route_label;
+
+        let txid = "ab".repeat(32);
+        assert_eq!(
+            route_label(&format!("/tx/{}/outspends", txid)),
+            "/tx/:txid/outspends"
+        );
+        assert_eq!(
+            route_label(&format!("/tx/{}/vin/3", txid)),
+            "/tx/:txid/vin/:param"
+        );
+        assert_eq!(
+            route_label("/address/bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq/txs/chain"),
+            "/address/:address/txs/chain"
+        );
+        assert_eq!(route_label("/block-height/800000"), "/block-height/:height");
+        assert_eq!(route_label("/blocks/tip/height"), "/blocks/tip/height");
+        // unknown routes all share a label
+        assert_eq!(route_label("/wp-login.php"), "other");
+        assert_eq!(route_label("/"), "other");
+        assert_eq!(route_label("/tx/a/b/c/d/e/f"), "other");
+        assert_eq!(route_label("/tx/tx/tx/tx"), "other");
+        assert_eq!(route_label(&format!("/tx/{}/foo", txid)), "other");
+        // the version prefix is kept in the label
+        assert_eq!(
+            route_label(&format!("/v1/tx/{}/outspends", txid)),
+            "/v1/tx/:txid/outspends"
+        );
+        assert_eq!(route_label("/v1"), "other");
+    }
+
+    #[test]
+    fn test_split_api_version() {
+        use super::{split_api_version, ResponseOptions};
+
+        let v1 = ResponseOptions { v1: true };
+        assert_eq!(split_api_version("/v1/tx/abcd"), ("/tx/abcd", v1));
+        assert_eq!(split_api_version("/v1"), ("", v1));
+        assert_eq!(
+            split_api_version("/tx/abcd"),
+            ("/tx/abcd", ResponseOptions::default())
+        );
+        assert_eq!(
+            split_api_version("/v10/tx"),
+            ("/v10/tx", ResponseOptions::default())
+        );
+    }
+
+    // A regtest chain of only the genesis block, indexed from a fake bitcoind whose mempool
+    // holds one tx spending the genesis coinbase
+    #[cfg(not(feature = "liquid"))]
+    struct Fixture {
+        query: crate::new_index::Query,
+        config: std::sync::Arc<crate::config::Config>,
+        genesis: crate::chain::Block,
+        mempool_tx: crate::chain::Transaction,
+        _db_dir: tempfile::TempDir,
+    }
+
+    #[cfg(not(feature = "liquid"))]
+    const FIXTURE_FEE: u64 = 10_000;
+
+    #[cfg(not(feature = "liquid"))]
+    impl Fixture {
+        fn new() -> Fixture {
+            use crate::chain::{OutPoint, Script, Transaction, TxIn, TxOut, Witness};
+            use crate::config::Config;
+            use crate::daemon::Daemon;
+            use crate::metrics::Metrics;
+            use crate::new_index::{ChainQuery, FetchFrom, Indexer, Mempool, Query, Store};
+            use crate::signal::Waiter;
+            use bitcoin::blockdata::constants::genesis_block;
+            use std::net::TcpListener;
+            use std::sync::{Arc, RwLock};
+
+            let genesis = genesis_block(bitcoin::Network::Regtest);
+            let coinbase = &genesis.txdata[0];
+            // a p2wpkh output
+            let mut script = vec![0x00, 0x14];
+            script.extend_from_slice(&[1; 20]);
+            let mempool_tx = Transaction {
+                version: 2,
+                lock_time: 0,
+                input: vec![TxIn {
+                    previous_output: OutPoint {
+                        txid: coinbase.txid(),
+                        vout: 0,
+                    },
+                    script_sig: Script::new(),
+                    sequence: 0xffffffff,
+                    witness: Witness::default(),
+                }],
+                output: vec![TxOut {
+                    value: coinbase.output[0].value - FIXTURE_FEE,
+                    script_pubkey: Script::from(script),
+                }],
+            };
+
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let daemon_addr = listener.local_addr().unwrap().to_string();
+            let (block, tx) = (genesis.clone(), mempool_tx.clone());
+            std::thread::spawn(move || serve_fake_daemon(listener, block, tx));
+
+            let db_dir = tempfile::tempdir().unwrap();
+            let dir = db_dir.path().to_str().unwrap();
+            let args = [
+                "electrs",
+                "--network",
+                "regtest",
+                "--db-dir",
+                dir,
+                "--daemon-dir",
+                dir,
+                "--daemon-rpc-addr",
+                &daemon_addr,
+                "--cookie",
+                "user:pass",
+            ];
+            let config = Arc::new(Config::parse_from(args.iter().map(|arg| arg.to_string())));
+            let metrics = Metrics::new(config.monitoring_addr);
+            let daemon = Arc::new(
+                Daemon::new(
+                    config.daemon_dir.clone(),
+                    config.blocks_dir.clone(),
+                    config.daemon_rpc_addr,
+                    config.cookie_getter(),
+                    config.network_type,
+                    config.magic,
+                    Waiter::start(),
+                    &metrics,
+                )
+                .unwrap(),
+            );
+            let store = Arc::new(Store::open(&config.db_path.join("newindex"), &config));
+            let mut indexer =
+                Indexer::open(Arc::clone(&store), FetchFrom::Bitcoind, &config, &metrics);
+            indexer.update(&daemon).unwrap();
+            let chain = Arc::new(ChainQuery::new(
+                store,
+                Arc::clone(&daemon),
+                &config,
+                &metrics,
+            ));
+            let mempool = Arc::new(RwLock::new(Mempool::new(
+                Arc::clone(&chain),
+                &metrics,
+                Arc::clone(&config),
+            )));
+            Mempool::update(&mempool, &daemon).unwrap();
+            let query = Query::new(chain, mempool, daemon, Arc::clone(&config), &metrics);
+
+            Fixture {
+                query,
+                config,
+                genesis,
+                mempool_tx,
+                _db_dir: db_dir,
+            }
+        }
+
+        fn get(&self, path: &str) -> Result<String, HttpError> {
+            use futures_util::FutureExt;
+            use hyper::{HeaderMap, Method};
+
+            let resp = super::handle_request(
+                Method::GET,
+                path.parse().unwrap(),
+                &HeaderMap::new(),
+                hyper::body::Bytes::new(),
+                super::Peer::Tcp(([127, 0, 0, 1], 0).into()),
+                &crate::util::CancelToken::default(),
+                &self.query,
+                &self.config,
+            )?;
+            // the bodies of non-streaming responses are ready right away
+            let body = hyper::body::to_bytes(resp.into_body())
+                .now_or_never()
+                .unwrap()
+                .unwrap();
+            Ok(String::from_utf8(body.to_vec()).unwrap())
+        }
+    }
+
+    // Answer bitcoind's JSON-RPC batches, as sent and parsed by Daemon's connections
+    #[cfg(not(feature = "liquid"))]
+    fn serve_fake_daemon(
+        listener: std::net::TcpListener,
+        genesis: crate::chain::Block,
+        mempool_tx: crate::chain::Transaction,
+    ) {
+        use std::io::{BufRead, BufReader, Read, Write};
+
+        for stream in listener.incoming() {
+            let stream = stream.unwrap();
+            let (genesis, mempool_tx) = (genesis.clone(), mempool_tx.clone());
+            std::thread::spawn(move || {
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut writer = stream;
+                loop {
+                    let mut content_length = 0;
+                    loop {
+                        let mut line = String::new();
+                        match reader.read_line(&mut line) {
+                            Ok(0) | Err(_) => return,
+                            Ok(_) => (),
+                        }
+                        let line = line.trim_end();
+                        if line.is_empty() {
+                            break;
+                        }
+                        if let Some(length) = line.strip_prefix("Content-Length: ") {
+                            content_length = length.parse().unwrap();
+                        }
+                    }
+                    let mut body = vec![0; content_length];
+                    reader.read_exact(&mut body).unwrap();
+                    let requests: Vec<Value> = serde_json::from_slice(&body).unwrap();
+                    let replies: Vec<Value> = requests
+                        .iter()
+                        .map(|request| {
+                            let method = request["method"].as_str().unwrap();
+                            match fake_rpc(method, &request["params"], &genesis, &mempool_tx) {
+                                Some(result) => {
+                                    json!({ "result": result, "error": null, "id": request["id"] })
+                                }
+                                None => json!({
+                                    "result": null,
+                                    "error": { "code": -32601, "message": "Method not found" },
+                                    "id": request["id"],
+                                }),
+                            }
+                        })
+                        .collect();
+                    let reply = Value::from(replies).to_string();
+                    // the content length counts the trailing newline
+                    let response = format!(
+                        "HTTP/1.1 200 OK\nContent-Length: {}\n\n{}\n",
+                        reply.len() + 1,
+                        reply
+                    );
+                    if writer.write_all(response.as_bytes()).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+    }
+
+    #[cfg(not(feature = "liquid"))]
+    fn fake_rpc(
+        method: &str,
+        params: &Value,
+        genesis: &crate::chain::Block,
+        mempool_tx: &crate::chain::Transaction,
+    ) -> Option<Value> {
+        use bitcoin::consensus::encode::serialize;
+        use bitcoin::hashes::hex::ToHex;
+
+        let hash = genesis.block_hash().to_hex();
+        Some(match method {
+            "getnetworkinfo" => json!({
+                "version": 250000,
+                "subversion": "/Satoshi:25.0.0/",
+                "relayfee": 0.00001,
+            }),
+            "getblockchaininfo" => json!({
+                "chain": "regtest",
+                "blocks": 0,
+                "headers": 0,
+                "bestblockhash": hash,
+                "pruned": false,
+                "verificationprogress": 1.0,
+                "initialblockdownload": false,
+            }),
+            "getmempoolinfo" => json!({ "loaded": true }),
+            "getbestblockhash" | "getblockhash" => json!(hash),
+            // headers are verbose unless asked otherwise
+            "getblockheader" if params[1] == false => {
+                json!(hex::encode(serialize(&genesis.header)))
+            }
+            "getblockheader" => json!({ "hash": hash, "height": 0 }),
+            "getblock" => json!(hex::encode(serialize(genesis))),
+            "getrawmempool" => json!([mempool_tx.txid()]),
+            "getrawtransaction" => json!(hex::encode(serialize(mempool_tx))),
+            _ => return None,
+        })
+    }
+
+    #[test]
+    #[cfg(not(feature = "liquid"))]
+    fn test_legacy_shapes() {
+        use crate::chain::Network;
+        use crate::util::ScriptToAddr;
+
+        let fixture = Fixture::new();
+        let block_hash = fixture.genesis.block_hash();
+        let coinbase_txid = fixture.genesis.txdata[0].txid();
+        let txid = fixture.mempool_tx.txid();
+        let output = &fixture.mempool_tx.output[0];
+        let address = output
+            .script_pubkey
+            .to_address_str(Network::Regtest)
+            .unwrap();
+
+        // unprefixed routes must keep responding exactly as before
+        let spend = format!(
+            r#"{{"spent":true,"txid":"{}","vin":0,"status":{{"confirmed":false}}}}"#,
+            txid
+        );
+        let fixtures = [
+            ("/blocks/tip/height".to_string(), "0".to_string()),
+            ("/blocks/tip/hash".to_string(), block_hash.to_string()),
+            (
+                format!("/block/{}/status", block_hash),
+                r#"{"in_best_chain":true,"height":0,"next_best":null}"#.to_string(),
+            ),
+            (
+                format!("/tx/{}/status", coinbase_txid),
+                format!(
+                    r#"{{"confirmed":true,"block_height":0,"block_hash":"{}","block_time":{}}}"#,
+                    block_hash, fixture.genesis.header.time
+                ),
+            ),
+            (format!("/tx/{}/outspend/0", coinbase_txid), spend.clone()),
+            (
+                format!("/tx/{}/outspends", coinbase_txid),
+                format!("[{}]", spend),
+            ),
+            (
+                format!("/address/{}/utxo", address),
+                format!(
+                    r#"[{{"txid":"{}","vout":0,"status":{{"confirmed":false}},"value":{}}}]"#,
+                    txid, output.value
+                ),
+            ),
+        ];
+        for (path, expected) in fixtures.iter() {
+            assert_eq!(&fixture.get(path).unwrap(), expected, "GET {}", path);
+        }
+        // txs keep the bare status of unconfirmed txs
+        let tx: Value =
+            serde_json::from_str(&fixture.get(&format!("/tx/{}", txid)).unwrap()).unwrap();
+        assert_eq!(tx["status"].to_string(), r#"{"confirmed":false}"#);
+        let path = format!("/address/{}/txs", address);
+        let txs: Value = serde_json::from_str(&fixture.get(&path).unwrap()).unwrap();
+        assert_eq!(txs[0]["status"].to_string(), r#"{"confirmed":false}"#);
+
+        // under /v1/, unconfirmed statuses carry the same mempool details as /tx/:txid/status
+        let status = fixture.get(&format!("/tx/{}/status", txid)).unwrap();
+        let status: Value = serde_json::from_str(&status).unwrap();
+        assert_eq!(status["fee"], FIXTURE_FEE);
+        assert!(status["first_seen"].is_u64());
+        let v1_statuses = [
+            (format!("/v1/tx/{}", txid), "/status"),
+            (format!("/v1/address/{}/txs", address), "/0/status"),
+            (format!("/v1/address/{}/utxo", address), "/0/status"),
+            (format!("/v1/tx/{}/outspend/0", coinbase_txid), "/status"),
+            (format!("/v1/tx/{}/outspends", coinbase_txid), "/0/status"),
+        ];
+        for (path, pointer) in v1_statuses.iter() {
+            let value: Value = serde_json::from_str(&fixture.get(path).unwrap()).unwrap();
+            assert_eq!(value.pointer(pointer), Some(&status), "GET {}", path);
+        }
+    }
+
+    #[test]
+    fn test_head_response() {
+        use super::{head_response, http_message, TTL_LONG, TTL_SHORT};
+        use hyper::body::HttpBody;
+        use hyper::{header, Body, Response, StatusCode};
+
+        // as served by /blocks/tip/height
+        let resp = head_response(http_message(StatusCode::OK, "800000", TTL_SHORT).unwrap());
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()[header::CONTENT_LENGTH], "6");
+        assert_eq!(resp.headers()[header::CONTENT_TYPE], "text/plain");
+        assert_eq!(
+            resp.headers()[header::CACHE_CONTROL],
+            format!("public, max-age={}", TTL_SHORT)
+        );
+        assert!(resp.body().is_end_stream());
+
+        // as served by /block/:hash/raw
+        let raw = vec![0u8; 285];
+        let resp = head_response(
+            Response::builder()
+                .header("Content-Type", "application/octet-stream")
+                .header("Cache-Control", format!("public, max-age={:}", TTL_LONG))
+                .body(Body::from(raw))
+                .unwrap(),
+        );
+        assert_eq!(resp.headers()[header::CONTENT_LENGTH], "285");
+        assert_eq!(
+            resp.headers()[header::CONTENT_TYPE],
+            "application/octet-stream"
+        );
+        assert!(resp.body().is_end_stream());
+    }
+
+    #[test]
+    fn test_set_cors_headers() {
+        use super::set_cors_headers;
+        use hyper::{header, header::HeaderValue, Body, Response};
+
+        let allowlist = "https://mempool.space, https://example.com";
+        let cors = |origins: &str, origin: Option<&'static str>| {
+            let mut resp = Response::new(Body::empty());
+            set_cors_headers(
+                &mut resp,
+                origins,
+                origin.map(HeaderValue::from_static).as_ref(),
+            );
+            resp
+        };
+
+        let resp = cors(allowlist, Some("https://example.com"));
+        assert_eq!(
+            resp.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://example.com"
+        );
+        assert_eq!(resp.headers()[header::VARY], "Origin");
+
+        for origin in &[Some("https://evil.com"), None] {
+            let resp = cors(allowlist, *origin);
+            assert!(resp
+                .headers()
+                .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+                .is_none());
+            assert_eq!(resp.headers()[header::VARY], "Origin");
+        }
+
+        let resp = cors("*", Some("https://evil.com"));
+        assert_eq!(resp.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+        assert!(resp.headers().get(header::VARY).is_none());
+    }
+
+    #[test]
+    fn test_internal_access() {
+        use super::{is_internal_access_allowed, Peer};
+        use crate::config::InternalApiAccess;
+
+        let tcp = |addr: &str| Peer::Tcp(addr.parse().unwrap());
+        let access: InternalApiAccess = "127.0.0.1, 10.0.0.0/8, fd00::/8".parse().unwrap();
+
+        assert!(is_internal_access_allowed(Peer::Unix, &access));
+        assert!(is_internal_access_allowed(tcp("127.0.0.1:1234"), &access));
+        assert!(is_internal_access_allowed(tcp("10.1.2.3:1234"), &access));
+        assert!(is_internal_access_allowed(
+            tcp("[::ffff:10.1.2.3]:1234"),
+            &access
+        ));
+        assert!(is_internal_access_allowed(tcp("[fd12::1]:1234"), &access));
+        assert!(!is_internal_access_allowed(tcp("127.0.0.2:1234"), &access));
+        assert!(!is_internal_access_allowed(tcp("11.0.0.1:1234"), &access));
+        assert!(!is_internal_access_allowed(tcp("[fe80::1]:1234"), &access));
+
+        let unix_only = InternalApiAccess::UnixOnly;
+        assert!(is_internal_access_allowed(Peer::Unix, &unix_only));
+        assert!(!is_internal_access_allowed(
+            tcp("127.0.0.1:1234"),
+            &unix_only
+        ));
+        assert!(is_internal_access_allowed(
+            tcp("1.2.3.4:1234"),
+            &InternalApiAccess::Any
+        ));
+
+        assert!("10.0.0.0/33".parse::<InternalApiAccess>().is_err());
+        assert!("localhost".parse::<InternalApiAccess>().is_err());
+    }
+
+    #[test]
+    fn test_serves_stale() {
+        use super::serves_stale;
+
+        assert!(serves_stale(&["health"]));
+        assert!(serves_stale(&["ready"]));
+        assert!(serves_stale(&["internal", "sync-status"]));
+        assert!(!serves_stale(&["blocks", "tip", "height"]));
+        assert!(!serves_stale(&["address", "bc1qxyz"]));
+    }
+
+    #[test]
+    fn test_is_heavy_route() {
+        use super::is_heavy_route;
+
+        for path in &[
+            "/address/bc1qxyz/txs",
+            "/address/bc1qxyz/txs/chain/abcd",
+            "/scripthash/abcd/utxo",
+            "/address/bc1qxyz/summary",
+            "/block/abcd/txs/25",
+            "/internal/mempool/txs/all",
+            "/internal/block/abcd/txs",
+            "/batch",
+        ] {
+            assert!(is_heavy_route(path), "{}", path);
+        }
+        for path in &[
+            "/address/bc1qxyz",
+            "/blocks/tip/height",
+            "/block/abcd/txids",
+            "/tx/abcd",
+        ] {
+            assert!(!is_heavy_route(path), "{}", path);
+        }
+    }
+
+    #[cfg(not(feature = "liquid"))]
+    #[test]
+    fn test_body_limit() {
+        use super::body_limit;
+        use crate::config::Config;
+        use hyper::Method;
+
+        let args = ["electrs", "--network", "regtest"];
+        let config = Config::parse_from(args.iter().map(|arg| arg.to_string()));
+        // anything that can be broadcast can be decoded and tested too
+        for path in &["/tx", "/tx/decode", "/tx/test", "/txs/test", "/txs/package"] {
+            let limit = body_limit(&Method::POST, path, &config);
+            assert_eq!(limit, config.rest_max_tx_body_size, "{}", path);
+        }
+        for path in &["/batch", "/scripthashes/txs"] {
+            let limit = body_limit(&Method::POST, path, &config);
+            assert_eq!(limit, config.rest_max_body_size, "{}", path);
+        }
+    }
+
+    #[cfg(not(feature = "liquid"))]
+    #[test]
+    fn test_is_admin_request() {
+        use super::{is_admin_request, Peer};
+        use crate::config::Config;
+        use hyper::header::{HeaderMap, HeaderValue};
+
+        let args = [
+            "electrs",
+            "--network",
+            "regtest",
+            "--rest-admin-token",
+            "s3cret",
+        ];
+        let config = Config::parse_from(args.iter().map(|arg| arg.to_string()));
+        let tcp = Peer::Tcp("127.0.0.1:1234".parse().unwrap());
+
+        let mut headers = HeaderMap::new();
+        assert!(!is_admin_request(&headers, tcp, &config));
+        assert!(is_admin_request(&headers, Peer::Unix, &config));
+        for wrong in &["s3cre", "s3crex", "s3cret ", "S3cret", ""] {
+            headers.insert("X-Admin-Token", HeaderValue::from_static(wrong));
+            assert!(!is_admin_request(&headers, tcp, &config), "{}", wrong);
+        }
+        headers.insert("X-Admin-Token", HeaderValue::from_static("s3cret"));
+        assert!(is_admin_request(&headers, tcp, &config));
+    }
+
+    #[test]
+    fn test_request_id() {
+        use super::request_id;
+        use hyper::header::{HeaderMap, HeaderValue};
+
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Request-Id", HeaderValue::from_static("abc-123"));
+        assert_eq!(request_id(&headers), "abc-123");
+
+        // ids that can't be logged safely are replaced
+        headers.insert("X-Request-Id", HeaderValue::from_static("has spaces"));
+        assert_ne!(request_id(&headers), "has spaces");
+
+        let generated = request_id(&HeaderMap::new());
+        assert_ne!(generated, request_id(&HeaderMap::new()));
+    }
+
+    #[test]
+    fn test_negotiate_encoding() {
+        use super::{negotiate_encoding, ContentEncoding};
+        use hyper::header::{HeaderMap, HeaderValue, ACCEPT_ENCODING};
+
+        let negotiate = |accept_encoding: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(ACCEPT_ENCODING, HeaderValue::from_static(accept_encoding));
+            negotiate_encoding(&headers)
+        };
+        assert_eq!(
+            negotiate("gzip, deflate, br"),
+            Some(ContentEncoding::Brotli)
+        );
+        assert_eq!(
+            negotiate("gzip;q=0.8, deflate"),
+            Some(ContentEncoding::Gzip)
+        );
+        assert_eq!(negotiate("br;q=0, GZIP"), Some(ContentEncoding::Gzip));
+        assert_eq!(negotiate("deflate, identity"), None);
+        assert_eq!(negotiate_encoding(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn test_encode_body_gzip() {
+        use super::{encode_body, ContentEncoding};
+        use std::io::Read;
+
+        let data = "{\"txid\":\"00\"},".repeat(200);
+        let compressed = encode_body(data.as_bytes(), ContentEncoding::Gzip).unwrap();
+        assert!(compressed.len() < data.len());
+
+        let mut decoded = String::new();
+        flate2::read::GzDecoder::new(&compressed[..])
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn test_revalidate_tip_dependent() {
+        use super::{json_response_etag, revalidate, TTL_SHORT};
+        use hyper::{header, header::HeaderValue, StatusCode};
+
+        let query_params = HashMap::new();
+        let fees: HashMap<u16, f64> = vec![(1, 12.5), (6, 4.0)].into_iter().collect();
+        let resp = json_response_etag(&fees, TTL_SHORT, &query_params).unwrap();
+        let etag = resp.headers()[header::ETAG].clone();
+        // the same body gets the same tag
+        let again = json_response_etag(&fees, TTL_SHORT, &query_params).unwrap();
+        assert_eq!(again.headers()[header::ETAG], etag);
+
+        let mut stale = json_response_etag(&fees, TTL_SHORT, &query_params).unwrap();
+        revalidate(&mut stale, &HeaderValue::from_static("\"0123\""));
+        assert_eq!(stale.status(), StatusCode::OK);
+
+        let mut fresh = resp;
+        revalidate(&mut fresh, &etag);
+        assert_eq!(fresh.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(
+            fresh.headers()[header::CACHE_CONTROL],
+            format!("public, max-age={}", TTL_SHORT)
+        );
+        assert_eq!(fresh.headers()[header::ETAG], etag);
+        assert!(fresh.headers().get(header::CONTENT_TYPE).is_none());
+    }
+
     #[test]
     fn test_difficulty_new() {
         use super::difficulty_new;
@@ -2199,4 +7107,355 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_ws_subscriptions() {
+        #[cfg(not(feature = "liquid"))]
+        let network = crate::chain::Network::Bitcoin;
+        #[cfg(feature = "liquid")]
+        let network = crate::chain::Network::Liquid;
+        let legacy = super::ResponseOptions::default();
+        let mut subscriptions = super::WsSubscriptions::default();
+        let hash = "8b01df4e368ea28f8dc0423bcf7a4923e3a12d307c875e47a0cfbf90b5c39161";
+
+        let reply = super::handle_ws_request(
+            &format!(r#"{{"subscribe":"scripthash","hash":"{}"}}"#, hash),
+            &mut subscriptions,
+            network,
+            legacy,
+        );
+        assert_eq!(reply["subscribed"], hash);
+        assert_eq!(subscriptions.len(), 1);
+
+        let reply = super::handle_ws_request(
+            r#"{"subscribe":"scripthash","hash":"00"}"#,
+            &mut subscriptions,
+            network,
+            legacy,
+        );
+        assert!(reply["error"].is_string());
+        assert_eq!(subscriptions.len(), 1);
+
+        let reply = super::handle_ws_request(
+            &format!(r#"{{"unsubscribe":"scripthash","hash":"{}"}}"#, hash),
+            &mut subscriptions,
+            network,
+            legacy,
+        );
+        assert_eq!(reply["unsubscribed"], hash);
+        assert_eq!(subscriptions.len(), 0);
+
+        for topic in &["blocks", "mempool-recent"] {
+            let request = format!(r#"{{"subscribe":"{}"}}"#, topic);
+            let reply = super::handle_ws_request(&request, &mut subscriptions, network, legacy);
+            assert_eq!(reply["subscribed"], *topic);
+        }
+        assert!(subscriptions.blocks && subscriptions.mempool_recent);
+        assert_eq!(subscriptions.len(), 2);
+
+        let reply = super::handle_ws_request(
+            r#"{"unsubscribe":"blocks"}"#,
+            &mut subscriptions,
+            network,
+            legacy,
+        );
+        assert_eq!(reply["unsubscribed"], "blocks");
+        assert!(!subscriptions.blocks);
+
+        let reply = super::handle_ws_request(
+            r#"{"subscribe":"tips"}"#,
+            &mut subscriptions,
+            network,
+            legacy,
+        );
+        assert!(reply["error"].is_string());
+        // errors use the envelope under /v1/
+        let v1 = super::ResponseOptions { v1: true };
+        let reply =
+            super::handle_ws_request(r#"{"subscribe":"tips"}"#, &mut subscriptions, network, v1);
+        assert_eq!(reply["error"]["code"], "bad_request");
+        assert_eq!(reply["error"]["message"], "Unknown topic 'tips'");
+
+        #[cfg(not(feature = "liquid"))]
+        {
+            let topic = "address:1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa";
+            let request = format!(r#"{{"subscribe":"{}"}}"#, topic);
+            let reply = super::handle_ws_request(&request, &mut subscriptions, network, legacy);
+            assert_eq!(reply["subscribed"], topic);
+            assert_eq!(subscriptions.addresses.len(), 1);
+
+            let reply = super::handle_ws_request(
+                r#"{"subscribe":"address:not-an-address"}"#,
+                &mut subscriptions,
+                network,
+                legacy,
+            );
+            assert!(reply["error"].is_string());
+            assert_eq!(subscriptions.addresses.len(), 1);
+        }
+    }
+
+    #[test]
+    fn test_is_form_urlencoded() {
+        use hyper::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+
+        let mut headers = HeaderMap::new();
+        assert!(!super::is_form_urlencoded(&headers));
+
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+        assert!(!super::is_form_urlencoded(&headers));
+
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/x-www-form-urlencoded; charset=UTF-8"),
+        );
+        assert!(super::is_form_urlencoded(&headers));
+    }
+
+    #[test]
+    fn test_parse_broadcast_rpc_error() {
+        use hyper::StatusCode;
+
+        let (status, value) = super::parse_broadcast_rpc_error(
+            r#"sendrawtransaction RPC error: {"code":-26,"message":"min relay fee not met, 100 < 141"}"#,
+        );
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(value.code, Some(-26));
+        assert_eq!(
+            value.reject_reason.as_deref(),
+            Some("min relay fee not met")
+        );
+
+        let (status, _) = super::parse_broadcast_rpc_error(
+            r#"sendrawtransaction RPC error: {"code":-26,"message":"txn-mempool-conflict"}"#,
+        );
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let (status, value) = super::parse_broadcast_rpc_error(
+            r#"sendrawtransaction RPC error: {"code":-22,"message":"TX decode failed"}"#,
+        );
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(value.message, "TX decode failed");
+        assert_eq!(value.reject_reason, None);
+
+        let (status, value) = super::parse_broadcast_rpc_error("something else");
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(value.code, None);
+        assert_eq!(value.message, "something else");
+    }
+
+    #[test]
+    fn test_is_endpoint_disabled() {
+        use hyper::Method;
+
+        let disabled = vec!["POST /tx".to_string(), "/internal".to_string()];
+        let check = |method: &Method, path: &str| {
+            let path: Vec<&str> = path.split('/').skip(1).collect();
+            super::is_endpoint_disabled(method, &path, &disabled)
+        };
+
+        assert!(check(&Method::POST, "/tx"));
+        assert!(check(&Method::POST, "/tx/test"));
+        assert!(!check(&Method::GET, "/tx/abcd"));
+        assert!(!check(&Method::POST, "/txs/test"));
+        assert!(check(&Method::GET, "/internal/mempool/txs"));
+        assert!(!check(&Method::GET, "/blocks/tip/height"));
+        assert!(!super::is_endpoint_disabled(&Method::GET, &["tx"], &[]));
+    }
+
+    #[test]
+    fn test_parse_range() {
+        use super::parse_range;
+
+        assert_eq!(parse_range("bytes=0-9", 100), Ok((0, 9)));
+        // the end is clamped to the body length
+        assert_eq!(parse_range("bytes=90-200", 100), Ok((90, 99)));
+        // open-ended
+        assert_eq!(parse_range("bytes=40-", 100), Ok((40, 99)));
+        // suffix
+        assert_eq!(parse_range("bytes=-10", 100), Ok((90, 99)));
+        assert_eq!(parse_range("bytes=-500", 100), Ok((0, 99)));
+
+        // invalid or unsatisfiable
+        assert_eq!(parse_range("bytes=100-", 100), Err(()));
+        assert_eq!(parse_range("bytes=10-5", 100), Err(()));
+        assert_eq!(parse_range("bytes=-0", 100), Err(()));
+        assert_eq!(parse_range("bytes=-", 100), Err(()));
+        assert_eq!(parse_range("bytes=a-b", 100), Err(()));
+        assert_eq!(parse_range("items=0-9", 100), Err(()));
+        // multiple ranges
+        assert_eq!(parse_range("bytes=0-9,20-29", 100), Err(()));
+    }
+
+    #[test]
+    fn test_filter_fields() {
+        let fields = ["txid", "fee", "unknown"].iter().copied().collect();
+
+        let mut value = serde_json::json!({ "txid": "aa", "fee": 1, "vin": [] });
+        super::filter_fields(&mut value, &fields);
+        assert_eq!(value, serde_json::json!({ "txid": "aa", "fee": 1 }));
+
+        let mut value = serde_json::json!([{ "txid": "aa", "size": 2 }, { "fee": 3 }]);
+        super::filter_fields(&mut value, &fields);
+        assert_eq!(value, serde_json::json!([{ "txid": "aa" }, { "fee": 3 }]));
+
+        let mut value = serde_json::json!(42);
+        super::filter_fields(&mut value, &fields);
+        assert_eq!(value, serde_json::json!(42));
+    }
+
+    #[test]
+    fn test_txs_by_txid_response() {
+        use crate::chain::Txid;
+        use bitcoin::hashes::hex::FromHex;
+        use hyper::StatusCode;
+
+        let txids: Vec<Txid> = ["01", "02", "03"]
+            .iter()
+            .map(|n| Txid::from_hex(&n.repeat(32)).unwrap())
+            .collect();
+        let values = vec![Some("tx1"), None, Some("tx3")];
+
+        assert_eq!(super::missing_txids(&txids, &values), vec![txids[1]]);
+
+        let mut query_params = HashMap::new();
+        let resp = super::txs_by_txid_response(&txids, values.clone(), &query_params).unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        query_params.insert("strict".to_string(), "true".to_string());
+        let err = super::txs_by_txid_response(&txids, values, &query_params).unwrap_err();
+        assert_eq!(err.0, StatusCode::NOT_FOUND);
+        assert!(err.1.contains(&txids[1].to_string()));
+
+        let all_found = vec![Some("tx1"), Some("tx2"), Some("tx3")];
+        assert!(super::txs_by_txid_response(&txids, all_found, &query_params).is_ok());
+    }
+
+    #[cfg(not(feature = "liquid"))]
+    #[test]
+    fn test_json_to_cbor() {
+        use super::{json_body, json_to_cbor, BlockValue, TransactionValue, UtxoValue};
+        use crate::chain::{OutPoint, Script, Transaction, TxIn, TxOut, Txid, Witness};
+        use crate::config::Config;
+        use crate::new_index::{BlockEntry, Utxo};
+        use crate::util::{BlockHeaderMeta, BlockId, BlockMeta, HeaderList};
+        use bitcoin::blockdata::constants::genesis_block;
+        use bitcoin::consensus::encode::serialize;
+        use bitcoin::hashes::hex::FromHex;
+
+        // decoding the CBOR body must give back the exact same fields as the JSON one
+        let round_trip = |json: &str| {
+            let cbor = json_to_cbor(json.as_bytes()).unwrap();
+            let decoded: Value = ciborium::de::from_reader(&cbor[..]).unwrap();
+            assert_eq!(decoded, serde_json::from_str::<Value>(json).unwrap());
+        };
+
+        let utxo = UtxoValue::from(Utxo {
+            txid: Txid::from_hex(&"ab".repeat(32)).unwrap(),
+            vout: 1,
+            confirmed: Some(BlockId {
+                height: 700_000,
+                hash: Default::default(),
+                time: 1_631_000_000,
+            }),
+            value: 5000,
+        });
+        round_trip(&json_body(&utxo, &HashMap::new()).unwrap());
+
+        // as served by /tx/:txid, with hashes, scripts and witnesses as hex strings and floats
+        let genesis = genesis_block(bitcoin::Network::Regtest);
+        let coinbase = &genesis.txdata[0];
+        let prevout = OutPoint {
+            txid: coinbase.txid(),
+            vout: 0,
+        };
+        let tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: prevout,
+                script_sig: Script::new(),
+                sequence: 0xfffffffd,
+                witness: Witness::from_vec(vec![vec![0x30, 0x44], vec![0x02]]),
+            }],
+            output: vec![TxOut {
+                value: coinbase.output[0].value - 2820,
+                script_pubkey: Script::from(vec![0x00, 0x14, 0xaa]),
+            }],
+        };
+        let txos = HashMap::from([(prevout, coinbase.output[0].clone())]);
+        let args = ["electrs", "--network", "regtest"];
+        let config = Config::parse_from(args.iter().map(|arg| arg.to_string()));
+        let tx = TransactionValue::new(tx, None, &txos, &config).unwrap();
+        round_trip(&json_body(&tx, &HashMap::new()).unwrap());
+
+        // as served by /blocks, with a null previousblockhash for the genesis block
+        let entry = BlockEntry {
+            entry: HeaderList::empty().order(vec![genesis.header]).remove(0),
+            size: serialize(&genesis).len() as u32,
+            block: genesis.clone(),
+        };
+        let blocks = [BlockValue::new(BlockHeaderMeta {
+            header_entry: entry.entry.clone(),
+            meta: BlockMeta::from(&entry),
+            mtp: genesis.header.time,
+        })];
+        round_trip(&json_body(&blocks, &HashMap::new()).unwrap());
+    }
+
+    #[cfg(not(feature = "liquid"))]
+    #[test]
+    fn test_utxos_binary() {
+        use crate::chain::Txid;
+        use crate::new_index::Utxo;
+        use crate::util::BlockId;
+        use bitcoin::hashes::hex::FromHex;
+
+        let txid = Txid::from_hex(&"ab".repeat(32)).unwrap();
+        let utxos = vec![
+            Utxo {
+                txid,
+                vout: 1,
+                confirmed: Some(BlockId {
+                    height: 700_000,
+                    hash: Default::default(),
+                    time: 0,
+                }),
+                value: 5000,
+            },
+            Utxo {
+                txid,
+                vout: 2,
+                confirmed: None,
+                value: 1,
+            },
+        ];
+
+        let buf = super::utxos_binary(&utxos);
+        assert_eq!(buf.len(), 4 + 2 * 48);
+        assert_eq!(&buf[0..4], &2u32.to_le_bytes());
+        assert_eq!(&buf[4..36], &txid[..]);
+        assert_eq!(&buf[36..40], &1u32.to_le_bytes());
+        assert_eq!(&buf[40..48], &5000u64.to_le_bytes());
+        assert_eq!(&buf[48..52], &700_000u32.to_le_bytes());
+        assert_eq!(&buf[96..100], &0u32.to_le_bytes());
+    }
+
+    #[test]
+    fn test_error_codes() {
+        use hyper::StatusCode;
+
+        let err = HttpError::from(hex::decode("zz").unwrap_err());
+        assert_eq!(err.code(), "invalid_hex");
+        assert_eq!(HttpError::not_found("gone".to_string()).code(), "not_found");
+        // errors without an explicit code fall back to one derived from their status
+        let timeout = HttpError::new(StatusCode::GATEWAY_TIMEOUT, "too slow".to_string());
+        assert_eq!(timeout.code(), "timeout");
+
+        let resp = super::error_response(err, true);
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(resp.headers()["Content-Type"], "application/json");
+        let resp = super::error_response(timeout, false);
+        assert_eq!(resp.headers()["Content-Type"], "text/plain");
+    }
 }
@@ -71,6 +71,19 @@ fn bincode_settings() {
     );
 }
 
+#[test]
+fn bincode_list_prefix() {
+    use crate::chain::Txid;
+    use bitcoin::hashes::Hash;
+
+    // block txid lists are read up to their first entry to find the coinbase
+    let txids: Vec<Txid> = (1..=3).map(|i| Txid::from_inner([i; 32])).collect();
+    let encoded = super::serialize_little(&txids).unwrap();
+    let (len, first): (u64, Txid) = super::deserialize_little(&encoded).unwrap();
+    assert_eq!(len, 3);
+    assert_eq!(first, txids[0]);
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
 struct TestStruct {
     a: u64,
@@ -66,6 +66,8 @@ pub struct HeaderList {
     headers: Vec<HeaderEntry>,
     heights: HashMap<BlockHash, usize>,
     tip: BlockHash,
+    // number of reorgs applied since startup
+    generation: u64,
 }
 
 impl HeaderList {
@@ -74,6 +76,7 @@ impl HeaderList {
             headers: vec![],
             heights: HashMap::new(),
             tip: BlockHash::default(),
+            generation: 0,
         }
     }
 
@@ -182,7 +185,16 @@ impl HeaderList {
             new_headers.len(),
             new_height
         );
-        let _removed = self.headers.split_off(new_height); // keep [0..new_height) entries
+        let removed = self.headers.split_off(new_height); // keep [0..new_height) entries
+        if !removed.is_empty() {
+            self.generation += 1;
+            info!(
+                "reorg of {} blocks at height {}, chain generation is now {}",
+                removed.len(),
+                new_height,
+                self.generation
+            );
+        }
         for new_header in new_headers {
             let height = new_header.height();
             assert_eq!(height, self.headers.len());
@@ -192,6 +204,10 @@ impl HeaderList {
         }
     }
 
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
     pub fn header_by_blockhash(&self, blockhash: &BlockHash) -> Option<&HeaderEntry> {
         let height = self.heights.get(blockhash)?;
         let header = self.headers.get(*height)?;
@@ -78,6 +78,24 @@ pub fn get_id_from_pos(
     Ok((txid, branch))
 }
 
+/// Recompute the merkle root from a leaf hash and its branch, mirroring how
+/// `create_merkle_branch_and_root` builds the branch.
+pub fn compute_merkle_root(
+    leaf: Sha256dHash,
+    branch: &[Sha256dHash],
+    mut index: usize,
+) -> Sha256dHash {
+    branch.iter().fold(leaf, |node, sibling| {
+        let parent = if index % 2 == 0 {
+            merklize(node, *sibling)
+        } else {
+            merklize(*sibling, node)
+        };
+        index /= 2;
+        parent
+    })
+}
+
 fn merklize(left: Sha256dHash, right: Sha256dHash) -> Sha256dHash {
     let data = [&left[..], &right[..]].concat();
     Sha256dHash::hash(&data)
@@ -103,3 +121,21 @@ fn create_merkle_branch_and_root(
     }
     (merkle, hashes[0])
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compute_merkle_root() {
+        let hashes: Vec<Sha256dHash> = (0u8..5).map(|i| Sha256dHash::hash(&[i])).collect();
+        for pos in 0..hashes.len() {
+            let (branch, root) = create_merkle_branch_and_root(hashes.clone(), pos);
+            assert_eq!(compute_merkle_root(hashes[pos], &branch, pos), root);
+            // the last leaf of an odd level is paired with itself, so its position can't be told
+            if branch[0] != hashes[pos] {
+                assert_ne!(compute_merkle_root(hashes[pos], &branch, pos ^ 1), root);
+            }
+        }
+    }
+}
@@ -2,6 +2,10 @@ use crate::chain::{Network, Transaction, TxOut};
 use std::collections::HashMap;
 
 const VSIZE_BIN_WIDTH: u32 = 50_000; // in vbytes
+const BLOCK_VSIZE: u64 = 1_000_000; // in vbytes (= 4M weight units)
+
+// bitcoind's -bytespersigop default
+const DEFAULT_BYTES_PER_SIGOP: u32 = 20;
 
 pub struct TxFeeInfo {
     pub fee: u64,   // in satoshis
@@ -22,6 +26,12 @@ impl TxFeeInfo {
     }
 }
 
+/// The virtual size used by bitcoind's mempool policy, which accounts sigop-heavy transactions
+/// as larger than their actual size (see GetVirtualTransactionSize).
+pub fn sigop_adjusted_vsize(weight: u32, sigop_cost: u32) -> u32 {
+    (weight.max(sigop_cost * DEFAULT_BYTES_PER_SIGOP) + 3) / 4
+}
+
 #[cfg(not(feature = "liquid"))]
 pub fn get_tx_fee(tx: &Transaction, prevouts: &HashMap<u32, &TxOut>, _network: Network) -> u64 {
     if tx.is_coin_base() {
@@ -58,3 +68,62 @@ pub fn make_fee_histogram(mut entries: Vec<&TxFeeInfo>) -> Vec<(f32, u32)> {
     }
     histogram
 }
+
+/// Estimate the feerate needed to confirm within each of `targets` blocks, based solely on
+/// the current mempool fee histogram (as produced by `make_fee_histogram`).
+///
+/// For a target of T blocks, this is the feerate at which the cumulative vsize of higher-paying
+/// transactions fills T blocks. Targets the mempool cannot fill get the lowest observed feerate,
+/// or `min_feerate` when the mempool is empty.
+pub fn estimate_fees_from_histogram(
+    histogram: &[(f32, u32)],
+    targets: &[u16],
+    min_feerate: f64,
+) -> HashMap<u16, f64> {
+    let floor = histogram.last().map_or(min_feerate, |(feerate, _)| {
+        (*feerate as f64).max(min_feerate)
+    });
+
+    targets
+        .iter()
+        .map(|&target| {
+            let capacity = target as u64 * BLOCK_VSIZE;
+            let mut cumulative = 0u64;
+            let feerate = histogram
+                .iter()
+                .find(|(_, vsize)| {
+                    cumulative += *vsize as u64;
+                    cumulative >= capacity
+                })
+                .map_or(floor, |(feerate, _)| (*feerate as f64).max(min_feerate));
+            (target, feerate)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_fees_from_histogram() {
+        // 600k vB at >= 20 sat/vB, another 600k vB at >= 10 sat/vB, 300k vB at >= 2 sat/vB
+        let histogram = vec![(20.0, 600_000), (10.0, 600_000), (2.0, 300_000)];
+        let estimates = estimate_fees_from_histogram(&histogram, &[1, 2, 3], 1.0);
+        assert_eq!(estimates[&1], 10.0);
+        assert_eq!(estimates[&2], 2.0);
+        // the mempool doesn't fill 3 blocks, fall back to the lowest feerate
+        assert_eq!(estimates[&3], 2.0);
+
+        let empty = estimate_fees_from_histogram(&[], &[1, 6], 1.0);
+        assert_eq!(empty[&1], 1.0);
+        assert_eq!(empty[&6], 1.0);
+    }
+
+    #[test]
+    fn test_sigop_adjusted_vsize() {
+        assert_eq!(sigop_adjusted_vsize(561, 4), 141);
+        // 80 sigops (a bare 1-of-20 multisig output) * 20 bytes per sigop
+        assert_eq!(sigop_adjusted_vsize(561, 80), 400);
+    }
+}
@@ -1,17 +1,24 @@
 mod block;
+mod cancel;
 mod script;
 mod transaction;
 
 pub mod bincode_util;
 pub mod electrum_merkle;
 pub mod fees;
+pub mod rate_limit;
 
 pub use self::block::{BlockHeaderMeta, BlockId, BlockMeta, BlockStatus, HeaderEntry, HeaderList};
+pub use self::cancel::CancelToken;
 pub use self::fees::get_tx_fee;
-pub use self::script::{get_innerscripts, ScriptToAddr, ScriptToAsm};
+pub use self::script::{
+    get_innerscripts, parse_inscription, parse_multisig, parse_op_return, script_type,
+    InscriptionInfo, MultisigInfo, OpReturnPayload, ScriptToAddr, ScriptToAsm, TaprootSpend,
+};
 pub use self::transaction::{
-    extract_tx_prevouts, has_prevout, is_coinbase, is_spendable, serialize_outpoint,
-    sigops::transaction_sigop_count, TransactionStatus, TxInput,
+    extract_tx_prevouts, has_prevout, is_coinbase, is_final_tx, is_spendable, raw_tx_output_count,
+    serialize_outpoint, signals_rbf, sigops::sigop_cost_breakdown, sigops::transaction_sigop_count,
+    TransactionStatus, TxInput,
 };
 
 use std::collections::HashMap;
@@ -0,0 +1,90 @@
+use lru::LruCache;
+use std::net::IpAddr;
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Token bucket rate limiter keyed by client IP.
+///
+/// Only the `max_clients` most recently seen IPs are tracked. An evicted client starts
+/// over with a full bucket, which bounds memory use when facing many distinct IPs.
+pub struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    buckets: Mutex<LruCache<IpAddr, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(rate: f64, burst: u32, max_clients: usize) -> Self {
+        assert!(rate > 0.0, "rate limit must be positive");
+        RateLimiter {
+            rate,
+            burst: burst.max(1) as f64,
+            buckets: Mutex::new(LruCache::new(
+                NonZeroUsize::new(max_clients).unwrap_or(NonZeroUsize::MIN),
+            )),
+        }
+    }
+
+    /// Take a token for `ip`, or return how long to wait until one becomes available
+    pub fn check(&self, ip: IpAddr) -> Result<(), Duration> {
+        self.check_at(ip, Instant::now())
+    }
+
+    fn check_at(&self, ip: IpAddr, now: Instant) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets.get_or_insert_mut(ip, || Bucket {
+            tokens: self.burst,
+            updated: now,
+        });
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.burst);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / self.rate))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RateLimiter;
+    use std::net::IpAddr;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_rate_limiter() {
+        let limiter = RateLimiter::new(2.0, 3, 2);
+        let (a, b, c): (IpAddr, IpAddr, IpAddr) = (
+            "10.0.0.1".parse().unwrap(),
+            "10.0.0.2".parse().unwrap(),
+            "10.0.0.3".parse().unwrap(),
+        );
+        let now = Instant::now();
+
+        // the burst is available right away, then the client has to wait for a refill
+        for _ in 0..3 {
+            assert!(limiter.check_at(a, now).is_ok());
+        }
+        assert_eq!(limiter.check_at(a, now), Err(Duration::from_millis(500)));
+        assert!(limiter.check_at(b, now).is_ok());
+
+        let later = now + Duration::from_millis(500);
+        assert!(limiter.check_at(a, later).is_ok());
+        assert!(limiter.check_at(a, later).is_err());
+
+        // tracking a third client evicts the least recently seen one, which starts over
+        assert!(limiter.check_at(c, later).is_ok());
+        assert_eq!(limiter.buckets.lock().unwrap().len(), 2);
+        assert!(!limiter.buckets.lock().unwrap().contains(&b));
+    }
+}
@@ -1,12 +1,23 @@
 #[cfg(feature = "liquid")]
 use elements::address as elements_address;
 
+use bitcoin::blockdata::opcodes;
+
 use crate::chain::{script, Network, Script, TxIn, TxOut};
-use script::Instruction::PushBytes;
+use script::Instruction::{self, PushBytes};
 
 pub struct InnerScripts {
     pub redeem_script: Option<Script>,
     pub witness_script: Option<Script>,
+    pub taproot: Option<TaprootSpend>,
+}
+
+// How a taproot output was spent, per BIP341
+pub struct TaprootSpend {
+    pub is_script_path: bool,
+    pub leaf_script: Option<Script>,
+    pub control_block: Option<Vec<u8>>,
+    pub annex_present: bool,
 }
 
 pub trait ScriptToAsm: std::fmt::Debug {
@@ -101,8 +112,536 @@ pub fn get_innerscripts(txin: &TxIn, prevout: &TxOut) -> InnerScripts {
         None
     };
 
+    let taproot = if prevout.script_pubkey.is_v1_p2tr() {
+        get_taproot_spend(txin)
+    } else {
+        None
+    };
+
     InnerScripts {
         redeem_script,
         witness_script,
+        taproot,
+    }
+}
+
+/// The data pushed by an OP_RETURN output script
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct OpReturnPayload {
+    pub data_hex: String,
+    // only set when the data is printable text
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_utf8: Option<String>,
+    pub pushes: Vec<String>,
+}
+
+/// Decode the pushes following the OP_RETURN opcode, up to the first non-push instruction.
+/// Returns None for scripts that aren't OP_RETURN outputs.
+pub fn parse_op_return(script: &Script) -> Option<OpReturnPayload> {
+    if !script.is_op_return() {
+        return None;
+    }
+    let pushes: Vec<&[u8]> = script
+        .instructions()
+        .skip(1) // the OP_RETURN itself
+        .map_while(|instruction| match instruction {
+            Ok(PushBytes(data)) => Some(data),
+            _ => None,
+        })
+        .collect();
+    let data = pushes.concat();
+
+    let data_utf8 = String::from_utf8(data.clone()).ok().filter(|text| {
+        !text.is_empty()
+            && text
+                .chars()
+                .all(|c| !c.is_control() || c == '\n' || c == '\r' || c == '\t')
+    });
+
+    Some(OpReturnPayload {
+        data_hex: hex::encode(&data),
+        data_utf8,
+        pushes: pushes.into_iter().map(hex::encode).collect(),
+    })
+}
+
+/// An ord inscription envelope found in a taproot leaf script. The body itself is not kept.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct InscriptionInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    pub content_length: usize,
+}
+
+const INSCRIPTION_PROTOCOL_ID: &[u8] = b"ord";
+const INSCRIPTION_CONTENT_TYPE_TAG: u8 = 1;
+
+/// Look for the first `OP_FALSE OP_IF "ord" <fields...> [OP_0 <body pushes...>] OP_ENDIF`
+/// envelope in a leaf script. Returns None if there is none or if it is malformed.
+pub fn parse_inscription(leaf_script: &Script) -> Option<InscriptionInfo> {
+    let instructions = leaf_script
+        .instructions()
+        .collect::<Result<Vec<_>, _>>()
+        .ok()?;
+
+    let is_op = |instruction: &Instruction, opcode: opcodes::All| match instruction {
+        Instruction::Op(op) => op.into_u8() == opcode.into_u8(),
+        _ => false,
+    };
+    let start = instructions.windows(3).position(|window| {
+        matches!(window[0], PushBytes(data) if data.is_empty())
+            && is_op(&window[1], opcodes::all::OP_IF)
+            && matches!(window[2], PushBytes(data) if data == INSCRIPTION_PROTOCOL_ID)
+    })?;
+
+    let mut content_type = None;
+    let mut fields = instructions[start + 3..].iter();
+    loop {
+        let tag = match fields.next()? {
+            instruction if is_op(instruction, opcodes::all::OP_ENDIF) => {
+                return Some(InscriptionInfo {
+                    content_type,
+                    content_length: 0,
+                })
+            }
+            // an empty push separates the fields from the body
+            PushBytes([]) => break,
+            PushBytes(data) if data.len() == 1 => data[0],
+            Instruction::Op(op)
+                if op.into_u8() >= opcodes::all::OP_PUSHNUM_1.into_u8()
+                    && op.into_u8() <= opcodes::all::OP_PUSHNUM_16.into_u8() =>
+            {
+                op.into_u8() - opcodes::all::OP_PUSHNUM_1.into_u8() + 1
+            }
+            _ => return None,
+        };
+        let value = match fields.next()? {
+            PushBytes(data) => data,
+            _ => return None,
+        };
+        if tag == INSCRIPTION_CONTENT_TYPE_TAG && content_type.is_none() {
+            content_type = Some(String::from_utf8_lossy(value).into_owned());
+        }
+    }
+
+    let mut content_length = 0;
+    for instruction in fields {
+        match instruction {
+            PushBytes(data) => content_length += data.len(),
+            instruction if is_op(instruction, opcodes::all::OP_ENDIF) => {
+                return Some(InscriptionInfo {
+                    content_type,
+                    content_length,
+                })
+            }
+            _ => return None,
+        }
+    }
+    // the envelope was never closed
+    None
+}
+
+fn get_taproot_spend(txin: &TxIn) -> Option<TaprootSpend> {
+    let witness = &txin.witness;
+    #[cfg(feature = "liquid")]
+    let witness = &witness.script_witness;
+
+    let w_len = witness.len();
+    // The annex is the last element if there are at least two and it starts with 0x50,
+    // it shouldn't be mistaken for the control block
+    let annex_present =
+        w_len >= 2 && witness.last().and_then(|last| last.first().copied()) == Some(0x50);
+    let stack_len = if annex_present { w_len - 1 } else { w_len };
+
+    match stack_len {
+        0 => None,
+        // a single signature
+        1 => Some(TaprootSpend {
+            is_script_path: false,
+            leaf_script: None,
+            control_block: None,
+            annex_present,
+        }),
+        // the script inputs, followed by the leaf script and the control block
+        _ => {
+            let mut elements = witness.iter().skip(stack_len - 2);
+            Some(TaprootSpend {
+                is_script_path: true,
+                leaf_script: elements.next().map(|script| Script::from(script.to_vec())),
+                control_block: elements.next().map(|cb| cb.to_vec()),
+                annex_present,
+            })
+        }
+    }
+}
+
+// TODO should the following something to put inside rust-elements lib?
+/// Classify an output script by its standard type
+pub fn script_type(script: &Script) -> &'static str {
+    if script.is_empty() {
+        "empty"
+    } else if script.is_op_return() {
+        "op_return"
+    } else if script.is_p2pk() {
+        "p2pk"
+    } else if script.is_p2pkh() {
+        "p2pkh"
+    } else if script.is_p2sh() {
+        "p2sh"
+    } else if script.is_v0_p2wpkh() {
+        "v0_p2wpkh"
+    } else if script.is_v0_p2wsh() {
+        "v0_p2wsh"
+    } else if is_v1_p2tr(script) {
+        "v1_p2tr"
+    } else if is_anchor(script) {
+        "anchor"
+    } else if let Some(version) = future_witness_version(script) {
+        FUTURE_WITNESS_TYPES[version as usize - 2]
+    } else if script.is_provably_unspendable() {
+        "provably_unspendable"
+    } else if is_bare_multisig(script) {
+        "multisig"
+    } else {
+        "unknown"
+    }
+}
+
+const FUTURE_WITNESS_TYPES: [&str; 15] = [
+    "v2_unknown",
+    "v3_unknown",
+    "v4_unknown",
+    "v5_unknown",
+    "v6_unknown",
+    "v7_unknown",
+    "v8_unknown",
+    "v9_unknown",
+    "v10_unknown",
+    "v11_unknown",
+    "v12_unknown",
+    "v13_unknown",
+    "v14_unknown",
+    "v15_unknown",
+    "v16_unknown",
+];
+
+// Witness versions 2 to 16 have no defined semantics yet, but are valid witness programs:
+// a version opcode followed by a single direct push of 2 to 40 bytes (BIP 141)
+fn future_witness_version(script: &Script) -> Option<u8> {
+    let len = script.len();
+    let version_op = script[0];
+    let is_program = (4..=42).contains(&len)
+        && version_op >= opcodes::all::OP_PUSHNUM_2.into_u8()
+        && version_op <= opcodes::all::OP_PUSHNUM_16.into_u8()
+        && script[1] as usize == len - 2;
+    if is_program {
+        Some(version_op - opcodes::all::OP_PUSHNUM_1.into_u8() + 1)
+    } else {
+        None
+    }
+}
+
+fn is_v1_p2tr(script: &Script) -> bool {
+    script.len() == 34
+        && script[0] == opcodes::all::OP_PUSHNUM_1.into_u8()
+        && script[1] == opcodes::all::OP_PUSHBYTES_32.into_u8()
+}
+fn is_bare_multisig(script: &Script) -> bool {
+    let len = script.len();
+    // 1-of-1 multisig is 37 bytes
+    // Max is 15 pubkeys
+    // Min is 1
+    // First byte must be <= the second to last (4-of-2 makes no sense)
+    // We won't check the pubkeys, just assume anything with the form
+    //   OP_M ... OP_N OP_CHECKMULTISIG
+    // is bare multisig
+    len >= 37
+        && script[len - 1] == opcodes::all::OP_CHECKMULTISIG.into_u8()
+        && script[len - 2] >= opcodes::all::OP_PUSHNUM_1.into_u8()
+        && script[len - 2] <= opcodes::all::OP_PUSHNUM_15.into_u8()
+        && script[0] >= opcodes::all::OP_PUSHNUM_1.into_u8()
+        && script[0] <= script[len - 2]
+}
+
+/// A bare multisig output script
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MultisigInfo {
+    pub m: u8,
+    pub n: u8,
+    pub pubkeys: Vec<String>,
+}
+
+/// Parse an `OP_M <pubkey>... OP_N OP_CHECKMULTISIG` output script, with compressed or
+/// uncompressed pubkeys only. Returns None if the script doesn't strictly match.
+pub fn parse_multisig(script: &Script) -> Option<MultisigInfo> {
+    if !is_bare_multisig(script) {
+        return None;
+    }
+    let pushnum = |instruction: &Instruction| match instruction {
+        Instruction::Op(op)
+            if op.into_u8() >= opcodes::all::OP_PUSHNUM_1.into_u8()
+                && op.into_u8() <= opcodes::all::OP_PUSHNUM_16.into_u8() =>
+        {
+            Some(op.into_u8() - opcodes::all::OP_PUSHNUM_1.into_u8() + 1)
+        }
+        _ => None,
+    };
+
+    let instructions = script.instructions().collect::<Result<Vec<_>, _>>().ok()?;
+    let (first, rest) = instructions.split_first()?;
+    let (last, rest) = rest.split_last()?;
+    let (n_op, keys) = rest.split_last()?;
+
+    let m = pushnum(first)?;
+    let n = pushnum(n_op)?;
+    match last {
+        Instruction::Op(op) if op.into_u8() == opcodes::all::OP_CHECKMULTISIG.into_u8() => (),
+        _ => return None,
+    }
+    let pubkeys = keys
+        .iter()
+        .map(|instruction| match instruction {
+            PushBytes(key) if key.len() == 33 || key.len() == 65 => Some(hex::encode(key)),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    if m > n || pubkeys.len() != n as usize {
+        return None;
+    }
+    Some(MultisigInfo { m, n, pubkeys })
+}
+
+fn is_anchor(script: &Script) -> bool {
+    let len = script.len();
+    len == 4
+        && script[0] == opcodes::all::OP_PUSHNUM_1.into_u8()
+        && script[1] == opcodes::all::OP_PUSHBYTES_2.into_u8()
+        && script[2] == 0x4e
+        && script[3] == 0x73
+}
+
+#[cfg(test)]
+mod tests {
+    use super::script_type;
+    use crate::chain::Script;
+
+    fn script(hex: &str) -> Script {
+        Script::from(hex::decode(hex).unwrap())
+    }
+
+    #[test]
+    fn test_script_type_p2tr() {
+        let p2tr = "5120a37c3903c8d0db6512e2b40b0dffa05e5a3ab73603ce8c9c4b7771e5412328f9";
+        assert_eq!(script_type(&script(p2tr)), "v1_p2tr");
+
+        // witness v1 programs that aren't 32 bytes long are not p2tr
+        let v1_short = "511fa37c3903c8d0db6512e2b40b0dffa05e5a3ab73603ce8c9c4b7771e5412328";
+        assert_eq!(script_type(&script(v1_short)), "unknown");
+    }
+
+    #[cfg(not(feature = "liquid"))]
+    fn p2tr_spend(witness: Vec<Vec<u8>>) -> super::TaprootSpend {
+        use crate::chain::{TxIn, TxOut, Witness};
+        let prevout = TxOut {
+            value: 1000,
+            script_pubkey: script(
+                "5120a37c3903c8d0db6512e2b40b0dffa05e5a3ab73603ce8c9c4b7771e5412328f9",
+            ),
+        };
+        let txin = TxIn {
+            witness: Witness::from_vec(witness),
+            ..Default::default()
+        };
+        super::get_innerscripts(&txin, &prevout).taproot.unwrap()
+    }
+
+    #[test]
+    #[cfg(not(feature = "liquid"))]
+    fn test_taproot_spend() {
+        let sig = vec![0x01; 64];
+        let leaf =
+            hex::decode("20a37c3903c8d0db6512e2b40b0dffa05e5a3ab73603ce8c9c4b7771e5412328f9ac")
+                .unwrap();
+        let control = vec![0xc0; 33];
+        let annex = vec![0x50, 0x01];
+
+        let key = p2tr_spend(vec![sig.clone()]);
+        assert!(!key.is_script_path && !key.annex_present);
+
+        // a key path spend with an annex, not to be mistaken for a script path spend
+        let key_annex = p2tr_spend(vec![sig.clone(), annex.clone()]);
+        assert!(!key_annex.is_script_path && key_annex.annex_present);
+
+        let script_path = p2tr_spend(vec![sig.clone(), leaf.clone(), control.clone()]);
+        assert!(script_path.is_script_path && !script_path.annex_present);
+        assert_eq!(script_path.leaf_script.unwrap().to_bytes(), leaf);
+        assert_eq!(script_path.control_block.unwrap(), control);
+
+        let script_annex = p2tr_spend(vec![sig, leaf.clone(), control.clone(), annex]);
+        assert!(script_annex.is_script_path && script_annex.annex_present);
+        assert_eq!(script_annex.leaf_script.unwrap().to_bytes(), leaf);
+        assert_eq!(script_annex.control_block.unwrap(), control);
+    }
+
+    #[test]
+    fn test_parse_op_return() {
+        use super::parse_op_return;
+
+        assert!(parse_op_return(&script("0014751e76e8199196d454941c45d1b3a323f1433bd6")).is_none());
+
+        // no data at all
+        let empty = parse_op_return(&script("6a")).unwrap();
+        assert_eq!(empty.data_hex, "");
+        assert!(empty.data_utf8.is_none());
+        assert!(empty.pushes.is_empty());
+
+        let text = parse_op_return(&script("6a0568656c6c6f")).unwrap();
+        assert_eq!(text.data_hex, "68656c6c6f");
+        assert_eq!(text.data_utf8.as_deref(), Some("hello"));
+
+        // valid utf-8 but not printable
+        let binary = parse_op_return(&script("6a020001")).unwrap();
+        assert!(binary.data_utf8.is_none());
+
+        let multiple = parse_op_return(&script("6a02aabb03ccddee")).unwrap();
+        assert_eq!(multiple.data_hex, "aabbccddee");
+        assert_eq!(multiple.pushes, vec!["aabb", "ccddee"]);
+    }
+
+    #[test]
+    fn test_parse_op_return_pushdata() {
+        use super::parse_op_return;
+
+        for hex in &["6a4c03aabbcc", "6a4d0300aabbcc", "6a4e03000000aabbcc"] {
+            let payload = parse_op_return(&script(hex)).unwrap();
+            assert_eq!(payload.data_hex, "aabbcc", "{}", hex);
+            assert_eq!(payload.pushes, vec!["aabbcc"], "{}", hex);
+        }
+    }
+
+    #[test]
+    fn test_parse_inscription() {
+        use super::parse_inscription;
+
+        // <pubkey> OP_CHECKSIG OP_FALSE OP_IF "ord" 1 "text/plain" OP_0 "hello" "!" OP_ENDIF
+        let pubkey = "20a37c3903c8d0db6512e2b40b0dffa05e5a3ab73603ce8c9c4b7771e5412328f9ac";
+        let envelope = "0063036f726401010a746578742f706c61696e000568656c6c6f012168";
+        let inscription = parse_inscription(&script(&format!("{}{}", pubkey, envelope))).unwrap();
+        assert_eq!(inscription.content_type.as_deref(), Some("text/plain"));
+        assert_eq!(inscription.content_length, 6);
+
+        // the content type tag as OP_1, and no body
+        let no_body = parse_inscription(&script("0063036f7264510a746578742f706c61696e68")).unwrap();
+        assert_eq!(no_body.content_type.as_deref(), Some("text/plain"));
+        assert_eq!(no_body.content_length, 0);
+
+        // not an ord envelope, or never closed
+        assert!(parse_inscription(&script(pubkey)).is_none());
+        assert!(parse_inscription(&script("0063036f7269000568656c6c6f68")).is_none());
+        assert!(parse_inscription(&script("0063036f7264000568656c6c6f")).is_none());
+    }
+
+    #[test]
+    fn test_script_type_anchor() {
+        assert_eq!(script_type(&script("51024e73")), "anchor");
+        // a different 2-byte witness v1 program is not an anchor
+        assert_eq!(script_type(&script("51024e74")), "unknown");
+    }
+
+    #[test]
+    fn test_script_type_future_witness() {
+        // v2 with a 32 bytes program
+        let v2 = "5220a37c3903c8d0db6512e2b40b0dffa05e5a3ab73603ce8c9c4b7771e5412328f9";
+        assert_eq!(script_type(&script(v2)), "v2_unknown");
+        // v16 with the shortest (2 bytes) and the longest (40 bytes) programs
+        assert_eq!(script_type(&script("60020102")), "v16_unknown");
+        let longest = format!("6028{}", "ab".repeat(40));
+        assert_eq!(script_type(&script(&longest)), "v16_unknown");
+
+        // programs out of the 2-40 bytes range
+        assert_eq!(script_type(&script("520101")), "unknown");
+        let too_long = format!("5229{}", "ab".repeat(41));
+        assert_eq!(script_type(&script(&too_long)), "unknown");
+        // push length not matching the script length
+        assert_eq!(script_type(&script("5203010203ac")), "unknown");
+        // not a push opcode after the version
+        assert_eq!(script_type(&script("5276a914")), "unknown");
+    }
+
+    #[test]
+    fn test_parse_multisig() {
+        use super::parse_multisig;
+        let pubkey = "030000000000000000000000000000000000000000000000000000000000000001";
+
+        let one_of_one = parse_multisig(&script(&format!("5121{}51ae", pubkey))).unwrap();
+        assert_eq!((one_of_one.m, one_of_one.n), (1, 1));
+        assert_eq!(one_of_one.pubkeys, vec![pubkey]);
+
+        let fifteen = format!("5f{}5fae", format!("21{}", pubkey).repeat(15));
+        let fifteen = parse_multisig(&script(&fifteen)).unwrap();
+        assert_eq!((fifteen.m, fifteen.n), (15, 15));
+        assert_eq!(fifteen.pubkeys.len(), 15);
+
+        // uncompressed pubkeys are fine too
+        let uncompressed = format!("04{}", "ab".repeat(64));
+        let one_of_two = format!("5121{}41{}52ae", pubkey, uncompressed);
+        let one_of_two = parse_multisig(&script(&one_of_two)).unwrap();
+        assert_eq!(one_of_two.pubkeys, vec![pubkey.to_string(), uncompressed]);
+    }
+
+    #[test]
+    fn test_parse_multisig_malformed() {
+        use super::parse_multisig;
+
+        // a 34 bytes push instead of a pubkey passes the heuristic but not strict parsing, and
+        // keeps its scriptpubkey_type
+        let bad_key = script(&format!("5122{}51ae", "02".repeat(34)));
+        assert!(parse_multisig(&bad_key).is_none());
+        assert_eq!(script_type(&bad_key), "multisig");
+
+        // the pubkey count doesn't match N
+        let wrong_n = script(&format!("5121{}52ae", "02".repeat(33)));
+        assert!(parse_multisig(&wrong_n).is_none());
+
+        // a non-push opcode between the pubkeys
+        let extra_op = script(&format!("5121{}7552ae", "02".repeat(33)));
+        assert!(parse_multisig(&extra_op).is_none());
+
+        // a short pubkey push that swallows OP_N
+        let truncated = script(&format!("5121{}51ae", "02".repeat(32)));
+        assert!(parse_multisig(&truncated).is_none());
+    }
+
+    #[test]
+    fn test_script_type_bare_multisig() {
+        let pubkey = "21030000000000000000000000000000000000000000000000000000000000000001";
+        // 1-of-1
+        let one_of_one = format!("51{}51ae", pubkey);
+        assert_eq!(script_type(&script(&one_of_one)), "multisig");
+        // 1-of-2
+        let one_of_two = format!("51{}{}52ae", pubkey, pubkey);
+        assert_eq!(script_type(&script(&one_of_two)), "multisig");
+        // 2-of-1 makes no sense
+        let two_of_one = format!("52{}51ae", pubkey);
+        assert_eq!(script_type(&script(&two_of_one)), "unknown");
+        // too short to hold a pubkey
+        assert_eq!(script_type(&script("510151ae")), "unknown");
+    }
+
+    #[test]
+    fn test_script_type_standard() {
+        assert_eq!(script_type(&script("")), "empty");
+        assert_eq!(script_type(&script("6a0401020304")), "op_return");
+        assert_eq!(
+            script_type(&script(
+                "76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac"
+            )),
+            "p2pkh"
+        );
+        assert_eq!(
+            script_type(&script("0014751e76e8199196d454941c45d1b3a323f1433bd6")),
+            "v0_p2wpkh"
+        );
     }
 }
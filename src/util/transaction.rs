@@ -24,6 +24,16 @@ pub struct TransactionStatus {
     pub block_hash: Option<BlockHash>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub block_time: Option<u32>,
+
+    // mempool details, only available for unconfirmed transactions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fee: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vsize: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub feerate: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_seen: Option<u64>,
 }
 
 impl From<Option<BlockId>> for TransactionStatus {
@@ -34,12 +44,20 @@ impl From<Option<BlockId>> for TransactionStatus {
                 block_height: Some(b.height),
                 block_hash: Some(b.hash),
                 block_time: Some(b.time),
+                fee: None,
+                vsize: None,
+                feerate: None,
+                first_seen: None,
             },
             None => TransactionStatus {
                 confirmed: false,
                 block_height: None,
                 block_hash: None,
                 block_time: None,
+                fee: None,
+                vsize: None,
+                feerate: None,
+                first_seen: None,
             },
         }
     }
@@ -75,6 +93,31 @@ pub fn is_spendable(txout: &TxOut) -> bool {
     return !txout.is_fee() && !txout.script_pubkey.is_provably_unspendable();
 }
 
+const SEQUENCE_FINAL: u32 = 0xffff_ffff;
+const MAX_BIP125_RBF_SEQUENCE: u32 = 0xffff_fffd;
+const LOCKTIME_THRESHOLD: u32 = 500_000_000;
+
+/// Whether any of the transaction's inputs signals BIP 125 replaceability
+pub fn signals_rbf(tx: &Transaction) -> bool {
+    tx.input
+        .iter()
+        .any(|txin| txin.sequence <= MAX_BIP125_RBF_SEQUENCE)
+}
+
+/// Whether the transaction's locktime allows its inclusion in a block at the given height
+/// and median-time-past. Mirrors bitcoind's IsFinalTx().
+pub fn is_final_tx(tx: &Transaction, height: u32, mtp: u32) -> bool {
+    if tx.lock_time == 0 {
+        return true;
+    }
+    let limit = if tx.lock_time < LOCKTIME_THRESHOLD {
+        height
+    } else {
+        mtp
+    };
+    tx.lock_time < limit || tx.input.iter().all(|txin| txin.sequence == SEQUENCE_FINAL)
+}
+
 /// Extract the previous TxOuts of a Transaction's TxIns
 ///
 /// # Errors
@@ -115,6 +158,35 @@ where
     s.end()
 }
 
+/// Get the number of outputs of a serialized transaction, without decoding the outputs
+/// themselves (or the witnesses that follow them)
+#[cfg(not(feature = "liquid"))]
+pub fn raw_tx_output_count(raw: &[u8]) -> Option<u32> {
+    use bitcoin::consensus::encode::Decodable;
+    use bitcoin::VarInt;
+    use std::convert::TryFrom;
+
+    let mut cursor = std::io::Cursor::new(raw);
+    i32::consensus_decode(&mut cursor).ok()?; // version
+    let mut input_count = VarInt::consensus_decode(&mut cursor).ok()?.0;
+    if input_count == 0 {
+        // segwit marker, followed by the flag and the actual input count
+        u8::consensus_decode(&mut cursor).ok()?;
+        input_count = VarInt::consensus_decode(&mut cursor).ok()?.0;
+    }
+    for _ in 0..input_count {
+        TxIn::consensus_decode(&mut cursor).ok()?;
+    }
+    let output_count = VarInt::consensus_decode(&mut cursor).ok()?.0;
+    u32::try_from(output_count).ok()
+}
+
+#[cfg(feature = "liquid")]
+pub fn raw_tx_output_count(raw: &[u8]) -> Option<u32> {
+    let tx: Transaction = elements::encode::deserialize(raw).ok()?;
+    Some(tx.output.len() as u32)
+}
+
 pub(super) mod sigops {
     use crate::chain::{
         hashes::hex::FromHex,
@@ -123,7 +195,7 @@ pub(super) mod sigops {
             All,
         },
         script::{self, Instruction},
-        Transaction, TxOut, Witness,
+        Transaction, TxIn, TxOut, Witness,
     };
     use std::collections::HashMap;
 
@@ -154,6 +226,43 @@ pub(super) mod sigops {
         get_sigop_cost(tx, &prevouts, true, true)
     }
 
+    /// Get the sigop cost of each input and each output of the transaction, which add up to
+    /// transaction_sigop_count(). prevout_map must have all the prevouts.
+    pub fn sigop_cost_breakdown(
+        tx: &Transaction,
+        prevout_map: &HashMap<u32, &TxOut>,
+    ) -> Result<(Vec<usize>, Vec<usize>), script::Error> {
+        #[cfg(not(feature = "liquid"))]
+        let is_coinbase_or_pegin = tx.is_coin_base();
+        #[cfg(feature = "liquid")]
+        let is_coinbase_or_pegin = tx.is_coinbase() || tx.input.iter().any(|input| input.is_pegin);
+
+        let inputs = tx
+            .input
+            .iter()
+            .enumerate()
+            .map(|(idx, input)| -> Result<usize, script::Error> {
+                let mut n = count_sigops(&input.script_sig, false) * 4;
+                // coinbase tx won't use prevouts, only their scriptSig counts
+                if !is_coinbase_or_pegin {
+                    let prevout = prevout_map
+                        .get(&(idx as u32))
+                        .ok_or(script::Error::EarlyEndOfScript)?;
+                    n += input_p2sh_sigop_count(input, prevout) * 4;
+                    n += input_witness_sigop_count(input, prevout);
+                }
+                Ok(n)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let outputs = tx
+            .output
+            .iter()
+            .map(|output| count_sigops(&output.script_pubkey, false) * 4)
+            .collect();
+
+        Ok((inputs, outputs))
+    }
+
     fn decode_pushnum(op: &All) -> Option<u8> {
         // 81 = OP_1, 96 = OP_16
         // 81 -> 1, so... 81 - 80 -> 1
@@ -225,22 +334,32 @@ pub(super) mod sigops {
         }
         let mut n = 0;
         for (input, prevout) in tx.input.iter().zip(previous_outputs.iter()) {
-            if prevout.script_pubkey.is_p2sh() {
-                if let Some(Ok(script::Instruction::PushBytes(redeem))) =
-                    input.script_sig.instructions().last()
-                {
-                    let script =
-                        script::Script::from_byte_iter(redeem.iter().map(|v| Ok(*v))).unwrap(); // I only return Ok, so it won't error
-                    n += count_sigops(&script, true);
-                }
-            }
+            n += input_p2sh_sigop_count(input, prevout);
         }
         n
     }
 
+    fn input_p2sh_sigop_count(input: &TxIn, prevout: &TxOut) -> usize {
+        if prevout.script_pubkey.is_p2sh() {
+            if let Some(Ok(script::Instruction::PushBytes(redeem))) =
+                input.script_sig.instructions().last()
+            {
+                let script = script::Script::from_byte_iter(redeem.iter().map(|v| Ok(*v))).unwrap(); // I only return Ok, so it won't error
+                return count_sigops(&script, true);
+            }
+        }
+        0
+    }
+
     fn get_witness_sigop_count(tx: &Transaction, previous_outputs: &[&TxOut]) -> usize {
         let mut n = 0;
+        for (input, prevout) in tx.input.iter().zip(previous_outputs.iter()) {
+            n += input_witness_sigop_count(input, prevout);
+        }
+        n
+    }
 
+    fn input_witness_sigop_count(input: &TxIn, prevout: &TxOut) -> usize {
         #[inline]
         fn is_push_only(script: &script::Script) -> bool {
             for inst in script.instructions() {
@@ -261,41 +380,30 @@ pub(super) mod sigops {
             }
         }
 
-        #[inline]
-        fn count_with_prevout(
-            prevout: &TxOut,
-            script_sig: &script::Script,
-            witness: &Witness,
-        ) -> usize {
-            let mut n = 0;
-
-            let script = if prevout.script_pubkey.is_witness_program() {
-                prevout.script_pubkey.clone()
-            } else if prevout.script_pubkey.is_p2sh()
-                && is_push_only(script_sig)
-                && !script_sig.is_empty()
-            {
-                script::Script::from_byte_iter(
-                    last_pushdata(script_sig).unwrap().iter().map(|v| Ok(*v)),
-                )
-                .unwrap()
-            } else {
-                return 0;
-            };
-
-            if script.is_v0_p2wsh() {
-                let bytes = script.as_bytes();
-                n += sig_ops(witness, bytes[0], &bytes[2..]);
-            } else if script.is_v0_p2wpkh() {
-                n += 1;
-            }
-            n
-        }
+        let script_sig = &input.script_sig;
+        let witness: &Witness = &input.witness;
+        let script = if prevout.script_pubkey.is_witness_program() {
+            prevout.script_pubkey.clone()
+        } else if prevout.script_pubkey.is_p2sh()
+            && is_push_only(script_sig)
+            && !script_sig.is_empty()
+        {
+            script::Script::from_byte_iter(
+                last_pushdata(script_sig).unwrap().iter().map(|v| Ok(*v)),
+            )
+            .unwrap()
+        } else {
+            return 0;
+        };
 
-        for (input, prevout) in tx.input.iter().zip(previous_outputs.iter()) {
-            n += count_with_prevout(prevout, &input.script_sig, &input.witness);
+        if script.is_v0_p2wsh() {
+            let bytes = script.as_bytes();
+            sig_ops(witness, bytes[0], &bytes[2..])
+        } else if script.is_v0_p2wpkh() {
+            1
+        } else {
+            0
         }
-        n
     }
 
     /// Get the sigop cost for this transaction.
@@ -350,3 +458,105 @@ pub(super) mod sigops {
         }
     }
 }
+
+#[cfg(all(test, not(feature = "liquid")))]
+mod tests {
+    use super::raw_tx_output_count;
+    use super::sigops::{sigop_cost_breakdown, transaction_sigop_count};
+    use crate::chain::{OutPoint, Script, Transaction, TxIn, TxOut, Witness};
+    use bitcoin::consensus::encode::serialize;
+    use std::collections::HashMap;
+
+    const PUBKEY: &str = "21030000000000000000000000000000000000000000000000000000000000000001";
+
+    fn script(hex: &str) -> Script {
+        Script::from(hex::decode(hex).unwrap())
+    }
+
+    fn tx(input: Vec<TxIn>, output: Vec<TxOut>) -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: 0,
+            input,
+            output,
+        }
+    }
+
+    fn txin(script_sig: Script, witness: Vec<Vec<u8>>) -> TxIn {
+        TxIn {
+            previous_output: OutPoint {
+                txid: "0000000000000000000000000000000000000000000000000000000000000001"
+                    .parse()
+                    .unwrap(),
+                vout: 0,
+            },
+            script_sig,
+            sequence: 0xffffffff,
+            witness: Witness::from_vec(witness),
+        }
+    }
+
+    fn txout(script_pubkey: Script) -> TxOut {
+        TxOut {
+            value: 1000,
+            script_pubkey,
+        }
+    }
+
+    #[test]
+    fn test_raw_tx_output_count() {
+        let outputs = vec![txout(script("51")); 3];
+        let legacy = tx(vec![txin(script("00"), vec![]); 2], outputs.clone());
+        let segwit = tx(vec![txin(script(""), vec![vec![0x30; 71]])], outputs);
+
+        assert_eq!(raw_tx_output_count(&serialize(&legacy)), Some(3));
+        assert_eq!(raw_tx_output_count(&serialize(&segwit)), Some(3));
+        assert_eq!(raw_tx_output_count(&serialize(&legacy)[..10]), None);
+    }
+
+    #[test]
+    fn test_sigop_breakdown_coinbase() {
+        let mut input = txin(script("03010203ac"), vec![]);
+        input.previous_output = OutPoint::null();
+        let tx = tx(vec![input], vec![txout(script(&format!("{}ac", PUBKEY)))]);
+
+        // coinbase inputs only count their scriptSig, without any prevout
+        let (inputs, outputs) = sigop_cost_breakdown(&tx, &HashMap::new()).unwrap();
+        assert_eq!(inputs, vec![4]);
+        assert_eq!(outputs, vec![4]);
+        assert_eq!(transaction_sigop_count(&tx, &HashMap::new()).unwrap(), 8);
+    }
+
+    #[test]
+    fn test_sigop_breakdown_bare_multisig() {
+        let multisig = script(&format!("51{}51ae", PUBKEY));
+        let prevout = txout(multisig.clone());
+        let tx = tx(vec![txin(script("00"), vec![])], vec![txout(multisig)]);
+        let prevouts: HashMap<u32, &TxOut> = vec![(0, &prevout)].into_iter().collect();
+
+        // bare multisig outputs count the maximum of 20 sigops, regardless of their pubkeys
+        let (inputs, outputs) = sigop_cost_breakdown(&tx, &prevouts).unwrap();
+        assert_eq!(inputs, vec![0]);
+        assert_eq!(outputs, vec![80]);
+        assert_eq!(transaction_sigop_count(&tx, &prevouts).unwrap(), 80);
+    }
+
+    #[test]
+    fn test_sigop_breakdown_p2sh_p2wsh() {
+        let witness_script = hex::decode(format!("51{}51ae", PUBKEY)).unwrap();
+        let witness_program = format!("0020{}", "ab".repeat(32));
+        let prevout = txout(script(&format!("a914{}87", "cd".repeat(20))));
+        let input = txin(
+            script(&format!("22{}", witness_program)),
+            vec![vec![], vec![0x30; 71], witness_script],
+        );
+        let tx = tx(vec![input], vec![txout(script(&witness_program))]);
+        let prevouts: HashMap<u32, &TxOut> = vec![(0, &prevout)].into_iter().collect();
+
+        // the sigops come from the witness script, counted accurately and without scaling
+        let (inputs, outputs) = sigop_cost_breakdown(&tx, &prevouts).unwrap();
+        assert_eq!(inputs, vec![1]);
+        assert_eq!(outputs, vec![0]);
+        assert_eq!(transaction_sigop_count(&tx, &prevouts).unwrap(), 1);
+    }
+}